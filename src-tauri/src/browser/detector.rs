@@ -1,122 +1,295 @@
-use super::Browser;
+use super::{version, Browser};
 
+#[cfg(target_os = "linux")]
+use super::linux;
 #[cfg(target_os = "windows")]
-use super::registry::{get_chrome_from_registry, get_edge_from_registry};
+use super::registry::get_app_path_from_registry;
+#[cfg(target_os = "linux")]
+use crate::proxy::helper::find_program;
+
+/// Where a browser installs itself on each platform. Only the current
+/// platform's fields are read.
+#[allow(dead_code)]
+struct KnownBrowser {
+    id: &'static str,
+    name: &'static str,
+    /// Relative to Program Files, Program Files (x86) or the local app data
+    /// directory, which per-user installs use.
+    windows: &'static [&'static str],
+    /// The executable the browser registers under `App Paths`, to find
+    /// installs elsewhere. Left out for channels sharing another's name.
+    windows_app_path: Option<&'static str>,
+    macos: &'static [&'static str],
+    /// Executable names, looked up in `PATH` and `.desktop` entries.
+    linux: &'static [&'static str],
+    flatpak: Option<&'static str>,
+    snap: Option<&'static str>,
+}
+
+/// Browsers in the order they are listed. Each channel gets its own ID, so
+/// sites can be pinned to one.
+const KNOWN_BROWSERS: &[KnownBrowser] = &[
+    KnownBrowser {
+        id: "chrome",
+        name: "Google Chrome",
+        windows: &[r"Google\Chrome\Application\chrome.exe"],
+        windows_app_path: Some("chrome.exe"),
+        macos: &["/Applications/Google Chrome.app/Contents/MacOS/Google Chrome"],
+        linux: &["google-chrome", "google-chrome-stable"],
+        flatpak: Some("com.google.Chrome"),
+        snap: None,
+    },
+    KnownBrowser {
+        id: "chrome-beta",
+        name: "Google Chrome Beta",
+        windows: &[r"Google\Chrome Beta\Application\chrome.exe"],
+        windows_app_path: None,
+        macos: &["/Applications/Google Chrome Beta.app/Contents/MacOS/Google Chrome Beta"],
+        linux: &["google-chrome-beta"],
+        flatpak: None,
+        snap: None,
+    },
+    KnownBrowser {
+        id: "chrome-dev",
+        name: "Google Chrome Dev",
+        windows: &[r"Google\Chrome Dev\Application\chrome.exe"],
+        windows_app_path: None,
+        macos: &["/Applications/Google Chrome Dev.app/Contents/MacOS/Google Chrome Dev"],
+        linux: &["google-chrome-unstable"],
+        flatpak: Some("com.google.ChromeDev"),
+        snap: None,
+    },
+    KnownBrowser {
+        id: "chrome-canary",
+        name: "Google Chrome Canary",
+        windows: &[r"Google\Chrome SxS\Application\chrome.exe"],
+        windows_app_path: None,
+        macos: &["/Applications/Google Chrome Canary.app/Contents/MacOS/Google Chrome Canary"],
+        linux: &["google-chrome-canary"],
+        flatpak: None,
+        snap: None,
+    },
+    KnownBrowser {
+        id: "chromium",
+        name: "Chromium",
+        windows: &[r"Chromium\Application\chrome.exe"],
+        windows_app_path: None,
+        macos: &["/Applications/Chromium.app/Contents/MacOS/Chromium"],
+        linux: &["chromium", "chromium-browser"],
+        flatpak: Some("org.chromium.Chromium"),
+        snap: Some("chromium"),
+    },
+    // Only Linux packages give it a name of its own; elsewhere it installs
+    // as Chromium and is detected as such
+    KnownBrowser {
+        id: "ungoogled-chromium",
+        name: "ungoogled-chromium",
+        windows: &[],
+        windows_app_path: None,
+        macos: &[],
+        linux: &["ungoogled-chromium"],
+        flatpak: Some("io.github.ungoogled_software.ungoogled_chromium"),
+        snap: None,
+    },
+    KnownBrowser {
+        id: "edge",
+        name: "Microsoft Edge",
+        windows: &[r"Microsoft\Edge\Application\msedge.exe"],
+        windows_app_path: Some("msedge.exe"),
+        macos: &["/Applications/Microsoft Edge.app/Contents/MacOS/Microsoft Edge"],
+        linux: &["microsoft-edge", "microsoft-edge-stable"],
+        flatpak: Some("com.microsoft.Edge"),
+        snap: None,
+    },
+    KnownBrowser {
+        id: "edge-beta",
+        name: "Microsoft Edge Beta",
+        windows: &[r"Microsoft\Edge Beta\Application\msedge.exe"],
+        windows_app_path: None,
+        macos: &["/Applications/Microsoft Edge Beta.app/Contents/MacOS/Microsoft Edge Beta"],
+        linux: &["microsoft-edge-beta"],
+        flatpak: None,
+        snap: None,
+    },
+    KnownBrowser {
+        id: "edge-dev",
+        name: "Microsoft Edge Dev",
+        windows: &[r"Microsoft\Edge Dev\Application\msedge.exe"],
+        windows_app_path: None,
+        macos: &["/Applications/Microsoft Edge Dev.app/Contents/MacOS/Microsoft Edge Dev"],
+        linux: &["microsoft-edge-dev"],
+        flatpak: None,
+        snap: None,
+    },
+    KnownBrowser {
+        id: "brave",
+        name: "Brave",
+        windows: &[r"BraveSoftware\Brave-Browser\Application\brave.exe"],
+        windows_app_path: Some("brave.exe"),
+        macos: &["/Applications/Brave Browser.app/Contents/MacOS/Brave Browser"],
+        linux: &["brave-browser", "brave-browser-stable", "brave"],
+        flatpak: Some("com.brave.Browser"),
+        snap: Some("brave"),
+    },
+    KnownBrowser {
+        id: "vivaldi",
+        name: "Vivaldi",
+        windows: &[r"Vivaldi\Application\vivaldi.exe"],
+        windows_app_path: Some("vivaldi.exe"),
+        macos: &["/Applications/Vivaldi.app/Contents/MacOS/Vivaldi"],
+        linux: &["vivaldi", "vivaldi-stable"],
+        flatpak: Some("com.vivaldi.Vivaldi"),
+        snap: Some("vivaldi"),
+    },
+    KnownBrowser {
+        id: "opera",
+        name: "Opera",
+        windows: &[r"Programs\Opera\opera.exe", r"Opera\opera.exe"],
+        windows_app_path: Some("opera.exe"),
+        macos: &["/Applications/Opera.app/Contents/MacOS/Opera"],
+        linux: &["opera"],
+        flatpak: Some("com.opera.Opera"),
+        snap: Some("opera"),
+    },
+    KnownBrowser {
+        id: "firefox",
+        name: "Mozilla Firefox",
+        windows: &[r"Mozilla Firefox\firefox.exe"],
+        windows_app_path: Some("firefox.exe"),
+        macos: &["/Applications/Firefox.app/Contents/MacOS/firefox"],
+        linux: &["firefox", "firefox-esr"],
+        flatpak: Some("org.mozilla.firefox"),
+        snap: Some("firefox"),
+    },
+    KnownBrowser {
+        id: "librewolf",
+        name: "LibreWolf",
+        windows: &[r"LibreWolf\librewolf.exe"],
+        windows_app_path: None,
+        macos: &["/Applications/LibreWolf.app/Contents/MacOS/librewolf"],
+        linux: &["librewolf"],
+        flatpak: Some("io.gitlab.librewolf-community"),
+        snap: None,
+    },
+];
 
 pub fn detect_all_browsers() -> Vec<Browser> {
-    let mut browsers = Vec::new();
-    
-    // Detect Chrome
-    if let Some(chrome_path) = detect_chrome() {
-        browsers.push(Browser {
-            id: "chrome".to_string(),
-            name: "Google Chrome".to_string(),
-            path: chrome_path,
-        });
-    }
-    
-    // Detect Edge
-    if let Some(edge_path) = detect_edge() {
-        browsers.push(Browser {
-            id: "edge".to_string(),
-            name: "Microsoft Edge".to_string(),
-            path: edge_path,
-        });
-    }
-    
+    let paths: Vec<Option<String>> = KNOWN_BROWSERS.iter().map(installed_path).collect();
+    #[cfg(target_os = "windows")]
+    let paths = with_registry_paths(paths);
+
+    let mut browsers: Vec<Browser> = KNOWN_BROWSERS
+        .iter()
+        .zip(paths)
+        .flat_map(|(known, path)| {
+            let installed = path.map(|path| Browser {
+                id: known.id.to_string(),
+                name: known.name.to_string(),
+                path,
+                ..Default::default()
+            });
+            installed.into_iter().chain(packaged_installs(known))
+        })
+        .collect();
+
+    version::refresh_all(&mut browsers);
     browsers
 }
 
-fn detect_chrome() -> Option<String> {
+/// The first of the browser's usual install paths that exists.
+fn installed_path(known: &KnownBrowser) -> Option<String> {
     #[cfg(target_os = "windows")]
     {
-        // Try common Chrome installation paths on Windows
-        let possible_paths = [
-            r"C:\Program Files\Google\Chrome\Application\chrome.exe",
-            r"C:\Program Files (x86)\Google\Chrome\Application\chrome.exe",
-        ];
-        
-        for path in &possible_paths {
-            if std::path::Path::new(path).exists() {
-                return Some(path.to_string());
-            }
-        }
-        
-        // Try to get from Windows Registry
-        if let Ok(chrome_path) = get_chrome_from_registry() {
-            return Some(chrome_path);
-        }
+        ["ProgramFiles", "ProgramFiles(x86)", "LOCALAPPDATA"]
+            .iter()
+            .filter_map(std::env::var_os)
+            .flat_map(|root| {
+                known
+                    .windows
+                    .iter()
+                    .map(move |path| std::path::Path::new(&root).join(path))
+            })
+            .find(|path| path.exists())
+            .map(|path| path.to_string_lossy().to_string())
     }
-    
+
     #[cfg(target_os = "macos")]
     {
-        let path = "/Applications/Google Chrome.app/Contents/MacOS/Google Chrome";
-        if std::path::Path::new(path).exists() {
-            return Some(path.to_string());
-        }
+        known
+            .macos
+            .iter()
+            .find(|path| std::path::Path::new(path).exists())
+            .map(|path| path.to_string())
     }
-    
+
     #[cfg(target_os = "linux")]
     {
-        let possible_paths = [
-            "/usr/bin/google-chrome",
-            "/usr/bin/google-chrome-stable",
-            "/usr/bin/chromium-browser",
-            "/usr/bin/chromium",
-        ];
-        
-        for path in &possible_paths {
-            if std::path::Path::new(path).exists() {
-                return Some(path.to_string());
-            }
-        }
+        linux::find_in_path(known.linux)
+            .or_else(|| linux::find_in_desktop_entries(known.linux))
+            .map(|path| path.to_string_lossy().to_string())
     }
-    
-    None
-}
 
-fn detect_edge() -> Option<String> {
-    #[cfg(target_os = "windows")]
+    #[cfg(not(any(target_os = "windows", target_os = "macos", target_os = "linux")))]
     {
-        // Try common Edge installation paths on Windows
-        let possible_paths = [
-            r"C:\Program Files (x86)\Microsoft\Edge\Application\msedge.exe",
-            r"C:\Program Files\Microsoft\Edge\Application\msedge.exe",
-        ];
-        
-        for path in &possible_paths {
-            if std::path::Path::new(path).exists() {
-                return Some(path.to_string());
-            }
-        }
-        
-        // Try to get from Windows Registry
-        if let Ok(edge_path) = get_edge_from_registry() {
-            return Some(edge_path);
-        }
+        let _ = known;
+        None
     }
-    
-    #[cfg(target_os = "macos")]
-    {
-        let path = "/Applications/Microsoft Edge.app/Contents/MacOS/Microsoft Edge";
-        if std::path::Path::new(path).exists() {
-            return Some(path.to_string());
-        }
+}
+
+/// Flatpak and Snap installs, listed apart from a native install under IDs
+/// of their own. Flatpak apps run through `flatpak run`, while snaps have
+/// a command of their own.
+#[cfg(target_os = "linux")]
+fn packaged_installs(known: &KnownBrowser) -> Vec<Browser> {
+    let mut browsers = Vec::new();
+
+    let flatpak = known
+        .flatpak
+        .filter(|app_id| linux::flatpak_installed(app_id))
+        .zip(find_program("flatpak"));
+    if let Some((app_id, flatpak)) = flatpak {
+        browsers.push(Browser {
+            id: format!("{}-flatpak", known.id),
+            name: format!("{} (Flatpak)", known.name),
+            path: flatpak.to_string_lossy().to_string(),
+            args: vec!["run".to_string(), app_id.to_string()],
+            ..Default::default()
+        });
     }
-    
-    #[cfg(target_os = "linux")]
-    {
-        let possible_paths = [
-            "/usr/bin/microsoft-edge",
-            "/usr/bin/microsoft-edge-stable",
-        ];
-        
-        for path in &possible_paths {
-            if std::path::Path::new(path).exists() {
-                return Some(path.to_string());
+    if let Some(path) = known.snap.and_then(linux::find_snap) {
+        browsers.push(Browser {
+            id: format!("{}-snap", known.id),
+            name: format!("{} (Snap)", known.name),
+            path: path.to_string_lossy().to_string(),
+            ..Default::default()
+        });
+    }
+
+    browsers
+}
+
+#[cfg(not(target_os = "linux"))]
+fn packaged_installs(_known: &KnownBrowser) -> Vec<Browser> {
+    Vec::new()
+}
+
+/// Fills in browsers installed outside the usual directories from the
+/// registry, unless the path found belongs to a browser already listed.
+#[cfg(target_os = "windows")]
+fn with_registry_paths(mut paths: Vec<Option<String>>) -> Vec<Option<String>> {
+    for (index, known) in KNOWN_BROWSERS.iter().enumerate() {
+        let Some(app_path) = known.windows_app_path.filter(|_| paths[index].is_none()) else {
+            continue;
+        };
+        if let Ok(path) = get_app_path_from_registry(app_path) {
+            if !paths
+                .iter()
+                .flatten()
+                .any(|found| found.eq_ignore_ascii_case(&path))
+            {
+                paths[index] = Some(path);
             }
         }
     }
-    
-    None
-}
\ No newline at end of file
+    paths
+}
@@ -0,0 +1,413 @@
+use super::firefox;
+use crate::proxy::{bypass, dns};
+use crate::settings::ProxyKind;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Groups browsers by the command line they take.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum BrowserFamily {
+    Chromium,
+    Firefox,
+    /// WebKit and other browsers, which only take a URL.
+    Other,
+}
+
+impl BrowserFamily {
+    /// Recognizes a browser by its ID, which packaged installs extend with a
+    /// suffix such as `-flatpak`, or by its executable name. Unknown browsers
+    /// are assumed to be Chromium-based, like most of them are.
+    pub fn detect(id: &str, path: &str) -> Self {
+        let executable = Path::new(path)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_ascii_lowercase())
+            .unwrap_or_default();
+        let is_any = |names: &[&str]| {
+            names.iter().any(|name| {
+                executable == *name
+                    || id
+                        .strip_prefix(name)
+                        .is_some_and(|rest| rest.is_empty() || rest.starts_with('-'))
+            })
+        };
+
+        if is_any(&["firefox", "firefox-esr", "librewolf", "waterfox", "floorp"]) {
+            BrowserFamily::Firefox
+        } else if is_any(&["safari", "epiphany"]) {
+            BrowserFamily::Other
+        } else {
+            BrowserFamily::Chromium
+        }
+    }
+
+    pub fn launcher(&self) -> &'static dyn BrowserLauncher {
+        match self {
+            BrowserFamily::Chromium => &ChromiumLauncher,
+            BrowserFamily::Firefox => &FirefoxLauncher,
+            BrowserFamily::Other => &OtherLauncher,
+        }
+    }
+}
+
+/// Where the browser sends its traffic.
+#[derive(Debug, Clone, Copy)]
+pub enum ProxyRoute<'a> {
+    /// A proxy URL such as `socks5://127.0.0.1:1080`.
+    Server(&'a str),
+    /// A PAC script URL.
+    Pac(&'a str),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ProxyOptions<'a> {
+    pub route: ProxyRoute<'a>,
+    /// Rules in `--proxy-bypass-list` syntax.
+    pub bypass: &'a [String],
+    pub remote_dns: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum WindowMode {
+    #[default]
+    Tab,
+    Window,
+}
+
+/// What a launch asks of the browser, whatever its family.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LaunchOptions<'a> {
+    pub profile_dir: Option<&'a Path>,
+    pub proxy: Option<ProxyOptions<'a>>,
+    pub url: Option<&'a str>,
+    pub ignore_cert_errors: bool,
+    pub window: WindowMode,
+}
+
+/// Turns launch options into the command line of one browser family.
+pub trait BrowserLauncher: Sync {
+    /// The directory, under `Browser::profiles_root`, holding the profiles
+    /// of proxied launches.
+    fn profiles_dir(&self) -> &'static str {
+        "profiles"
+    }
+
+    /// Whether a proxy of this kind has to be reached through the local
+    /// forwarder because the browser cannot use it directly.
+    fn needs_forwarder(&self, kind: &ProxyKind) -> bool;
+
+    /// Writes the settings the browser reads from its profile rather than
+    /// its command line.
+    fn prepare_profile(&self, _options: &LaunchOptions) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn args(&self, options: &LaunchOptions) -> Result<Vec<String>, String>;
+}
+
+pub struct ChromiumLauncher;
+
+impl BrowserLauncher for ChromiumLauncher {
+    /// Chromium ignores credentials in --proxy-server, has no SOCKS auth at
+    /// all and no SOCKS4a.
+    fn needs_forwarder(&self, kind: &ProxyKind) -> bool {
+        match kind {
+            ProxyKind::Socks4a(_) => true,
+            ProxyKind::Http(server)
+            | ProxyKind::Https(server)
+            | ProxyKind::Socks4(server)
+            | ProxyKind::Socks5(server) => server.credentials().is_some(),
+            _ => false,
+        }
+    }
+
+    fn args(&self, options: &LaunchOptions) -> Result<Vec<String>, String> {
+        let mut args = vec![match options.window {
+            WindowMode::Tab => "--new-tab".to_string(),
+            WindowMode::Window => "--new-window".to_string(),
+        }];
+
+        if options.ignore_cert_errors {
+            args.extend(
+                [
+                    "--ignore-certificate-errors",
+                    "--ignore-ssl-errors",
+                    "--ignore-certificate-errors-spki-list",
+                    "--ignore-certificate-errors-ssl-invalid",
+                    "--allow-running-insecure-content",
+                ]
+                .map(str::to_string),
+            );
+        }
+        if let Some(profile_dir) = options.profile_dir {
+            args.push(format!("--user-data-dir={}", profile_dir.to_string_lossy()));
+        }
+
+        match options.proxy {
+            Some(ProxyOptions {
+                route: ProxyRoute::Pac(url),
+                ..
+            }) => args.push(format!("--proxy-pac-url={}", url)),
+            Some(ProxyOptions {
+                route: ProxyRoute::Server(server),
+                bypass,
+                remote_dns,
+            }) => {
+                args.push(format!("--proxy-server={}", server));
+                if let Some(bypass_list) = bypass::to_flag_value(bypass) {
+                    args.push(format!("--proxy-bypass-list={}", bypass_list));
+                }
+                if remote_dns {
+                    args.extend(dns::remote_dns_args(server, bypass));
+                }
+            }
+            None => {}
+        }
+
+        // Otherwise the browser opens to its home page
+        args.extend(options.url.map(str::to_string));
+        Ok(args)
+    }
+}
+
+pub struct FirefoxLauncher;
+
+impl BrowserLauncher for FirefoxLauncher {
+    /// Kept apart so a profile name used with both families never shares
+    /// a directory.
+    fn profiles_dir(&self) -> &'static str {
+        "firefox-profiles"
+    }
+
+    /// Firefox prompts for HTTP proxy credentials, has no SOCKS auth, no
+    /// SOCKS4a, and only reaches HTTPS proxies from PAC scripts.
+    fn needs_forwarder(&self, kind: &ProxyKind) -> bool {
+        match kind {
+            ProxyKind::Https(_) | ProxyKind::Socks4a(_) => true,
+            ProxyKind::Http(server) | ProxyKind::Socks4(server) | ProxyKind::Socks5(server) => {
+                server.credentials().is_some()
+            }
+            _ => false,
+        }
+    }
+
+    fn prepare_profile(&self, options: &LaunchOptions) -> Result<(), String> {
+        match (options.profile_dir, &options.proxy) {
+            (Some(profile_dir), Some(proxy)) => {
+                firefox::write_user_js(profile_dir, proxy, options.ignore_cert_errors)
+            }
+            (None, Some(_)) => Err("Firefox needs a dedicated profile to use a proxy".to_string()),
+            // Certificate preferences also live in the profile, so without
+            // a dedicated one they are left alone
+            (_, None) => Ok(()),
+        }
+    }
+
+    /// Proxy and certificate settings come from the profile's `user.js`.
+    fn args(&self, options: &LaunchOptions) -> Result<Vec<String>, String> {
+        let mut args = Vec::new();
+        if let Some(profile_dir) = options.profile_dir {
+            args.push("-profile".to_string());
+            args.push(profile_dir.to_string_lossy().to_string());
+        }
+
+        let window = match options.window {
+            WindowMode::Tab => "-new-tab",
+            WindowMode::Window => "-new-window",
+        };
+        match options.url {
+            Some(url) => args.extend([window.to_string(), url.to_string()]),
+            None if options.window == WindowMode::Window => args.push(window.to_string()),
+            None => {}
+        }
+        Ok(args)
+    }
+}
+
+pub struct OtherLauncher;
+
+impl BrowserLauncher for OtherLauncher {
+    fn needs_forwarder(&self, _kind: &ProxyKind) -> bool {
+        false
+    }
+
+    /// Only the URL can be passed on; the system decides the rest.
+    fn args(&self, options: &LaunchOptions) -> Result<Vec<String>, String> {
+        if options.proxy.is_some() {
+            return Err("This browser cannot be launched with a proxy".to_string());
+        }
+        Ok(options.url.map(str::to_string).into_iter().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn proxied<'a>(route: ProxyRoute<'a>, bypass: &'a [String]) -> LaunchOptions<'a> {
+        LaunchOptions {
+            profile_dir: Some(Path::new("/profiles/work")),
+            proxy: Some(ProxyOptions {
+                route,
+                bypass,
+                remote_dns: false,
+            }),
+            url: Some("https://example.com/"),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn detects_families() {
+        assert_eq!(
+            BrowserFamily::detect("chrome", "/usr/bin/google-chrome"),
+            BrowserFamily::Chromium
+        );
+        assert_eq!(
+            BrowserFamily::detect("custom", "/opt/firefox/firefox"),
+            BrowserFamily::Firefox
+        );
+        assert_eq!(
+            BrowserFamily::detect("librewolf", "/usr/bin/librewolf"),
+            BrowserFamily::Firefox
+        );
+        assert_eq!(
+            BrowserFamily::detect("firefox-flatpak", "/usr/bin/flatpak"),
+            BrowserFamily::Firefox
+        );
+        assert_eq!(
+            BrowserFamily::detect("web", "/usr/bin/epiphany"),
+            BrowserFamily::Other
+        );
+    }
+
+    #[test]
+    fn chromium_proxy_server() {
+        let bypass = vec!["*.corp".to_string(), "10.0.0.0/8".to_string()];
+        let args = ChromiumLauncher
+            .args(&proxied(
+                ProxyRoute::Server("socks5://127.0.0.1:1080"),
+                &bypass,
+            ))
+            .unwrap();
+        assert_eq!(
+            args,
+            [
+                "--new-tab",
+                "--user-data-dir=/profiles/work",
+                "--proxy-server=socks5://127.0.0.1:1080",
+                "--proxy-bypass-list=*.corp;10.0.0.0/8",
+                "https://example.com/",
+            ]
+        );
+    }
+
+    #[test]
+    fn chromium_pac_ignores_bypass() {
+        let bypass = vec!["*.corp".to_string()];
+        let args = ChromiumLauncher
+            .args(&proxied(
+                ProxyRoute::Pac("http://127.0.0.1:9000/rules.pac"),
+                &bypass,
+            ))
+            .unwrap();
+        assert_eq!(
+            args,
+            [
+                "--new-tab",
+                "--user-data-dir=/profiles/work",
+                "--proxy-pac-url=http://127.0.0.1:9000/rules.pac",
+                "https://example.com/",
+            ]
+        );
+    }
+
+    #[test]
+    fn chromium_remote_dns_and_cert_errors() {
+        let mut options = proxied(ProxyRoute::Server("socks5://proxy.example:1080"), &[]);
+        options.proxy = options.proxy.map(|proxy| ProxyOptions {
+            remote_dns: true,
+            ..proxy
+        });
+        options.ignore_cert_errors = true;
+        options.window = WindowMode::Window;
+
+        let args = ChromiumLauncher.args(&options).unwrap();
+        assert_eq!(
+            args,
+            [
+                "--new-window",
+                "--ignore-certificate-errors",
+                "--ignore-ssl-errors",
+                "--ignore-certificate-errors-spki-list",
+                "--ignore-certificate-errors-ssl-invalid",
+                "--allow-running-insecure-content",
+                "--user-data-dir=/profiles/work",
+                "--proxy-server=socks5://proxy.example:1080",
+                "--host-resolver-rules=MAP * ~NOTFOUND , EXCLUDE localhost , EXCLUDE proxy.example",
+                "--force-webrtc-ip-handling-policy=disable_non_proxied_udp",
+                "--dns-prefetch-disable",
+                "https://example.com/",
+            ]
+        );
+    }
+
+    #[test]
+    fn chromium_without_proxy() {
+        let args = ChromiumLauncher.args(&LaunchOptions::default()).unwrap();
+        assert_eq!(args, ["--new-tab"]);
+    }
+
+    #[test]
+    fn firefox_uses_profile_for_proxy() {
+        let args = FirefoxLauncher
+            .args(&proxied(ProxyRoute::Server("http://127.0.0.1:8080"), &[]))
+            .unwrap();
+        assert_eq!(
+            args,
+            [
+                "-profile",
+                "/profiles/work",
+                "-new-tab",
+                "https://example.com/"
+            ]
+        );
+    }
+
+    #[test]
+    fn firefox_window_without_url() {
+        let options = LaunchOptions {
+            window: WindowMode::Window,
+            ignore_cert_errors: true,
+            ..Default::default()
+        };
+        assert_eq!(FirefoxLauncher.args(&options).unwrap(), ["-new-window"]);
+        assert!(FirefoxLauncher
+            .args(&LaunchOptions::default())
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn firefox_proxy_needs_profile() {
+        let mut options = proxied(ProxyRoute::Server("http://127.0.0.1:8080"), &[]);
+        options.profile_dir = None;
+        assert!(FirefoxLauncher.prepare_profile(&options).is_err());
+    }
+
+    #[test]
+    fn other_passes_only_url() {
+        let options = LaunchOptions {
+            url: Some("https://example.com/"),
+            ignore_cert_errors: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            OtherLauncher.args(&options).unwrap(),
+            ["https://example.com/"]
+        );
+        assert!(OtherLauncher
+            .args(&proxied(ProxyRoute::Server("http://127.0.0.1:8080"), &[]))
+            .is_err());
+    }
+}
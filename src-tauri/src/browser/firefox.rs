@@ -0,0 +1,246 @@
+use super::family::{ProxyOptions, ProxyRoute};
+use crate::proxy::bypass::BypassRule;
+use reqwest::Url;
+use serde_json::{json, Value};
+use std::path::Path;
+
+/// Firefox reads proxy settings from its profile rather than the command
+/// line, so every proxied launch rewrites the profile's `user.js`, which
+/// Firefox applies over its saved preferences on startup.
+pub fn write_user_js(
+    profile_dir: &Path,
+    proxy: &ProxyOptions,
+    ignore_cert_errors: bool,
+) -> Result<(), String> {
+    let prefs = user_prefs(proxy, ignore_cert_errors)?;
+    let contents: String = prefs
+        .iter()
+        .map(|(name, value)| format!("user_pref({}, {});\n", json!(name), value))
+        .collect();
+
+    std::fs::create_dir_all(profile_dir)
+        .map_err(|e| format!("Failed to create profile directory: {}", e))?;
+    std::fs::write(profile_dir.join("user.js"), contents)
+        .map_err(|e| format!("Failed to write Firefox preferences: {}", e))
+}
+
+fn user_prefs(
+    proxy: &ProxyOptions,
+    ignore_cert_errors: bool,
+) -> Result<Vec<(&'static str, Value)>, String> {
+    let ProxyOptions {
+        route,
+        bypass,
+        remote_dns,
+    } = *proxy;
+    let mut prefs = vec![
+        // A dedicated profile should not ask to become the default browser
+        ("browser.shell.checkDefaultBrowser", json!(false)),
+        ("browser.aboutwelcome.enabled", json!(false)),
+    ];
+
+    // user.js values stick in prefs.js after the browser exits, so every
+    // managed pref is written on each launch, reset when a route or option
+    // does not use it
+    let mut autoconfig_url = "";
+    let mut http = (String::new(), 0);
+    let mut socks = (String::new(), 0);
+    let mut socks_version = 5;
+    let proxy_type = match route {
+        ProxyRoute::Pac(url) => {
+            autoconfig_url = url;
+            2
+        }
+        ProxyRoute::Server(server) => {
+            let url = Url::parse(server).map_err(|e| format!("Invalid proxy {}: {}", server, e))?;
+            let host = url
+                .host_str()
+                .ok_or_else(|| format!("Proxy {} has no host", server))?
+                .trim_matches(|c| c == '[' || c == ']')
+                .to_string();
+            let port = url
+                .port()
+                .ok_or_else(|| format!("Proxy {} has no port", server))?;
+
+            match url.scheme() {
+                "http" => http = (host, port),
+                "socks4" | "socks5" => {
+                    socks_version = if url.scheme() == "socks4" { 4 } else { 5 };
+                    socks = (host, port);
+                }
+                // HTTPS proxies only work from PAC scripts in Firefox, so
+                // they are reached through the local forwarder instead
+                scheme => return Err(format!("Firefox cannot use {} proxies directly", scheme)),
+            }
+            1
+        }
+    };
+
+    prefs.extend([
+        ("network.proxy.type", json!(proxy_type)),
+        ("network.proxy.autoconfig_url", json!(autoconfig_url)),
+        ("network.proxy.http", json!(http.0)),
+        ("network.proxy.http_port", json!(http.1)),
+        ("network.proxy.ssl", json!(http.0)),
+        ("network.proxy.ssl_port", json!(http.1)),
+        ("network.proxy.socks", json!(socks.0)),
+        ("network.proxy.socks_port", json!(socks.1)),
+        ("network.proxy.socks_version", json!(socks_version)),
+        ("network.proxy.socks_remote_dns", json!(remote_dns)),
+        ("network.proxy.share_proxy_settings", json!(false)),
+    ]);
+
+    let (no_proxies_on, proxy_localhost) = bypass_prefs(bypass);
+    prefs.push(("network.proxy.no_proxies_on", json!(no_proxies_on)));
+    prefs.push((
+        "network.proxy.allow_hijacking_localhost",
+        json!(proxy_localhost),
+    ));
+
+    // The counterparts of the Chromium flags that keep lookups on the proxy
+    prefs.push(("network.dns.disablePrefetch", json!(remote_dns)));
+    prefs.push(("network.predictor.enabled", json!(!remote_dns)));
+    prefs.push(("media.peerconnection.ice.proxy_only", json!(remote_dns)));
+
+    // Firefox has no preference that skips certificate errors outright.
+    // These stop pinning and HSTS from hiding the exception button, which
+    // the error page then offers right away, and allow mixed content.
+    // Otherwise they go back to Firefox's defaults.
+    let ignore = ignore_cert_errors;
+    prefs.extend([
+        (
+            "security.cert_pinning.enforcement_level",
+            json!(if ignore { 0 } else { 1 }),
+        ),
+        (
+            "network.stricttransportsecurity.preloadlist",
+            json!(!ignore),
+        ),
+        ("browser.xul.error_pages.expert_bad_cert", json!(ignore)),
+        (
+            "security.mixed_content.block_active_content",
+            json!(!ignore),
+        ),
+        ("security.mixed_content.block_display_content", json!(false)),
+    ]);
+
+    Ok(prefs)
+}
+
+/// Translates Chromium bypass rules into `network.proxy.no_proxies_on`,
+/// and whether `<-loopback>` asks for localhost to be proxied too.
+fn bypass_prefs(bypass: &[String]) -> (String, bool) {
+    let mut entries = Vec::new();
+    let mut proxy_localhost = false;
+
+    for rule in bypass {
+        match BypassRule::parse(rule) {
+            Ok(BypassRule::Local) => entries.push("<local>".to_string()),
+            Ok(BypassRule::SubtractImplicit) => proxy_localhost = true,
+            Ok(BypassRule::Network {
+                address,
+                prefix_len,
+            }) => entries.push(format!("{}/{}", address, prefix_len)),
+            // Firefox matches a leading dot against every subdomain and has
+            // no per-scheme rules, so those apply to all schemes
+            Ok(BypassRule::Host { pattern, port, .. }) => {
+                let host = match pattern.strip_prefix("*.") {
+                    Some(domain) => format!(".{}", domain),
+                    None => pattern,
+                };
+                entries.push(match port {
+                    Some(port) => format!("{}:{}", host, port),
+                    None => host,
+                });
+            }
+            Err(_) => {}
+        }
+    }
+
+    (entries.join(", "), proxy_localhost)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn prefs(route: ProxyRoute, ignore_cert_errors: bool) -> Vec<(&'static str, Value)> {
+        user_prefs(
+            &ProxyOptions {
+                route,
+                bypass: &[],
+                remote_dns: false,
+            },
+            ignore_cert_errors,
+        )
+        .unwrap()
+    }
+
+    fn pref(prefs: &[(&'static str, Value)], name: &str) -> Value {
+        prefs
+            .iter()
+            .find(|(pref, _)| *pref == name)
+            .map(|(_, value)| value.clone())
+            .unwrap_or_else(|| panic!("{} is not written", name))
+    }
+
+    #[test]
+    fn resets_http_prefs_for_socks_route() {
+        let http = prefs(ProxyRoute::Server("http://127.0.0.1:8080"), true);
+        let socks = prefs(ProxyRoute::Server("socks5://127.0.0.1:1080"), false);
+
+        // The same prefs are written either way, so none keeps a stale value
+        let names = |prefs: &[(&'static str, Value)]| {
+            let mut names: Vec<&str> = prefs.iter().map(|(name, _)| *name).collect();
+            names.sort_unstable();
+            names
+        };
+        assert_eq!(names(&http), names(&socks));
+
+        assert_eq!(pref(&http, "network.proxy.http"), json!("127.0.0.1"));
+        assert_eq!(pref(&http, "network.proxy.ssl_port"), json!(8080));
+        assert_eq!(pref(&http, "network.proxy.socks"), json!(""));
+        assert_eq!(
+            pref(&http, "security.cert_pinning.enforcement_level"),
+            json!(0)
+        );
+
+        assert_eq!(pref(&socks, "network.proxy.type"), json!(1));
+        assert_eq!(pref(&socks, "network.proxy.http"), json!(""));
+        assert_eq!(pref(&socks, "network.proxy.http_port"), json!(0));
+        assert_eq!(pref(&socks, "network.proxy.ssl"), json!(""));
+        assert_eq!(pref(&socks, "network.proxy.ssl_port"), json!(0));
+        assert_eq!(pref(&socks, "network.proxy.autoconfig_url"), json!(""));
+        assert_eq!(pref(&socks, "network.proxy.socks"), json!("127.0.0.1"));
+        assert_eq!(pref(&socks, "network.proxy.socks_port"), json!(1080));
+        assert_eq!(pref(&socks, "network.proxy.socks_version"), json!(5));
+        assert_eq!(
+            pref(&socks, "security.cert_pinning.enforcement_level"),
+            json!(1)
+        );
+        assert_eq!(
+            pref(&socks, "network.stricttransportsecurity.preloadlist"),
+            json!(true)
+        );
+        assert_eq!(
+            pref(&socks, "browser.xul.error_pages.expert_bad_cert"),
+            json!(false)
+        );
+        assert_eq!(
+            pref(&socks, "security.mixed_content.block_active_content"),
+            json!(true)
+        );
+    }
+
+    #[test]
+    fn resets_server_prefs_for_pac_route() {
+        let pac = prefs(ProxyRoute::Pac("http://127.0.0.1:9000/rules.pac"), false);
+        assert_eq!(pref(&pac, "network.proxy.type"), json!(2));
+        assert_eq!(
+            pref(&pac, "network.proxy.autoconfig_url"),
+            json!("http://127.0.0.1:9000/rules.pac")
+        );
+        assert_eq!(pref(&pac, "network.proxy.http"), json!(""));
+        assert_eq!(pref(&pac, "network.proxy.socks_port"), json!(0));
+    }
+}
@@ -0,0 +1,257 @@
+use std::path::{Path, PathBuf};
+
+/// Where Snap puts the commands of installed snaps.
+const SNAP_BIN_DIR: &str = "/snap/bin";
+
+/// Looks for the first of `names` in `PATH`. Snap and Flatpak commands are
+/// left out, as those installs are listed as packaged browsers.
+pub fn find_in_path(names: &[&str]) -> Option<PathBuf> {
+    let path = std::env::var_os("PATH").unwrap_or_else(|| "/usr/local/bin:/usr/bin:/bin".into());
+    let dirs: Vec<PathBuf> = std::env::split_paths(&path)
+        .filter(|dir| !is_packaged_bin_dir(dir))
+        .collect();
+
+    names
+        .iter()
+        .flat_map(|name| dirs.iter().map(move |dir| dir.join(name)))
+        .find(|candidate| candidate.is_file())
+}
+
+/// Looks for the first of `names` among the programs started by `.desktop`
+/// entries, which finds browsers installed outside `PATH`, e.g. in `/opt`.
+pub fn find_in_desktop_entries(names: &[&str]) -> Option<PathBuf> {
+    desktop_programs().into_iter().find(|program| {
+        program
+            .file_name()
+            .is_some_and(|name| names.iter().any(|wanted| name == *wanted))
+            && program.is_file()
+    })
+}
+
+/// The command of an installed snap.
+pub fn find_snap(name: &str) -> Option<PathBuf> {
+    let path = Path::new(SNAP_BIN_DIR).join(name);
+    path.exists().then_some(path)
+}
+
+/// The name of the snap whose command `program` is, e.g. `chromium` for
+/// `/snap/bin/chromium`.
+pub fn snap_name(program: &Path) -> Option<&str> {
+    if program.parent()? != Path::new(SNAP_BIN_DIR) {
+        return None;
+    }
+    program.file_name()?.to_str()
+}
+
+/// The directory a snap may write to across revisions. Strict confinement
+/// keeps snaps out of hidden directories in the home directory, such as the
+/// app's settings directory.
+pub fn snap_common_dir(name: &str) -> Option<PathBuf> {
+    let home = std::env::var_os("HOME")?;
+    Some(Path::new(&home).join("snap").join(name).join("common"))
+}
+
+/// Whether a Flatpak app is installed system-wide or for the current user.
+pub fn flatpak_installed(app_id: &str) -> bool {
+    let mut installations = vec![PathBuf::from("/var/lib/flatpak")];
+    installations.extend(data_home().map(|dir| dir.join("flatpak")));
+    installations.iter().any(|installation| {
+        installation
+            .join("app")
+            .join(app_id)
+            .join("current")
+            .exists()
+    })
+}
+
+/// Whether `dir` holds the commands of snaps or Flatpak apps.
+fn is_packaged_bin_dir(dir: &Path) -> bool {
+    dir.starts_with(SNAP_BIN_DIR) || dir.ends_with("flatpak/exports/bin")
+}
+
+fn data_home() -> Option<PathBuf> {
+    std::env::var_os("XDG_DATA_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".local/share")))
+}
+
+/// The `applications` directories of the XDG data directories, the user's
+/// own first.
+fn application_dirs() -> Vec<PathBuf> {
+    let data_dirs = std::env::var_os("XDG_DATA_DIRS")
+        .filter(|dirs| !dirs.is_empty())
+        .unwrap_or_else(|| "/usr/local/share:/usr/share".into());
+
+    data_home()
+        .into_iter()
+        .chain(std::env::split_paths(&data_dirs))
+        .map(|dir| dir.join("applications"))
+        .collect()
+}
+
+/// The programs the `.desktop` entries in the application directories start,
+/// with bare names looked up in `PATH`. Like in `find_in_path`, Snap and
+/// Flatpak commands are left out.
+fn desktop_programs() -> Vec<PathBuf> {
+    let mut programs = Vec::new();
+    for dir in application_dirs() {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().and_then(|extension| extension.to_str()) != Some("desktop") {
+                continue;
+            }
+            let Ok(contents) = std::fs::read_to_string(&path) else {
+                continue;
+            };
+            let program = exec_line(&contents)
+                .and_then(exec_program)
+                .and_then(|program| {
+                    let path = Path::new(&program);
+                    if path.is_absolute() {
+                        let packaged = path.parent().is_some_and(is_packaged_bin_dir);
+                        (!packaged).then(|| path.to_path_buf())
+                    } else {
+                        find_in_path(&[program.as_str()])
+                    }
+                });
+            programs.extend(program);
+        }
+    }
+    programs
+}
+
+/// The `Exec` key of the `[Desktop Entry]` group.
+fn exec_line(contents: &str) -> Option<&str> {
+    let mut in_entry = false;
+    for line in contents.lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_entry = line == "[Desktop Entry]";
+        } else if in_entry {
+            if let Some(exec) = line.strip_prefix("Exec=") {
+                return Some(exec);
+            }
+        }
+    }
+    None
+}
+
+/// The program of an `Exec` command line, skipping an `env` prefix that
+/// sets variables.
+fn exec_program(exec: &str) -> Option<String> {
+    exec_args(exec)
+        .into_iter()
+        .find(|arg| arg != "env" && arg != "/usr/bin/env" && !arg.contains('='))
+}
+
+/// Splits an `Exec` value into arguments. Arguments may be quoted, and
+/// inside quotes a backslash escapes the next character.
+fn exec_args(exec: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut in_arg = false;
+    let mut quoted = false;
+
+    let mut chars = exec.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                in_arg = true;
+            }
+            '\\' if quoted => current.extend(chars.next()),
+            c if c.is_whitespace() && !quoted => {
+                if in_arg {
+                    args.push(std::mem::take(&mut current));
+                    in_arg = false;
+                }
+            }
+            c => {
+                current.push(c);
+                in_arg = true;
+            }
+        }
+    }
+    if in_arg {
+        args.push(current);
+    }
+    args
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_exec_of_desktop_entry() {
+        let contents = "\
+[Desktop Entry]
+Name=Firefox
+Exec=firefox %u
+
+[Desktop Action new-window]
+Exec=firefox --new-window %u
+";
+        assert_eq!(exec_line(contents), Some("firefox %u"));
+
+        let action_only = "[Desktop Action new-window]\nExec=firefox --new-window\n";
+        assert_eq!(exec_line(action_only), None);
+    }
+
+    #[test]
+    fn splits_quoted_arguments() {
+        assert_eq!(
+            exec_args(r#""/opt/My Browser/browser" --new-window %U"#),
+            ["/opt/My Browser/browser", "--new-window", "%U"]
+        );
+        assert_eq!(
+            exec_args(r#"  browser  "say \"hi\"" """#),
+            ["browser", r#"say "hi""#, ""]
+        );
+    }
+
+    #[test]
+    fn finds_program_after_env_prefix() {
+        assert_eq!(
+            exec_program("env MOZ_ENABLE_WAYLAND=1 GDK_BACKEND=wayland firefox %u").as_deref(),
+            Some("firefox")
+        );
+        assert_eq!(
+            exec_program(r#"/usr/bin/env LANG=C "/opt/brave.com/brave/brave-browser" %U"#)
+                .as_deref(),
+            Some("/opt/brave.com/brave/brave-browser")
+        );
+        assert_eq!(
+            exec_program("/usr/bin/google-chrome-stable %U").as_deref(),
+            Some("/usr/bin/google-chrome-stable")
+        );
+        assert_eq!(exec_program("env FOO=1"), None);
+    }
+
+    #[test]
+    fn recognizes_packaged_command_dirs() {
+        assert!(is_packaged_bin_dir(Path::new("/snap/bin")));
+        assert!(is_packaged_bin_dir(Path::new(
+            "/var/lib/flatpak/exports/bin"
+        )));
+        assert!(is_packaged_bin_dir(Path::new(
+            "/home/user/.local/share/flatpak/exports/bin"
+        )));
+        assert!(!is_packaged_bin_dir(Path::new("/usr/bin")));
+        assert!(!is_packaged_bin_dir(Path::new("/opt/snapshot/bin")));
+    }
+
+    #[test]
+    fn recognizes_snap_commands() {
+        assert_eq!(snap_name(Path::new("/snap/bin/chromium")), Some("chromium"));
+        assert_eq!(snap_name(Path::new("/snap/bin/firefox")), Some("firefox"));
+        assert_eq!(snap_name(Path::new("/usr/bin/chromium")), None);
+        assert_eq!(
+            snap_name(Path::new("/snap/chromium/current/usr/lib/chromium")),
+            None
+        );
+    }
+}
@@ -1,13 +1,80 @@
 use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
 
 pub mod detector;
+pub mod family;
+pub mod firefox;
+#[cfg(target_os = "linux")]
+pub mod linux;
+pub mod preflight;
+pub mod session;
+pub mod version;
 
 #[cfg(target_os = "windows")]
 pub mod registry;
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+pub use family::BrowserFamily;
+pub use version::Channel;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct Browser {
     pub id: String,
     pub name: String,
     pub path: String,
-}
\ No newline at end of file
+    /// Arguments for the launcher `path` names, for packaged installs such
+    /// as `run com.google.Chrome` for `flatpak`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub args: Vec<String>,
+    /// Read on detection and again on every startup.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub channel: Option<Channel>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub family: Option<BrowserFamily>,
+}
+
+impl Browser {
+    /// How the browser takes launch options, as detected along with its
+    /// version. Browsers picked by hand are recognized by their executable.
+    pub fn family(&self) -> BrowserFamily {
+        self.family
+            .unwrap_or_else(|| BrowserFamily::detect(&self.id, &self.path))
+    }
+
+    /// The directory holding the browser's dedicated profiles, normally the
+    /// settings directory. Snaps cannot reach that one, so theirs live in the
+    /// snap's own data directory.
+    pub fn profiles_root(&self, settings_dir: &Path) -> PathBuf {
+        #[cfg(target_os = "linux")]
+        if let Some(dir) = linux::snap_name(Path::new(&self.path)).and_then(linux::snap_common_dir)
+        {
+            return dir.join("browser-proxy-launcher");
+        }
+        settings_dir.to_path_buf()
+    }
+
+    /// A command starting the browser, to which its own arguments are added.
+    /// Flatpak apps are sandboxed, so they are granted `dirs`, such as the
+    /// profile directory.
+    pub fn command(&self, dirs: &[&Path]) -> tokio::process::Command {
+        let mut command = tokio::process::Command::new(&self.path);
+        let is_flatpak = Path::new(&self.path)
+            .file_name()
+            .is_some_and(|name| name == "flatpak");
+        match self.args.split_first() {
+            Some((run, rest)) if is_flatpak && run == "run" => {
+                command.arg(run);
+                command.args(
+                    dirs.iter()
+                        .map(|dir| format!("--filesystem={}", dir.to_string_lossy())),
+                );
+                command.args(rest);
+            }
+            _ => {
+                command.args(&self.args);
+            }
+        }
+        command
+    }
+}
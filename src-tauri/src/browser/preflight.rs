@@ -0,0 +1,354 @@
+use super::Browser;
+use crate::proxy::{health, helper, pac};
+use crate::settings::{PacRule, ProxyConfig, ProxyKind};
+use reqwest::Url;
+use serde::Serialize;
+use std::path::Path;
+use std::time::Duration;
+
+const PROXY_PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Files a running browser keeps in its profile: Chromium's `SingletonLock`
+/// and `lockfile`, and the `lock` symlink of Firefox on Unix.
+const PROFILE_LOCK_FILES: [&str; 3] = ["SingletonLock", "lockfile", "lock"];
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckStatus {
+    Pass,
+    Warn,
+    Fail,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct PreflightCheck {
+    pub name: String,
+    pub status: CheckStatus,
+    pub message: String,
+}
+
+impl PreflightCheck {
+    fn new(name: &str, status: CheckStatus, message: impl Into<String>) -> Self {
+        Self {
+            name: name.to_string(),
+            status,
+            message: message.into(),
+        }
+    }
+}
+
+/// The outcome of a launch request, including every preflight check.
+#[derive(Debug, Serialize, Clone)]
+pub struct LaunchReport {
+    pub launched: bool,
+    /// The proxy launched with; for sites using a group, the chosen member.
+    pub proxy_id: Option<String>,
+    pub checks: Vec<PreflightCheck>,
+}
+
+impl LaunchReport {
+    pub fn new(checks: Vec<PreflightCheck>) -> Self {
+        Self {
+            launched: false,
+            proxy_id: None,
+            checks,
+        }
+    }
+
+    pub fn has_failures(&self) -> bool {
+        self.checks
+            .iter()
+            .any(|check| check.status == CheckStatus::Fail)
+    }
+
+    pub fn push(&mut self, name: &str, status: CheckStatus, message: impl Into<String>) {
+        self.checks.push(PreflightCheck::new(name, status, message));
+    }
+}
+
+pub struct PreflightInput<'a> {
+    pub browser: &'a Browser,
+    pub proxy: Option<&'a ProxyConfig>,
+    /// Every configured proxy, for resolving chain hops.
+    pub proxies: &'a [ProxyConfig],
+    /// The rules behind proxies of the `rules` type.
+    pub pac_rules: &'a [PacRule],
+    pub url: Option<&'a str>,
+    pub profile_dir: Option<&'a Path>,
+    /// Whether the app already runs a browser session for this profile.
+    pub session_active: bool,
+}
+
+pub async fn run(input: PreflightInput<'_>) -> Vec<PreflightCheck> {
+    let mut checks = vec![check_browser(input.browser)];
+
+    if let Some(proxy) = input.proxy {
+        checks.push(match proxy.kind {
+            ProxyKind::Rules => check_rules(input.pac_rules, input.proxies, input.url).await,
+            _ => check_proxy(proxy, input.proxies).await,
+        });
+    }
+    if let Some(profile_dir) = input.profile_dir {
+        checks.push(check_profile(profile_dir, input.session_active));
+    }
+    if let Some(url) = input.url {
+        checks.push(check_url(url));
+    }
+
+    checks
+}
+
+fn check_browser(browser: &Browser) -> PreflightCheck {
+    let path = Path::new(&browser.path);
+
+    let metadata = match std::fs::metadata(path) {
+        Ok(metadata) => metadata,
+        Err(e) => {
+            return PreflightCheck::new(
+                "browser",
+                CheckStatus::Fail,
+                format!("{} not found at {}: {}", browser.name, browser.path, e),
+            )
+        }
+    };
+
+    if !metadata.is_file() {
+        return PreflightCheck::new(
+            "browser",
+            CheckStatus::Fail,
+            format!("{} is not a file", browser.path),
+        );
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        if metadata.permissions().mode() & 0o111 == 0 {
+            return PreflightCheck::new(
+                "browser",
+                CheckStatus::Fail,
+                format!("{} is not executable", browser.path),
+            );
+        }
+    }
+
+    PreflightCheck::new(
+        "browser",
+        CheckStatus::Pass,
+        format!("{} found at {}", browser.name, browser.path),
+    )
+}
+
+/// Checks the rules and the proxy they route the launch URL through.
+async fn check_rules(
+    rules: &[PacRule],
+    proxies: &[ProxyConfig],
+    url: Option<&str>,
+) -> PreflightCheck {
+    if let Err(e) = pac::validate_rules(rules, proxies) {
+        return PreflightCheck::new("proxy", CheckStatus::Fail, e);
+    }
+
+    match url.and_then(|url| Url::parse(url).ok()) {
+        Some(url) => match pac::route(rules, proxies, &url) {
+            Ok(Some(target)) => check_proxy(target, proxies).await,
+            Ok(None) => PreflightCheck::new(
+                "proxy",
+                CheckStatus::Pass,
+                format!(
+                    "PAC rules route {} directly",
+                    url.host_str().unwrap_or_default()
+                ),
+            ),
+            Err(e) => PreflightCheck::new("proxy", CheckStatus::Fail, e),
+        },
+        None if rules.is_empty() => PreflightCheck::new(
+            "proxy",
+            CheckStatus::Warn,
+            "No PAC rules are defined, all traffic goes directly",
+        ),
+        None => PreflightCheck::new(
+            "proxy",
+            CheckStatus::Pass,
+            format!("{} PAC rules are valid", rules.len()),
+        ),
+    }
+}
+
+async fn check_proxy(proxy: &ProxyConfig, proxies: &[ProxyConfig]) -> PreflightCheck {
+    match &proxy.kind {
+        ProxyKind::Pac { url } => match pac::fetch_script(url).await {
+            Ok(_) => PreflightCheck::new(
+                "proxy",
+                CheckStatus::Pass,
+                format!("PAC script is reachable at {}", url),
+            ),
+            Err(e) => PreflightCheck::new("proxy", CheckStatus::Fail, e),
+        },
+        ProxyKind::Http(server)
+        | ProxyKind::Https(server)
+        | ProxyKind::Socks4(server)
+        | ProxyKind::Socks4a(server)
+        | ProxyKind::Socks5(server) => {
+            match health::probe_tcp(&server.host, server.port, PROXY_PROBE_TIMEOUT).await {
+                Ok(elapsed) => PreflightCheck::new(
+                    "proxy",
+                    CheckStatus::Pass,
+                    format!(
+                        "{}:{} is reachable ({} ms)",
+                        server.host,
+                        server.port,
+                        elapsed.as_millis()
+                    ),
+                ),
+                Err((_, message)) => PreflightCheck::new("proxy", CheckStatus::Fail, message),
+            }
+        }
+        ProxyKind::Ssh(server) => {
+            if helper::find_program("ssh").is_none() {
+                return PreflightCheck::new(
+                    "proxy",
+                    CheckStatus::Fail,
+                    "ssh was not found in PATH",
+                );
+            }
+            if let Some(identity_file) = &server.identity_file {
+                if !Path::new(identity_file).is_file() {
+                    return PreflightCheck::new(
+                        "proxy",
+                        CheckStatus::Fail,
+                        format!("SSH identity file {} not found", identity_file),
+                    );
+                }
+            }
+            match health::probe_tcp(&server.host, server.port, PROXY_PROBE_TIMEOUT).await {
+                Ok(elapsed) => PreflightCheck::new(
+                    "proxy",
+                    CheckStatus::Pass,
+                    format!(
+                        "SSH server {}:{} is reachable ({} ms)",
+                        server.host,
+                        server.port,
+                        elapsed.as_millis()
+                    ),
+                ),
+                Err((_, message)) => PreflightCheck::new("proxy", CheckStatus::Fail, message),
+            }
+        }
+        ProxyKind::Helper(command) => match helper::find_program(&command.command) {
+            Some(path) => PreflightCheck::new(
+                "proxy",
+                CheckStatus::Pass,
+                format!("Helper found at {}", path.display()),
+            ),
+            None => PreflightCheck::new(
+                "proxy",
+                CheckStatus::Fail,
+                format!("Helper command {} not found", command.command),
+            ),
+        },
+        ProxyKind::Tor(tor) if tor.launched() => {
+            let program = tor.tor_path.as_deref().unwrap_or("tor");
+            match helper::find_program(program) {
+                Some(path) => PreflightCheck::new(
+                    "proxy",
+                    CheckStatus::Pass,
+                    format!("Tor found at {}", path.display()),
+                ),
+                None => PreflightCheck::new(
+                    "proxy",
+                    CheckStatus::Fail,
+                    format!("Tor binary {} not found", program),
+                ),
+            }
+        }
+        ProxyKind::Tor(tor) => {
+            match health::probe_tcp(&tor.host, tor.port, PROXY_PROBE_TIMEOUT).await {
+                Ok(elapsed) => PreflightCheck::new(
+                    "proxy",
+                    CheckStatus::Pass,
+                    format!(
+                        "Tor SOCKS port {}:{} is reachable ({} ms)",
+                        tor.host,
+                        tor.port,
+                        elapsed.as_millis()
+                    ),
+                ),
+                Err((_, message)) => PreflightCheck::new("proxy", CheckStatus::Fail, message),
+            }
+        }
+        // Later hops are only reachable through the first one, which the
+        // health check covers
+        ProxyKind::Chain { .. } => match proxy.chain_hops(proxies) {
+            Ok(hops) => match hops.first() {
+                Some(first) => Box::pin(check_proxy(first, proxies)).await,
+                None => PreflightCheck::new("proxy", CheckStatus::Fail, "Chain has no hops"),
+            },
+            Err(e) => PreflightCheck::new("proxy", CheckStatus::Fail, e),
+        },
+        // Handled by check_rules, which knows the rules and the URL
+        ProxyKind::Rules => {
+            PreflightCheck::new("proxy", CheckStatus::Pass, "PAC rules are served locally")
+        }
+    }
+}
+
+fn check_profile(profile_dir: &Path, session_active: bool) -> PreflightCheck {
+    if let Err(e) = std::fs::create_dir_all(profile_dir) {
+        return PreflightCheck::new(
+            "profile",
+            CheckStatus::Fail,
+            format!("Failed to create profile directory: {}", e),
+        );
+    }
+
+    let probe = profile_dir.join(".preflight-write-test");
+    if let Err(e) = std::fs::write(&probe, b"") {
+        return PreflightCheck::new(
+            "profile",
+            CheckStatus::Fail,
+            format!("Profile directory is not writable: {}", e),
+        );
+    }
+    let _ = std::fs::remove_file(&probe);
+
+    // symlink_metadata because SingletonLock is a dangling symlink on Unix
+    let locked = PROFILE_LOCK_FILES
+        .iter()
+        .any(|name| std::fs::symlink_metadata(profile_dir.join(name)).is_ok());
+
+    if !locked {
+        PreflightCheck::new("profile", CheckStatus::Pass, "Profile directory is ready")
+    } else if session_active {
+        PreflightCheck::new(
+            "profile",
+            CheckStatus::Pass,
+            "Browser is already running with this profile; opening a new tab in it",
+        )
+    } else {
+        PreflightCheck::new(
+            "profile",
+            CheckStatus::Warn,
+            "Profile is in use by another browser instance; the new tab will use that instance's proxy settings",
+        )
+    }
+}
+
+fn check_url(url: &str) -> PreflightCheck {
+    match Url::parse(url) {
+        Ok(parsed) if parsed.has_host() || parsed.scheme() == "file" => {
+            PreflightCheck::new("url", CheckStatus::Pass, format!("{} is valid", url))
+        }
+        _ if Url::parse(&format!("https://{}", url)).is_ok() => PreflightCheck::new(
+            "url",
+            CheckStatus::Warn,
+            format!("{} has no scheme; the browser will guess one", url),
+        ),
+        Ok(_) => PreflightCheck::new("url", CheckStatus::Warn, format!("{} has no host", url)),
+        Err(e) => PreflightCheck::new(
+            "url",
+            CheckStatus::Fail,
+            format!("{} is not a valid URL: {}", url, e),
+        ),
+    }
+}
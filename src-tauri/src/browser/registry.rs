@@ -3,18 +3,17 @@ use winreg::enums::*;
 #[cfg(target_os = "windows")]
 use winreg::RegKey;
 
+/// The path an executable is registered under in `App Paths`, machine-wide
+/// or for the current user.
 #[cfg(target_os = "windows")]
-pub fn get_chrome_from_registry() -> Result<String, Box<dyn std::error::Error>> {
-    let hklm = RegKey::predef(HKEY_LOCAL_MACHINE);
-    let chrome_key = hklm.open_subkey(r"SOFTWARE\Microsoft\Windows\CurrentVersion\App Paths\chrome.exe")?;
-    let path: String = chrome_key.get_value("")?;
+pub fn get_app_path_from_registry(executable: &str) -> Result<String, Box<dyn std::error::Error>> {
+    let subkey = format!(
+        r"SOFTWARE\Microsoft\Windows\CurrentVersion\App Paths\{}",
+        executable
+    );
+    let app_key = RegKey::predef(HKEY_LOCAL_MACHINE)
+        .open_subkey(&subkey)
+        .or_else(|_| RegKey::predef(HKEY_CURRENT_USER).open_subkey(&subkey))?;
+    let path: String = app_key.get_value("")?;
     Ok(path)
 }
-
-#[cfg(target_os = "windows")]
-pub fn get_edge_from_registry() -> Result<String, Box<dyn std::error::Error>> {
-    let hklm = RegKey::predef(HKEY_LOCAL_MACHINE);
-    let edge_key = hklm.open_subkey(r"SOFTWARE\Microsoft\Windows\CurrentVersion\App Paths\msedge.exe")?;
-    let path: String = edge_key.get_value("")?;
-    Ok(path)
-}
\ No newline at end of file
@@ -0,0 +1,85 @@
+use crate::proxy::forwarder::LocalForwarder;
+use crate::proxy::helper::HelperProcess;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// A browser process launched by the app, together with the local resources
+/// that must stay alive for as long as the browser is running.
+pub struct BrowserSession {
+    pub proxy_id: Option<String>,
+    pub proxy_server: Option<String>,
+    _forwarder: Option<LocalForwarder>,
+    helper: Option<HelperProcess>,
+}
+
+impl BrowserSession {
+    pub fn new(
+        proxy_id: Option<String>,
+        proxy_server: Option<String>,
+        forwarder: Option<LocalForwarder>,
+        helper: Option<HelperProcess>,
+    ) -> Self {
+        Self {
+            proxy_id,
+            proxy_server,
+            _forwarder: forwarder,
+            helper,
+        }
+    }
+}
+
+/// Tracks running browser sessions by profile directory.
+///
+/// Chromium hands a launch off to the process that already owns a profile,
+/// so a second launch into the same profile must reuse the running session
+/// instead of starting fresh resources for it.
+#[derive(Clone, Default)]
+pub struct SessionManager {
+    sessions: Arc<Mutex<HashMap<String, BrowserSession>>>,
+}
+
+impl SessionManager {
+    pub fn is_active(&self, profile: &str) -> bool {
+        self.sessions.lock().unwrap().contains_key(profile)
+    }
+
+    pub fn proxy_server(&self, profile: &str) -> Option<String> {
+        self.sessions
+            .lock()
+            .unwrap()
+            .get(profile)
+            .and_then(|session| session.proxy_server.clone())
+    }
+
+    /// Control ports of the helpers serving `proxy_id` in running sessions.
+    pub fn control_ports(&self, proxy_id: &str) -> Vec<u16> {
+        self.sessions
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|session| session.proxy_id.as_deref() == Some(proxy_id))
+            .filter_map(|session| session.helper.as_ref()?.control_port())
+            .collect()
+    }
+
+    /// The proxy server of a helper serving `proxy_id` in a running session.
+    pub fn helper_proxy_server(&self, proxy_id: &str) -> Option<String> {
+        self.sessions
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|session| session.proxy_id.as_deref() == Some(proxy_id))
+            .find_map(|session| Some(session.helper.as_ref()?.proxy_server()))
+    }
+
+    pub fn start(&self, profile: String, session: BrowserSession) {
+        self.sessions.lock().unwrap().insert(profile, session);
+    }
+
+    /// Removes the session, releasing any resources it owns.
+    pub fn end(&self, profile: &str) {
+        if self.sessions.lock().unwrap().remove(profile).is_some() {
+            println!("Browser session for profile {} ended", profile);
+        }
+    }
+}
@@ -0,0 +1,335 @@
+use super::{Browser, BrowserFamily};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::process::{Command, Stdio};
+use std::sync::LazyLock;
+use std::time::{Duration, Instant};
+
+/// Flatpak apps may take a while to start just to print their version.
+const VERSION_TIMEOUT: Duration = Duration::from_secs(10);
+
+const VERSION_POLL: Duration = Duration::from_millis(50);
+
+/// Flags a launch may pass, with the first major version of the family
+/// that no longer supports them.
+const REMOVED_FLAGS: &[(BrowserFamily, &str, u32)] = &[
+    // Prefetching became a profile setting when the predictor was replaced
+    (BrowserFamily::Chromium, "--dns-prefetch-disable", 69),
+];
+
+static VERSION_PATTERN: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\d+(\.\d+)+").expect("version pattern is valid"));
+
+#[cfg(target_os = "macos")]
+static BUNDLE_VERSION_PATTERN: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"<key>CFBundleShortVersionString</key>\s*<string>([^<]+)</string>")
+        .expect("bundle version pattern is valid")
+});
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Channel {
+    Stable,
+    Beta,
+    Dev,
+    Canary,
+    Esr,
+}
+
+/// Reads the browser's version and channel and detects its family,
+/// returning whether any of them changed. A browser that cannot be asked
+/// keeps what was read before.
+pub fn refresh(browser: &mut Browser) -> bool {
+    let family = Some(BrowserFamily::detect(&browser.id, &browser.path));
+    let mut changed = browser.family != family;
+    browser.family = family;
+
+    let Some(reported) = read_version(browser) else {
+        return changed;
+    };
+    let version = parse_version(&reported);
+    let channel = Some(channel(&browser.id, &reported));
+    if let (Some(old), Some(new)) = (&browser.version, &version) {
+        if old != new {
+            println!("{} changed from version {} to {}", browser.name, old, new);
+        }
+    }
+
+    changed |= browser.version != version || browser.channel != channel;
+    browser.version = version;
+    browser.channel = channel;
+    changed
+}
+
+/// Refreshes every browser at once, since each may start a process.
+pub fn refresh_all(browsers: &mut [Browser]) -> bool {
+    std::thread::scope(|scope| {
+        let refreshes: Vec<_> = browsers
+            .iter_mut()
+            .map(|browser| scope.spawn(move || refresh(browser)))
+            .collect();
+        let changed: Vec<bool> = refreshes
+            .into_iter()
+            .map(|refresh| refresh.join().unwrap_or(false))
+            .collect();
+        changed.contains(&true)
+    })
+}
+
+/// Explains each of `flags` that the browser's version no longer supports.
+pub fn removed_flag_warnings<'a>(
+    browser: &Browser,
+    flags: impl IntoIterator<Item = &'a str>,
+) -> Vec<String> {
+    let Some(major) = engine_major(browser) else {
+        return Vec::new();
+    };
+    let family = browser.family();
+    let flags: Vec<&str> = flags.into_iter().collect();
+
+    REMOVED_FLAGS
+        .iter()
+        .filter(|(removed_family, flag, removed_in)| {
+            *removed_family == family
+                && major >= *removed_in
+                && flags
+                    .iter()
+                    .any(|passed| passed.split('=').next() == Some(*flag))
+        })
+        .map(|(_, flag, removed_in)| {
+            format!(
+                "{} {} no longer supports {}, which was removed in version {}",
+                browser.name,
+                browser.version.as_deref().unwrap_or_default(),
+                flag,
+                removed_in
+            )
+        })
+        .collect()
+}
+
+/// Explains every flag a launch may pass that the browser's version no
+/// longer supports.
+pub fn startup_warnings(browser: &Browser) -> Vec<String> {
+    removed_flag_warnings(browser, REMOVED_FLAGS.iter().map(|(_, flag, _)| *flag))
+}
+
+/// The major version of the browser's engine. Opera and Vivaldi number
+/// their releases apart from Chromium's, so theirs is unknown.
+fn engine_major(browser: &Browser) -> Option<u32> {
+    if browser.id.starts_with("opera") || browser.id.starts_with("vivaldi") {
+        return None;
+    }
+    browser.version.as_deref()?.split('.').next()?.parse().ok()
+}
+
+/// What the browser reports about its version. Windows builds do not print
+/// it, so it is read from the install directory there, and from the app
+/// bundle on macOS.
+fn read_version(browser: &Browser) -> Option<String> {
+    #[cfg(target_os = "windows")]
+    {
+        installed_version(std::path::Path::new(&browser.path))
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        bundle_version(std::path::Path::new(&browser.path)).or_else(|| version_output(browser))
+    }
+
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    {
+        version_output(browser)
+    }
+}
+
+/// Runs the browser with `--version`, killing it if it takes too long.
+#[cfg_attr(target_os = "windows", allow(dead_code))]
+fn version_output(browser: &Browser) -> Option<String> {
+    let mut child = Command::new(&browser.path)
+        .args(&browser.args)
+        .arg("--version")
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .ok()?;
+
+    let started = Instant::now();
+    loop {
+        match child.try_wait() {
+            Ok(Some(_)) => break,
+            Ok(None) if started.elapsed() < VERSION_TIMEOUT => std::thread::sleep(VERSION_POLL),
+            _ => {
+                eprintln!("{} did not report its version", browser.name);
+                let _ = child.kill();
+                let _ = child.wait();
+                return None;
+            }
+        }
+    }
+
+    let output = child.wait_with_output().ok()?;
+    let text = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (!text.is_empty()).then_some(text)
+}
+
+/// Firefox keeps its version in `application.ini`, Chromium-based browsers
+/// install each version into a directory named after it.
+#[cfg(target_os = "windows")]
+fn installed_version(path: &std::path::Path) -> Option<String> {
+    let dir = path.parent()?;
+    if let Ok(ini) = std::fs::read_to_string(dir.join("application.ini")) {
+        if let Some(version) = ini.lines().find_map(|line| line.strip_prefix("Version=")) {
+            return Some(version.trim().to_string());
+        }
+    }
+
+    std::fs::read_dir(dir)
+        .ok()?
+        .flatten()
+        .filter(|entry| entry.path().is_dir())
+        .filter_map(|entry| parse_version(&entry.file_name().to_string_lossy()))
+        .max_by_key(|version| {
+            version
+                .split('.')
+                .map(|part| part.parse::<u32>().unwrap_or(0))
+                .collect::<Vec<_>>()
+        })
+}
+
+/// `CFBundleShortVersionString` from the `Info.plist` of the app bundle
+/// the executable is in.
+#[cfg(target_os = "macos")]
+fn bundle_version(path: &std::path::Path) -> Option<String> {
+    let plist = std::fs::read_to_string(path.parent()?.parent()?.join("Info.plist")).ok()?;
+    Some(BUNDLE_VERSION_PATTERN.captures(&plist)?[1].to_string())
+}
+
+/// The first dotted number in the text, e.g. `120.0.6099.71` in
+/// `Google Chrome 120.0.6099.71 beta`.
+fn parse_version(text: &str) -> Option<String> {
+    VERSION_PATTERN
+        .find(text)
+        .map(|found| found.as_str().to_string())
+}
+
+/// The channel named by the browser's ID, e.g. `chrome-beta`, or by what it
+/// reports, e.g. `Mozilla Firefox 115.5.0esr`.
+fn channel(id: &str, reported: &str) -> Channel {
+    let text = format!("{} {}", id, reported).to_ascii_lowercase();
+    let words: Vec<&str> = text
+        .split(|c: char| !c.is_ascii_alphabetic())
+        .filter(|word| !word.is_empty())
+        .collect();
+    let names = |names: &[&str]| names.iter().any(|name| words.contains(name));
+
+    if names(&["canary"]) {
+        Channel::Canary
+    } else if names(&["dev", "unstable"]) {
+        Channel::Dev
+    } else if names(&["beta"]) {
+        Channel::Beta
+    } else if names(&["esr"]) {
+        Channel::Esr
+    } else {
+        Channel::Stable
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_versions() {
+        assert_eq!(
+            parse_version("Google Chrome 120.0.6099.71 beta").as_deref(),
+            Some("120.0.6099.71")
+        );
+        assert_eq!(
+            parse_version("Mozilla Firefox 115.5.0esr").as_deref(),
+            Some("115.5.0")
+        );
+        assert_eq!(
+            parse_version("Opera 105.0.4970.21").as_deref(),
+            Some("105.0.4970.21")
+        );
+        assert_eq!(parse_version("Chromium 2 build"), None);
+        assert_eq!(parse_version(""), None);
+    }
+
+    #[test]
+    fn reads_channels() {
+        assert_eq!(
+            channel("chrome-beta", "Google Chrome 121.0.6167.16 beta"),
+            Channel::Beta
+        );
+        assert_eq!(
+            channel("chrome-dev", "Google Chrome 122.0.6182.0 dev"),
+            Channel::Dev
+        );
+        assert_eq!(
+            channel("custom", "Google Chrome 122.0.6182.0 unstable"),
+            Channel::Dev
+        );
+        assert_eq!(
+            channel("chrome-canary", "Google Chrome 123.0.6200.0 canary"),
+            Channel::Canary
+        );
+        assert_eq!(
+            channel("firefox", "Mozilla Firefox 115.5.0esr"),
+            Channel::Esr
+        );
+        assert_eq!(
+            channel("chrome", "Google Chrome 120.0.6099.71"),
+            Channel::Stable
+        );
+        // Only whole words name a channel
+        assert_eq!(
+            channel("edge", "Microsoft Edge 120.0.2210.61 developer build"),
+            Channel::Stable
+        );
+    }
+
+    fn browser(id: &str, path: &str, version: &str) -> Browser {
+        Browser {
+            id: id.to_string(),
+            name: "Test Browser".to_string(),
+            path: path.to_string(),
+            version: Some(version.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn warns_about_removed_flags() {
+        let flags = [
+            "--new-tab",
+            "--dns-prefetch-disable",
+            "https://example.com/",
+        ];
+
+        let current = browser("chrome", "/usr/bin/google-chrome", "120.0.6099.71");
+        assert_eq!(
+            removed_flag_warnings(&current, flags),
+            ["Test Browser 120.0.6099.71 no longer supports --dns-prefetch-disable, which was removed in version 69"]
+        );
+        let removed_in = browser("chromium", "/usr/bin/chromium", "69.0.3497.81");
+        assert_eq!(removed_flag_warnings(&removed_in, flags).len(), 1);
+        assert_eq!(startup_warnings(&removed_in).len(), 1);
+
+        let older = browser("chromium", "/usr/bin/chromium", "68.0.3440.106");
+        assert!(removed_flag_warnings(&older, flags).is_empty());
+        assert!(startup_warnings(&older).is_empty());
+
+        // Flags only count for their own family, and unpassed flags not at all
+        let firefox = browser("firefox", "/usr/bin/firefox", "120.0");
+        assert!(removed_flag_warnings(&firefox, flags).is_empty());
+        assert!(removed_flag_warnings(&current, ["--new-tab"]).is_empty());
+
+        // Opera numbers its releases apart from Chromium's
+        let opera = browser("opera", "/usr/bin/opera", "105.0.4970.21");
+        assert!(removed_flag_warnings(&opera, flags).is_empty());
+    }
+}
@@ -1,112 +1,305 @@
-use crate::browser::{detector, Browser};
-use crate::settings::{ProxyConfig, SettingsManager};
+use crate::browser::family::{LaunchOptions, ProxyOptions, ProxyRoute};
+use crate::browser::preflight::{self, CheckStatus, LaunchReport, PreflightInput};
+use crate::browser::session::{BrowserSession, SessionManager};
+use crate::browser::{detector, version, Browser};
+use crate::proxy::connection_log::ConnectionLog;
+use crate::proxy::forwarder::{ForwarderSession, LocalForwarder, Upstream};
+use crate::proxy::group::{self, GroupCursors};
+use crate::proxy::helper::{HelperProcess, HelperSpec};
+use crate::proxy::kill_switch::KillSwitch;
+use crate::proxy::monitor::HealthMonitor;
+use crate::proxy::pac;
+use crate::proxy::pac_server::{self, PacServer};
+use crate::proxy::usage::UsageTracker;
+use crate::settings::{PacRule, ProxyConfig, ProxyKind, SettingsManager};
+use tauri::Manager;
 
 struct LaunchConfig {
     browser: Browser,
     proxy: Option<ProxyConfig>,
+    /// Every configured proxy, for resolving chain hops.
+    proxies: Vec<ProxyConfig>,
+    pac_rules: Vec<PacRule>,
     url: Option<String>,
     profile_name: String,
     ignore_cert_errors: bool,
+    block_on_preflight_failure: bool,
+    connection_log_size: usize,
+    connection_log_to_file: bool,
 }
 
-fn launch_browser_with_config(config: LaunchConfig) -> Result<(), String> {
-    let mut command = std::process::Command::new(&config.browser.path);
-
-    command.arg("--new-tab");
-    // Add certificate error ignore flag if enabled
-    if config.ignore_cert_errors {
-        command.arg("--ignore-certificate-errors");
-        command.arg("--ignore-ssl-errors");
-        command.arg("--ignore-certificate-errors-spki-list");
-        command.arg("--ignore-certificate-errors-ssl-invalid");
-        command.arg("--allow-running-insecure-content");
+/// The app state a launch uses.
+struct LaunchContext<'a> {
+    sessions: &'a SessionManager,
+    monitor: &'a HealthMonitor,
+    pac_server: &'a PacServer,
+    usage: &'a UsageTracker,
+    connection_log: &'a ConnectionLog,
+}
+
+/// Reuses the forwarder of the browser session already running with this
+/// profile, or starts a new one for `proxy` that meters and logs the
+/// session's requests, and blocks them while a fail-closed proxy is down.
+async fn forwarded_proxy_server(
+    proxy: &ProxyConfig,
+    config: &LaunchConfig,
+    session_key: &str,
+    context: &LaunchContext<'_>,
+    forwarder: &mut Option<LocalForwarder>,
+) -> Result<String, String> {
+    if let Some(existing) = context.sessions.proxy_server(session_key) {
+        return Ok(existing);
     }
 
-    // Add proxy arguments if needed
-    if let Some(proxy) = config.proxy {
-        // Generate profile path based on profile name
-        let profile_path = format!("./profiles/{}", config.profile_name);
-
-        // Get the settings directory and resolve profile path
-        let settings_manager = SettingsManager::new()
-            .map_err(|e| format!("Failed to create settings manager: {}", e))?;
-        let settings_dir = settings_manager
-            .get_settings_dir()
-            .map_err(|e| format!("Failed to get settings directory: {}", e))?;
-        let full_profile_path = settings_dir.join(&profile_path);
-
-        // Create profile directory if it doesn't exist
-        if let Some(parent) = full_profile_path.parent() {
-            if !parent.exists() {
-                std::fs::create_dir_all(parent)
-                    .map_err(|e| format!("Failed to create profile directory: {}", e))?;
-            }
+    let route = Upstream::route(proxy, &config.proxies)?;
+
+    // Traffic through a chain is also billed by each of its hops
+    let mut proxy_ids = vec![proxy.id.clone()];
+    if let ProxyKind::Chain { hops } = &proxy.kind {
+        proxy_ids.extend(hops.iter().cloned());
+    }
+    let session = ForwarderSession {
+        kill_switch: proxy
+            .fail_closed
+            .then(|| KillSwitch::new(context.monitor.clone(), proxy_ids.clone())),
+        meter: context.usage.start_session(&config.profile_name, proxy_ids),
+        log: context.connection_log.logger(
+            &config.profile_name,
+            &proxy.id,
+            config.connection_log_size,
+            config.connection_log_to_file,
+        ),
+    };
+
+    let local = LocalForwarder::start(route, Some(session))
+        .await
+        .map_err(|e| format!("Failed to start local forwarder: {}", e))?;
+    let url = local.proxy_server();
+    *forwarder = Some(local);
+    Ok(url)
+}
+
+/// Reuses the helper of the browser session already running with this
+/// profile, or starts the one serving `proxy` and waits until it is ready.
+async fn helper_proxy_server(
+    proxy: &ProxyConfig,
+    config: &LaunchConfig,
+    session_key: &str,
+    context: &LaunchContext<'_>,
+    helper: &mut Option<HelperProcess>,
+) -> Result<String, String> {
+    if let Some(existing) = context.sessions.proxy_server(session_key) {
+        return Ok(existing);
+    }
+
+    let spec = HelperSpec::for_proxy(proxy, &config.profile_name)?
+        .ok_or_else(|| format!("Proxy '{}' has no helper process", proxy.name))?;
+    let process = HelperProcess::start(spec).await?;
+    let url = process.proxy_server();
+    *helper = Some(process);
+    Ok(url)
+}
+
+async fn launch_browser_with_config(
+    config: LaunchConfig,
+    context: LaunchContext<'_>,
+) -> Result<LaunchReport, String> {
+    let launcher = config.browser.family().launcher();
+
+    // Proxied launches get a dedicated profile based on the profile name
+    let profile_dir = match &config.proxy {
+        Some(_) => {
+            let settings_manager = SettingsManager::new()
+                .map_err(|e| format!("Failed to create settings manager: {}", e))?;
+            let settings_dir = settings_manager
+                .get_settings_dir()
+                .map_err(|e| format!("Failed to get settings directory: {}", e))?;
+            Some(config.browser.profiles_root(&settings_dir).join(format!(
+                "./{}/{}",
+                launcher.profiles_dir(),
+                config.profile_name
+            )))
         }
+        None => None,
+    };
+    let session_key = profile_dir
+        .as_ref()
+        .map(|dir| dir.to_string_lossy().to_string());
+    let session_active = session_key
+        .as_deref()
+        .is_some_and(|key| context.sessions.is_active(key));
+
+    let mut report = LaunchReport::new(
+        preflight::run(PreflightInput {
+            browser: &config.browser,
+            proxy: config.proxy.as_ref(),
+            proxies: &config.proxies,
+            pac_rules: &config.pac_rules,
+            url: config.url.as_deref(),
+            profile_dir: profile_dir.as_deref(),
+            session_active,
+        })
+        .await,
+    );
+
+    report.proxy_id = config.proxy.as_ref().map(|proxy| proxy.id.clone());
+
+    if report.has_failures() && config.block_on_preflight_failure {
+        println!(
+            "Preflight checks failed, not launching {}",
+            config.browser.name
+        );
+        return Ok(report);
+    }
+
+    let mut command = config.browser.command(profile_dir.as_deref().as_slice());
+    let mut forwarder = None;
+    let mut helper = None;
+    let mut proxy_server = None;
+    let mut pac_url = None;
 
-        command.arg(&format!(
-            "--user-data-dir={}",
-            full_profile_path.to_string_lossy()
-        ));
-
-        match proxy.proxy_type.as_str() {
-            "http" => {
-                let proxy_url =
-                    if let (Some(username), Some(password)) = (&proxy.username, &proxy.password) {
-                        format!(
-                            "http://{}:{}@{}:{}",
-                            username, password, proxy.host, proxy.port
-                        )
-                    } else {
-                        format!("http://{}:{}", proxy.host, proxy.port)
-                    };
-                command.arg(&format!("--proxy-server={}", proxy_url));
+    if let (Some(proxy), Some(key)) = (&config.proxy, &session_key) {
+        proxy_server = match &proxy.kind {
+            ProxyKind::Http(server)
+            | ProxyKind::Https(server)
+            | ProxyKind::Socks4(server)
+            | ProxyKind::Socks4a(server)
+            | ProxyKind::Socks5(server) => {
+                // The forwarder is also what enforces the kill switch
+                if launcher.needs_forwarder(&proxy.kind) || proxy.fail_closed {
+                    Some(
+                        forwarded_proxy_server(proxy, &config, key, &context, &mut forwarder)
+                            .await?,
+                    )
+                } else {
+                    Some(format!(
+                        "{}://{}:{}",
+                        proxy.kind.type_name(),
+                        server.host,
+                        server.port
+                    ))
+                }
             }
-            "socks5" => {
-                let proxy_url =
-                    if let (Some(username), Some(password)) = (&proxy.username, &proxy.password) {
-                        format!(
-                            "socks5://{}:{}@{}:{}",
-                            username, password, proxy.host, proxy.port
-                        )
-                    } else {
-                        format!("socks5://{}:{}", proxy.host, proxy.port)
-                    };
-                command.arg(&format!("--proxy-server={}", proxy_url));
+            // The browser only sees the first hop of a chain, the forwarder does the rest
+            ProxyKind::Chain { .. } => {
+                Some(forwarded_proxy_server(proxy, &config, key, &context, &mut forwarder).await?)
             }
-            "pac" => {
-                if let Some(pac_url) = &proxy.url {
-                    command.arg(&format!("--proxy-pac-url={}", pac_url));
+            ProxyKind::Tor(tor) if !tor.launched() => {
+                if proxy.fail_closed {
+                    Some(
+                        forwarded_proxy_server(proxy, &config, key, &context, &mut forwarder)
+                            .await?,
+                    )
+                } else {
+                    Some(format!("socks5://{}:{}", tor.host, tor.port))
                 }
             }
-            _ => {}
-        }
+            // Started for the session and stopped when the browser exits
+            ProxyKind::Ssh(_) | ProxyKind::Helper(_) | ProxyKind::Tor(_) => {
+                Some(helper_proxy_server(proxy, &config, key, &context, &mut helper).await?)
+            }
+            ProxyKind::Pac { url } => {
+                // Chromium does not load file:// PAC URLs reliably, so local
+                // files are served over loopback, as are fail-closed scripts
+                let url = if pac::local_path(url).is_some() || proxy.fail_closed {
+                    context
+                        .pac_server
+                        .url(&pac_server::proxy_script_path(&proxy.id))
+                        .await?
+                } else {
+                    url.clone()
+                };
+                pac_url = Some(url);
+                None
+            }
+            ProxyKind::Rules => {
+                let path = if proxy.fail_closed {
+                    pac_server::proxy_script_path(&proxy.id)
+                } else {
+                    pac_server::RULES_PATH.to_string()
+                };
+                pac_url = Some(context.pac_server.url(&path).await?);
+                None
+            }
+        };
     }
 
-    // Add URL if specified, otherwise browser will open to home page
-    if let Some(url) = &config.url {
-        command.arg(url);
+    let options = LaunchOptions {
+        profile_dir: profile_dir.as_deref(),
+        proxy: config.proxy.as_ref().and_then(|proxy| {
+            let route = match (&proxy_server, &pac_url) {
+                (Some(proxy_server), _) => ProxyRoute::Server(proxy_server),
+                (None, Some(pac_url)) => ProxyRoute::Pac(pac_url),
+                (None, None) => return None,
+            };
+            Some(ProxyOptions {
+                route,
+                bypass: &proxy.bypass,
+                remote_dns: proxy.remote_dns,
+            })
+        }),
+        url: config.url.as_deref(),
+        ignore_cert_errors: config.ignore_cert_errors,
+        ..Default::default()
+    };
+    launcher.prepare_profile(&options)?;
+    let args = launcher.args(&options)?;
+    for warning in version::removed_flag_warnings(&config.browser, args.iter().map(String::as_str))
+    {
+        report.push("browser_version", CheckStatus::Warn, warning);
     }
+    command.args(args);
 
     // Launch the browser
     match command.spawn() {
-        Ok(_) => {
+        Ok(mut child) => {
             let target = config.url.as_deref().unwrap_or("home page");
             println!(
                 "Successfully launched {} with {}",
                 target, config.browser.name
             );
-            Ok(())
+
+            // A profile that is already running hands the launch off to the
+            // existing process, which keeps using its own session
+            if let Some(key) = session_key.filter(|key| !context.sessions.is_active(key)) {
+                context.sessions.start(
+                    key.clone(),
+                    BrowserSession::new(
+                        config.proxy.as_ref().map(|proxy| proxy.id.clone()),
+                        proxy_server,
+                        forwarder,
+                        helper,
+                    ),
+                );
+
+                let sessions = context.sessions.clone();
+                tauri::async_runtime::spawn(async move {
+                    let _ = child.wait().await;
+                    sessions.end(&key);
+                });
+            }
+
+            report.launched = true;
+            Ok(report)
         }
         Err(e) => {
             eprintln!("Failed to launch browser: {}", e);
-            Err(format!("Failed to launch browser: {}", e))
+            report.push(
+                "launch",
+                CheckStatus::Fail,
+                format!("Failed to launch browser: {}", e),
+            );
+            Ok(report)
         }
     }
 }
 
 #[tauri::command]
 pub async fn detect_browsers() -> Result<Vec<Browser>, String> {
-    Ok(detector::detect_all_browsers())
+    // Reading versions starts every browser found
+    tokio::task::spawn_blocking(detector::detect_all_browsers)
+        .await
+        .map_err(|e| format!("Failed to detect browsers: {}", e))
 }
 
 #[tauri::command]
@@ -129,7 +322,10 @@ pub async fn browse_for_browser_executable(app_handle: tauri::AppHandle) -> Resu
 }
 
 #[tauri::command]
-pub async fn launch_site(site_id: String, _app_handle: tauri::AppHandle) -> Result<(), String> {
+pub async fn launch_site(
+    site_id: String,
+    app_handle: tauri::AppHandle,
+) -> Result<LaunchReport, String> {
     println!("Launching site: {}", site_id);
 
     let settings_manager =
@@ -144,6 +340,7 @@ pub async fn launch_site(site_id: String, _app_handle: tauri::AppHandle) -> Resu
         .sites
         .iter()
         .find(|s| s.id == site_id)
+        .cloned()
         .ok_or_else(|| format!("Site with ID {} not found", site_id))?;
 
     // Find the browser
@@ -151,13 +348,36 @@ pub async fn launch_site(site_id: String, _app_handle: tauri::AppHandle) -> Resu
         .browsers
         .iter()
         .find(|b| b.id == site.browser_id)
+        .cloned()
         .ok_or_else(|| format!("Browser with ID {} not found", site.browser_id))?;
 
-    // Find the proxy if specified
-    let proxy = if let Some(proxy_id) = &site.proxy_id {
-        settings.proxies.iter().find(|p| p.id == *proxy_id).cloned()
-    } else {
-        None
+    // Find the proxy if specified, choosing a member when it is a group
+    let proxy = match &site.proxy_id {
+        Some(proxy_id) => match settings.proxy_groups.iter().find(|g| g.id == *proxy_id) {
+            Some(group) => {
+                let member = group::select(
+                    group,
+                    &settings.proxies,
+                    &app_handle.state::<HealthMonitor>(),
+                    &app_handle.state::<GroupCursors>(),
+                )?
+                .clone();
+                println!(
+                    "Proxy group '{}' selected '{}' ({:?})",
+                    group.name, member.name, group.strategy
+                );
+                Some(member)
+            }
+            None => Some(
+                settings
+                    .proxies
+                    .iter()
+                    .find(|p| p.id == *proxy_id)
+                    .cloned()
+                    .ok_or_else(|| format!("Proxy with ID {} not found", proxy_id))?,
+            ),
+        },
+        None => None,
     };
 
     // Determine profile name before creating config to avoid borrowing issues
@@ -169,18 +389,36 @@ pub async fn launch_site(site_id: String, _app_handle: tauri::AppHandle) -> Resu
 
     // Create launch configuration
     let config = LaunchConfig {
-        browser: browser.clone(),
+        browser,
         proxy,
+        proxies: settings.proxies.clone(),
+        pac_rules: settings.pac_rules.clone(),
         url: Some(site.url.clone()),
         profile_name,
         ignore_cert_errors: settings.ignore_cert_errors,
+        block_on_preflight_failure: settings.preflight_blocks_launch,
+        connection_log_size: settings.connection_log_size,
+        connection_log_to_file: settings.connection_log_to_file,
     };
 
-    launch_browser_with_config(config)
+    launch_browser_with_config(
+        config,
+        LaunchContext {
+            sessions: &app_handle.state::<SessionManager>(),
+            monitor: &app_handle.state::<HealthMonitor>(),
+            pac_server: &app_handle.state::<PacServer>(),
+            usage: &app_handle.state::<UsageTracker>(),
+            connection_log: &app_handle.state::<ConnectionLog>(),
+        },
+    )
+    .await
 }
 
 #[tauri::command]
-pub async fn launch_proxy(proxy_id: String, _app_handle: tauri::AppHandle) -> Result<(), String> {
+pub async fn launch_proxy(
+    proxy_id: String,
+    app_handle: tauri::AppHandle,
+) -> Result<LaunchReport, String> {
     println!("Testing proxy: {}", proxy_id);
 
     let settings_manager =
@@ -209,6 +447,8 @@ pub async fn launch_proxy(proxy_id: String, _app_handle: tauri::AppHandle) -> Re
     let config = LaunchConfig {
         browser: default_browser.clone(),
         proxy: Some(proxy.clone()),
+        proxies: settings.proxies.clone(),
+        pac_rules: settings.pac_rules.clone(),
         url: if settings.default_launch_url.is_empty() {
             None
         } else {
@@ -216,7 +456,20 @@ pub async fn launch_proxy(proxy_id: String, _app_handle: tauri::AppHandle) -> Re
         },
         profile_name: proxy.name.clone(),
         ignore_cert_errors: settings.ignore_cert_errors,
+        block_on_preflight_failure: settings.preflight_blocks_launch,
+        connection_log_size: settings.connection_log_size,
+        connection_log_to_file: settings.connection_log_to_file,
     };
 
-    launch_browser_with_config(config)
+    launch_browser_with_config(
+        config,
+        LaunchContext {
+            sessions: &app_handle.state::<SessionManager>(),
+            monitor: &app_handle.state::<HealthMonitor>(),
+            pac_server: &app_handle.state::<PacServer>(),
+            usage: &app_handle.state::<UsageTracker>(),
+            connection_log: &app_handle.state::<ConnectionLog>(),
+        },
+    )
+    .await
 }
@@ -1,7 +1,9 @@
 pub mod browser_commands;
+pub mod proxy_commands;
 pub mod settings_commands;
 pub mod window_commands;
 
 pub use browser_commands::*;
+pub use proxy_commands::*;
 pub use settings_commands::*;
 pub use window_commands::*;
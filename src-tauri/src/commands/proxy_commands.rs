@@ -0,0 +1,264 @@
+use crate::browser::session::SessionManager;
+use crate::browser::BrowserFamily;
+use crate::proxy::connection_log::{ConnectionEntry, ConnectionLog, ConnectionLogFilter};
+use crate::proxy::dns::{self, DnsLeakReport};
+use crate::proxy::geo::GeoIp;
+use crate::proxy::health::{self, HelperCheck, ProxyHealth};
+use crate::proxy::monitor::{self, HealthMonitor};
+use crate::proxy::pac;
+use crate::proxy::pac_eval::{self, PacEvaluation};
+use crate::proxy::tor;
+use crate::proxy::usage::{ProxyUsage, UsageTracker};
+use crate::settings::{PacRule, ProxyKind, SettingsManager};
+use tauri::Manager;
+
+#[tauri::command]
+pub async fn test_proxy(
+    proxy_id: String,
+    app_handle: tauri::AppHandle,
+) -> Result<ProxyHealth, String> {
+    println!("Checking proxy health: {}", proxy_id);
+
+    let settings_manager =
+        SettingsManager::new().map_err(|e| format!("Failed to create settings manager: {}", e))?;
+
+    let settings = settings_manager
+        .load_settings()
+        .map_err(|e| format!("Failed to load settings: {}", e))?;
+
+    let proxy = settings
+        .proxies
+        .iter()
+        .find(|p| p.id == proxy_id)
+        .ok_or_else(|| format!("Proxy with ID {} not found", proxy_id))?;
+
+    // A helper already running for a browser session holds the proxy's
+    // ports, so it is checked instead of starting another
+    let helper_check = match app_handle
+        .state::<SessionManager>()
+        .helper_proxy_server(&proxy.id)
+    {
+        Some(proxy_server) => HelperCheck::Running(Some(proxy_server)),
+        None => HelperCheck::Start,
+    };
+    let mut health = health::check_proxy(
+        proxy,
+        &settings.proxies,
+        &settings.pac_rules,
+        &settings.health_check_url,
+        helper_check,
+    )
+    .await;
+    app_handle
+        .state::<GeoIp>()
+        .locate(&mut health, &settings.geoip_databases);
+    monitor::publish(
+        &app_handle,
+        health.clone(),
+        settings.health_history_size,
+        proxy.fail_closed,
+    );
+
+    Ok(health)
+}
+
+#[tauri::command]
+pub async fn get_proxy_health(app_handle: tauri::AppHandle) -> Result<Vec<ProxyHealth>, String> {
+    Ok(app_handle.state::<HealthMonitor>().latest_all())
+}
+
+#[tauri::command]
+pub async fn get_proxy_health_history(
+    proxy_id: String,
+    app_handle: tauri::AppHandle,
+) -> Result<Vec<ProxyHealth>, String> {
+    Ok(app_handle.state::<HealthMonitor>().history(&proxy_id))
+}
+
+/// Daily traffic totals and running sessions metered by the local
+/// forwarder, for every proxy or only `proxy_id`.
+#[tauri::command]
+pub async fn get_proxy_usage(
+    proxy_id: Option<String>,
+    app_handle: tauri::AppHandle,
+) -> Result<ProxyUsage, String> {
+    let usage = app_handle.state::<UsageTracker>().inner().clone();
+    tokio::task::spawn_blocking(move || usage.usage(proxy_id.as_deref()))
+        .await
+        .map_err(|e| format!("Failed to read proxy usage: {}", e))
+}
+
+/// Logged forwarder requests matching `filter`, newest first.
+#[tauri::command]
+pub async fn get_connection_log(
+    filter: Option<ConnectionLogFilter>,
+    app_handle: tauri::AppHandle,
+) -> Result<Vec<ConnectionEntry>, String> {
+    Ok(app_handle
+        .state::<ConnectionLog>()
+        .query(&filter.unwrap_or_default()))
+}
+
+#[tauri::command]
+pub async fn clear_connection_log(app_handle: tauri::AppHandle) -> Result<(), String> {
+    app_handle.state::<ConnectionLog>().clear();
+    Ok(())
+}
+
+/// Runs the default browser against a local stub SOCKS5 proxy with the same
+/// DNS settings as `proxy_id`, reporting any hostname it resolved locally.
+#[tauri::command]
+pub async fn dns_leak_test(proxy_id: String) -> Result<DnsLeakReport, String> {
+    println!("Running DNS leak test for proxy: {}", proxy_id);
+
+    let settings_manager =
+        SettingsManager::new().map_err(|e| format!("Failed to create settings manager: {}", e))?;
+
+    let settings = settings_manager
+        .load_settings()
+        .map_err(|e| format!("Failed to load settings: {}", e))?;
+
+    let proxy = settings
+        .proxies
+        .iter()
+        .find(|p| p.id == proxy_id)
+        .ok_or_else(|| format!("Proxy with ID {} not found", proxy_id))?;
+    // SSH tunnels, Tor and SOCKS5 helpers are reached over SOCKS5 too, so the
+    // stub proxy stands in for them the same way
+    if !proxy.kind.resolves_remotely() {
+        return Err(format!(
+            "DNS leak tests are only available for proxies that can resolve hostnames remotely (SOCKS5, SSH, Tor or SOCKS5 helpers), not {} proxies",
+            proxy.kind.type_name()
+        ));
+    }
+
+    // The test drives headless Chromium, which other families cannot stand in for
+    let chromium_browsers = || {
+        settings
+            .browsers
+            .iter()
+            .filter(|b| b.family() == BrowserFamily::Chromium)
+    };
+    let browser = chromium_browsers()
+        .find(|b| b.id == settings.default_browser)
+        .or_else(|| chromium_browsers().next())
+        .ok_or_else(|| "DNS leak tests need a Chromium-based browser".to_string())?;
+
+    let report = dns::leak_test(browser, proxy.remote_dns).await?;
+    println!(
+        "DNS leak test for {} {}",
+        proxy.name,
+        if report.passed { "passed" } else { "failed" }
+    );
+
+    Ok(report)
+}
+
+/// Requests new Tor circuits for a Tor proxy. A launched Tor is asked in
+/// every browser session using it, an existing one on its control port.
+#[tauri::command]
+pub async fn tor_new_identity(
+    proxy_id: String,
+    app_handle: tauri::AppHandle,
+) -> Result<(), String> {
+    let settings_manager =
+        SettingsManager::new().map_err(|e| format!("Failed to create settings manager: {}", e))?;
+
+    let settings = settings_manager
+        .load_settings()
+        .map_err(|e| format!("Failed to load settings: {}", e))?;
+
+    let proxy = settings
+        .proxies
+        .iter()
+        .find(|p| p.id == proxy_id)
+        .ok_or_else(|| format!("Proxy with ID {} not found", proxy_id))?;
+    let ProxyKind::Tor(setup) = &proxy.kind else {
+        return Err(format!("Proxy '{}' is not a Tor proxy", proxy.name));
+    };
+
+    let control_ports = if setup.launched() {
+        app_handle
+            .state::<SessionManager>()
+            .control_ports(&proxy.id)
+    } else {
+        setup.control_port.into_iter().collect()
+    };
+    if control_ports.is_empty() {
+        return Err(if setup.launched() {
+            format!("No browser is running with Tor proxy '{}'", proxy.name)
+        } else {
+            format!("Tor proxy '{}' has no control port", proxy.name)
+        });
+    }
+
+    for control_port in control_ports {
+        tor::new_identity(&setup.host, control_port, setup.control_password.as_deref()).await?;
+    }
+    println!("Requested a new Tor identity for {}", proxy.name);
+
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn get_pac_rules() -> Result<Vec<PacRule>, String> {
+    let settings_manager =
+        SettingsManager::new().map_err(|e| format!("Failed to create settings manager: {}", e))?;
+
+    let settings = settings_manager
+        .load_settings()
+        .map_err(|e| format!("Failed to load settings: {}", e))?;
+
+    Ok(settings.pac_rules)
+}
+
+/// Replaces the PAC rules. Browsers pick the change up the next time they
+/// fetch the script from the local PAC server.
+#[tauri::command]
+pub async fn save_pac_rules(rules: Vec<PacRule>) -> Result<(), String> {
+    println!("Saving {} PAC rules", rules.len());
+
+    let settings_manager =
+        SettingsManager::new().map_err(|e| format!("Failed to create settings manager: {}", e))?;
+
+    let mut settings = settings_manager
+        .load_settings()
+        .map_err(|e| format!("Failed to load settings: {}", e))?;
+
+    pac::validate_rules(&rules, &settings.proxies)?;
+    settings.pac_rules = rules;
+
+    settings_manager
+        .save_settings(&settings)
+        .map_err(|e| format!("Failed to save settings: {}", e))
+}
+
+/// Runs the PAC script of `proxy_id`, local, remote or generated from rules,
+/// to preview which proxy the browser would use for `url`.
+#[tauri::command]
+pub async fn evaluate_pac(proxy_id: String, url: String) -> Result<PacEvaluation, String> {
+    println!("Evaluating PAC script of proxy {} for {}", proxy_id, url);
+
+    let settings_manager =
+        SettingsManager::new().map_err(|e| format!("Failed to create settings manager: {}", e))?;
+
+    let settings = settings_manager
+        .load_settings()
+        .map_err(|e| format!("Failed to load settings: {}", e))?;
+
+    let proxy = settings
+        .proxies
+        .iter()
+        .find(|p| p.id == proxy_id)
+        .ok_or_else(|| format!("Proxy with ID {} not found", proxy_id))?;
+
+    let script = match &proxy.kind {
+        ProxyKind::Pac { url } => pac::fetch_script(url).await?,
+        ProxyKind::Rules => pac::generate_script(&settings.pac_rules, &settings.proxies)?,
+        _ => return Err(format!("Proxy '{}' does not use a PAC script", proxy.name)),
+    };
+
+    tokio::task::spawn_blocking(move || pac_eval::evaluate(&script, &url))
+        .await
+        .map_err(|e| format!("PAC evaluation failed: {}", e))?
+}
@@ -1,4 +1,5 @@
 use crate::autostart::AutoStartManager;
+use crate::proxy::pac;
 use crate::settings::{AppSettings, SettingsManager};
 use std::env;
 
@@ -50,18 +51,56 @@ pub async fn save_settings(
 ) -> Result<(), String> {
     println!("Saving settings: {:?}", settings);
 
+    for proxy in &settings.proxies {
+        proxy.validate(&settings.proxies)?;
+    }
+    settings.validate_health_checks()?;
+
     let settings_manager =
         SettingsManager::new().map_err(|e| format!("Failed to create settings manager: {}", e))?;
 
     // Clean up sites that reference non-existent proxies
     let mut cleaned_settings = settings.clone();
 
-    let proxy_ids: std::collections::HashSet<String> = cleaned_settings
+    let mut proxy_ids: std::collections::HashSet<String> = cleaned_settings
         .proxies
         .iter()
         .map(|p| p.id.clone())
         .collect();
 
+    // Drop group members whose proxy was deleted
+    for group in &mut cleaned_settings.proxy_groups {
+        group.members.retain(|member| {
+            let exists = proxy_ids.contains(member);
+            if !exists {
+                println!(
+                    "Removing proxy '{}' from group '{}' because it no longer exists",
+                    member, group.name
+                );
+            }
+            exists
+        });
+    }
+
+    // Drop PAC rules whose proxy was deleted
+    cleaned_settings.pac_rules.retain(|rule| {
+        let exists = rule
+            .proxy_id
+            .as_ref()
+            .is_none_or(|proxy_id| proxy_ids.contains(proxy_id));
+        if !exists {
+            println!(
+                "Removing PAC rule '{}' because its proxy no longer exists",
+                rule.pattern
+            );
+        }
+        exists
+    });
+    pac::validate_rules(&cleaned_settings.pac_rules, &cleaned_settings.proxies)?;
+
+    // Sites may reference a proxy group instead of a single proxy
+    proxy_ids.extend(cleaned_settings.proxy_groups.iter().map(|g| g.id.clone()));
+
     let original_site_count = cleaned_settings.sites.len();
     cleaned_settings.sites.retain(|site| {
         if let Some(proxy_id) = &site.proxy_id {
@@ -187,6 +226,12 @@ pub async fn import_settings(file_path: String) -> Result<AppSettings, String> {
     let settings: AppSettings = serde_json::from_str(&content)
         .map_err(|e| format!("Failed to parse settings file: {}", e))?;
 
+    for proxy in &settings.proxies {
+        proxy.validate(&settings.proxies)?;
+    }
+    settings.validate_health_checks()?;
+    pac::validate_rules(&settings.pac_rules, &settings.proxies)?;
+
     let settings_manager =
         SettingsManager::new().map_err(|e| format!("Failed to create settings manager: {}", e))?;
 
@@ -1,37 +1,83 @@
 mod autostart;
 mod browser;
 mod commands;
+mod proxy;
 mod settings;
 
-use browser::detector;
+use browser::{detector, version};
+use browser::session::SessionManager;
+use proxy::connection_log::{self, ConnectionLog};
+use proxy::geo::GeoIp;
+use proxy::group::GroupCursors;
+use proxy::monitor::{self, HealthMonitor};
+use proxy::pac_server::PacServer;
+use proxy::usage::{self, UsageTracker};
 use settings::SettingsManager;
-use tauri::Manager;
+use tauri::{Emitter, Manager};
+
+/// Emitted once browsers detected or probed at startup have been saved.
+const BROWSERS_UPDATED_EVENT: &str = "browsers-updated";
 
 #[tauri::command]
 fn quit_app(app_handle: tauri::AppHandle) {
     app_handle.exit(0);
 }
 
-fn initialize_app() -> Result<(), Box<dyn std::error::Error>> {
+fn initialize_app(app_handle: &tauri::AppHandle) -> Result<(), Box<dyn std::error::Error>> {
     println!("Initializing application...");
 
     let settings_manager = SettingsManager::new()?;
-    let mut settings = settings_manager.load_settings()?;
+    let settings = settings_manager.load_settings()?;
 
     // Detect browsers if not already detected
-    if settings.browsers.is_empty() {
+    let (browsers, changed) = if settings.browsers.is_empty() {
         println!("Detecting browsers...");
         let detected_browsers = detector::detect_all_browsers();
         println!("Detected {} browsers", detected_browsers.len());
-
-        settings.browsers = detected_browsers;
-        settings_manager.save_settings(&settings)?;
-        println!("Browsers saved to settings");
+        (detected_browsers, true)
     } else {
         println!(
             "Browsers already detected: {} browsers",
             settings.browsers.len()
         );
+
+        // Browsers update themselves, so their versions are read again
+        let mut browsers = settings.browsers;
+        let changed = version::refresh_all(&mut browsers);
+        (browsers, changed)
+    };
+
+    for browser in &browsers {
+        for warning in version::startup_warnings(browser) {
+            eprintln!("Warning: {}", warning);
+        }
+    }
+    if !changed {
+        return Ok(());
+    }
+
+    // Settings may have been saved while the browsers were probed, so only
+    // what was probed is written back
+    let mut settings = settings_manager.load_settings()?;
+    if settings.browsers.is_empty() {
+        settings.browsers = browsers;
+    } else {
+        for browser in &mut settings.browsers {
+            if let Some(probed) = browsers
+                .iter()
+                .find(|probed| probed.id == browser.id && probed.path == browser.path)
+            {
+                browser.version = probed.version.clone();
+                browser.channel = probed.channel;
+                browser.family = probed.family;
+            }
+        }
+    }
+    settings_manager.save_settings(&settings)?;
+    println!("Browsers saved to settings");
+
+    if let Err(e) = app_handle.emit(BROWSERS_UPDATED_EVENT, ()) {
+        eprintln!("Failed to emit browsers updated event: {}", e);
     }
 
     Ok(())
@@ -42,6 +88,13 @@ pub fn run() {
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_dialog::init())
+        .manage(SessionManager::default())
+        .manage(HealthMonitor::default())
+        .manage(GroupCursors::default())
+        .manage(PacServer::default())
+        .manage(GeoIp::default())
+        .manage(UsageTracker::default())
+        .manage(ConnectionLog::default())
         .invoke_handler(tauri::generate_handler![
             commands::detect_browsers,
             commands::browse_for_browser_executable,
@@ -56,14 +109,38 @@ pub fn run() {
             commands::import_settings,
             commands::launch_site,
             commands::launch_proxy,
+            commands::test_proxy,
+            commands::get_proxy_health,
+            commands::get_proxy_health_history,
+            commands::get_proxy_usage,
+            commands::get_connection_log,
+            commands::clear_connection_log,
+            commands::dns_leak_test,
+            commands::tor_new_identity,
+            commands::get_pac_rules,
+            commands::save_pac_rules,
+            commands::evaluate_pac,
             commands::toggle_window,
             quit_app
         ])
         .setup(|app| {
-            // Initialize application settings and detect browsers
-            if let Err(e) = initialize_app() {
-                eprintln!("Failed to initialize application: {}", e);
-            }
+            // Initialize application settings and detect browsers. Reading
+            // browser versions starts each of them, so it runs in the background
+            let app_handle = app.handle().clone();
+            tauri::async_runtime::spawn_blocking(move || {
+                if let Err(e) = initialize_app(&app_handle) {
+                    eprintln!("Failed to initialize application: {}", e);
+                }
+            });
+
+            // Start probing configured proxies in the background
+            monitor::spawn(app.handle().clone());
+
+            // Persist traffic of metered browser sessions
+            usage::spawn(app.handle().clone());
+
+            // Stream logged forwarder requests to the frontend
+            connection_log::spawn(app.handle().clone());
 
             // Check if launched with --minimized flag (auto-start)
             let args: Vec<String> = std::env::args().collect();
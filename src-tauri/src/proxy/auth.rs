@@ -0,0 +1,16 @@
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+
+/// A username and password for authenticating to a proxy.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Credentials {
+    pub username: String,
+    pub password: String,
+}
+
+impl Credentials {
+    pub fn basic_auth(&self) -> String {
+        let token = BASE64.encode(format!("{}:{}", self.username, self.password));
+        format!("Basic {}", token)
+    }
+}
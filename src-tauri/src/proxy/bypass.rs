@@ -0,0 +1,321 @@
+use reqwest::Url;
+use std::net::IpAddr;
+
+/// Matches hostnames without a dot, e.g. `intranet`.
+const LOCAL_RULE: &str = "<local>";
+/// Removes Chromium's implicit bypass of loopback and link-local addresses.
+const SUBTRACT_IMPLICIT_RULE: &str = "<-loopback>";
+
+/// A single entry of Chromium's `--proxy-bypass-list`, e.g. `*.internal.corp`,
+/// `10.0.0.0/8` or `https://example.com:8443`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BypassRule {
+    Local,
+    SubtractImplicit,
+    Network {
+        address: IpAddr,
+        prefix_len: u8,
+    },
+    Host {
+        scheme: Option<String>,
+        pattern: String,
+        port: Option<u16>,
+    },
+}
+
+impl BypassRule {
+    pub fn parse(rule: &str) -> Result<Self, String> {
+        let rule = rule.trim();
+        if rule.is_empty() {
+            return Err("Bypass rule is empty".to_string());
+        }
+        if rule.eq_ignore_ascii_case(LOCAL_RULE) {
+            return Ok(BypassRule::Local);
+        }
+        if rule.eq_ignore_ascii_case(SUBTRACT_IMPLICIT_RULE) {
+            return Ok(BypassRule::SubtractImplicit);
+        }
+        if rule.starts_with('<') {
+            return Err(format!("Unknown bypass rule {}", rule));
+        }
+
+        let (scheme, rest) = match rule.split_once("://") {
+            Some((scheme, rest)) => {
+                if scheme.is_empty() || !scheme.chars().all(|c| c.is_ascii_alphanumeric()) {
+                    return Err(format!("Invalid scheme in bypass rule {}", rule));
+                }
+                (Some(scheme.to_ascii_lowercase()), rest)
+            }
+            None => (None, rule),
+        };
+
+        if let Some((address, prefix_len)) = rest.split_once('/') {
+            if scheme.is_some() {
+                return Err(format!("CIDR bypass rule {} cannot have a scheme", rule));
+            }
+            return parse_network(address, prefix_len)
+                .ok_or_else(|| format!("Invalid CIDR range in bypass rule {}", rule));
+        }
+
+        let (pattern, port) =
+            split_port(rest).ok_or_else(|| format!("Invalid port in bypass rule {}", rule))?;
+        let valid_pattern = !pattern.is_empty()
+            && (pattern.parse::<IpAddr>().is_ok()
+                || pattern
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "-._*".contains(c)));
+        if !valid_pattern {
+            return Err(format!("Invalid host in bypass rule {}", rule));
+        }
+
+        // A leading dot matches the domain and all of its subdomains
+        let pattern = if pattern.starts_with('.') {
+            format!("*{}", pattern.to_ascii_lowercase())
+        } else {
+            pattern.to_ascii_lowercase()
+        };
+
+        Ok(BypassRule::Host {
+            scheme,
+            pattern,
+            port,
+        })
+    }
+
+    pub fn matches(&self, url: &Url) -> bool {
+        match self {
+            BypassRule::Local => {
+                host_ip(url).is_none() && url.host_str().is_some_and(|host| !host.contains('.'))
+            }
+            BypassRule::SubtractImplicit => false,
+            BypassRule::Network {
+                address,
+                prefix_len,
+            } => host_ip(url).is_some_and(|ip| in_network(ip, *address, *prefix_len)),
+            BypassRule::Host {
+                scheme,
+                pattern,
+                port,
+            } => {
+                if scheme.as_deref().is_some_and(|s| s != url.scheme()) {
+                    return false;
+                }
+                if port.is_some() && *port != url.port_or_known_default() {
+                    return false;
+                }
+                match host_ip(url) {
+                    Some(ip) => pattern.parse::<IpAddr>().is_ok_and(|p| p == ip),
+                    None => url
+                        .host_str()
+                        .is_some_and(|host| wildcard_match(pattern, &host.to_ascii_lowercase())),
+                }
+            }
+        }
+    }
+}
+
+/// Checks every rule, returning the first syntax error.
+pub fn validate(rules: &[String]) -> Result<(), String> {
+    rules
+        .iter()
+        .try_for_each(|rule| BypassRule::parse(rule).map(|_| ()))
+}
+
+/// The value for `--proxy-bypass-list`, or `None` when there are no rules.
+pub fn to_flag_value(rules: &[String]) -> Option<String> {
+    let rules: Vec<&str> = rules
+        .iter()
+        .map(|rule| rule.trim())
+        .filter(|rule| !rule.is_empty())
+        .collect();
+    (!rules.is_empty()).then(|| rules.join(";"))
+}
+
+/// Whether the browser would connect to `url` directly instead of through
+/// the proxy. Invalid rules are ignored, as Chromium does.
+pub fn bypasses(rules: &[String], url: &Url) -> bool {
+    let rules: Vec<BypassRule> = rules
+        .iter()
+        .filter_map(|rule| BypassRule::parse(rule).ok())
+        .collect();
+
+    let implicit = !rules.contains(&BypassRule::SubtractImplicit);
+    (implicit && is_implicitly_bypassed(url)) || rules.iter().any(|rule| rule.matches(url))
+}
+
+/// Chromium never proxies localhost, loopback or link-local addresses
+/// unless `<-loopback>` is given.
+fn is_implicitly_bypassed(url: &Url) -> bool {
+    match host_ip(url) {
+        Some(IpAddr::V4(ip)) => ip.is_loopback() || ip.is_link_local(),
+        Some(IpAddr::V6(ip)) => ip.is_loopback() || (ip.segments()[0] & 0xffc0) == 0xfe80,
+        None => url.host_str().is_some_and(|host| {
+            let host = host.to_ascii_lowercase();
+            host == "localhost" || host.ends_with(".localhost")
+        }),
+    }
+}
+
+fn host_ip(url: &Url) -> Option<IpAddr> {
+    url.host_str()?
+        .trim_matches(|c| c == '[' || c == ']')
+        .parse()
+        .ok()
+}
+
+fn parse_network(address: &str, prefix_len: &str) -> Option<BypassRule> {
+    let address: IpAddr = address
+        .trim_matches(|c| c == '[' || c == ']')
+        .parse()
+        .ok()?;
+    let prefix_len: u8 = prefix_len.parse().ok()?;
+    let max = if address.is_ipv4() { 32 } else { 128 };
+    (prefix_len <= max).then_some(BypassRule::Network {
+        address,
+        prefix_len,
+    })
+}
+
+fn in_network(ip: IpAddr, network: IpAddr, prefix_len: u8) -> bool {
+    match (ip, network) {
+        (IpAddr::V4(ip), IpAddr::V4(network)) => {
+            let mask = u32::MAX.checked_shl(32 - prefix_len as u32).unwrap_or(0);
+            u32::from(ip) & mask == u32::from(network) & mask
+        }
+        (IpAddr::V6(ip), IpAddr::V6(network)) => {
+            let mask = u128::MAX.checked_shl(128 - prefix_len as u32).unwrap_or(0);
+            u128::from(ip) & mask == u128::from(network) & mask
+        }
+        _ => false,
+    }
+}
+
+/// Splits `host[:port]`, where IPv6 literals must be bracketed.
+fn split_port(value: &str) -> Option<(&str, Option<u16>)> {
+    if let Some(rest) = value.strip_prefix('[') {
+        let (host, after) = rest.split_once(']')?;
+        return match after.strip_prefix(':') {
+            Some(port) => Some((host, Some(port.parse().ok()?))),
+            None if after.is_empty() => Some((host, None)),
+            None => None,
+        };
+    }
+
+    match value.rsplit_once(':') {
+        Some((host, port)) if !host.contains(':') => Some((host, Some(port.parse().ok()?))),
+        // Unbracketed IPv6 literals cannot carry a port
+        _ => Some((value, None)),
+    }
+}
+
+fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = text.strip_prefix(first) else {
+        return false;
+    };
+
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        return rest.is_empty();
+    };
+
+    for part in middle {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+    rest.len() >= last.len() && rest.ends_with(last)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bypassed(rules: &[&str], url: &str) -> bool {
+        let rules: Vec<String> = rules.iter().map(|rule| rule.to_string()).collect();
+        bypasses(&rules, &Url::parse(url).unwrap())
+    }
+
+    #[test]
+    fn matches_host_wildcards() {
+        assert!(bypassed(&["*.corp"], "https://wiki.corp/"));
+        assert!(bypassed(&["*.corp"], "http://a.b.corp:8080/"));
+        assert!(!bypassed(&["*.corp"], "https://corp/"));
+        assert!(!bypassed(&["*.corp"], "https://wiki.corp.example.com/"));
+        assert!(bypassed(&[".Example.com"], "https://www.example.com/"));
+        assert!(bypassed(&["intranet"], "http://INTRANET/"));
+    }
+
+    #[test]
+    fn matches_networks() {
+        assert!(bypassed(&["10.0.0.0/8"], "http://10.20.30.40/"));
+        assert!(!bypassed(&["10.0.0.0/8"], "http://11.0.0.1/"));
+        assert!(bypassed(&["fd00::/8"], "http://[fd12::1]/"));
+        assert!(!bypassed(&["fd00::/8"], "http://10.0.0.1/"));
+        // Networks only ever match IP literals, never hostnames
+        assert!(!bypassed(&["10.0.0.0/8"], "http://ten.example/"));
+    }
+
+    #[test]
+    fn matches_scheme_and_port() {
+        let rule = ["https://example.com:8443"];
+        assert!(bypassed(&rule, "https://example.com:8443/login"));
+        assert!(!bypassed(&rule, "https://example.com/"));
+        assert!(!bypassed(&rule, "http://example.com:8443/"));
+
+        // Default ports count as given
+        assert!(bypassed(&["http://example.com:80"], "http://example.com/"));
+        assert!(bypassed(
+            &["[::1]:8080", "<-loopback>"],
+            "http://[::1]:8080/"
+        ));
+    }
+
+    #[test]
+    fn matches_local_hostnames() {
+        assert!(bypassed(&["<local>"], "http://intranet/"));
+        assert!(!bypassed(&["<local>"], "http://intranet.corp/"));
+        assert!(!bypassed(&["<local>"], "http://[2001:db8::1]/"));
+    }
+
+    #[test]
+    fn bypasses_loopback_and_link_local_implicitly() {
+        for url in [
+            "http://localhost:3000/",
+            "http://app.localhost/",
+            "http://127.0.0.1/",
+            "http://169.254.1.1/",
+            "http://[::1]/",
+            "http://[fe80::1]/",
+        ] {
+            assert!(bypassed(&[], url), "{} was proxied", url);
+            assert!(
+                !bypassed(&["<-loopback>"], url),
+                "{} bypassed despite <-loopback>",
+                url
+            );
+        }
+        assert!(!bypassed(&[], "http://[fec0::1]/"));
+        assert!(!bypassed(&[], "http://example.com/"));
+    }
+
+    #[test]
+    fn rejects_bad_syntax() {
+        for rule in [
+            "",
+            "<remote>",
+            "10.0.0.0/33",
+            "10.0.0.0/x",
+            "https://10.0.0.0/8",
+            "ht tp://example.com",
+            "example.com:port",
+            "[::1",
+            "exa mple.com",
+        ] {
+            assert!(BypassRule::parse(rule).is_err(), "{:?} was accepted", rule);
+        }
+        assert!(validate(&["*.corp".to_string(), "<LOCAL>".to_string()]).is_ok());
+    }
+}
@@ -0,0 +1,289 @@
+use super::usage::TrafficTotals;
+use crate::settings::SettingsManager;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::{Emitter, Manager};
+use tokio::sync::broadcast;
+
+pub const CONNECTION_LOGGED_EVENT: &str = "connection-logged";
+
+const LOG_FILE: &str = "connections.log";
+
+/// Size at which the log file is rotated.
+const MAX_FILE_SIZE: u64 = 5 * 1024 * 1024;
+
+/// Rotated files kept next to the current one, as `connections.log.1` etc.
+const ROTATED_FILES: usize = 3;
+
+/// One request handled by a local forwarder.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ConnectionEntry {
+    pub id: u64,
+    pub started_at: DateTime<Utc>,
+    /// Profile of the browser session that made the request.
+    pub profile: String,
+    pub proxy_id: String,
+    /// `CONNECT` for tunnels, otherwise the method of a plain HTTP request.
+    pub method: String,
+    /// The `host:port` requested.
+    pub target: String,
+    /// The upstream hops the request went through, e.g. `a:8080 -> b:1080`.
+    pub upstream: String,
+    /// The status returned to the browser: 200 or 502 for tunnels, the
+    /// origin's status for plain HTTP. `None` when none was received.
+    pub status: Option<u16>,
+    #[serde(flatten)]
+    pub traffic: TrafficTotals,
+    pub duration_ms: u64,
+    pub error: Option<String>,
+}
+
+/// What the forwarder knows about a request once it is done.
+pub struct ConnectionRecord {
+    pub started_at: DateTime<Utc>,
+    pub method: String,
+    pub target: String,
+    pub upstream: String,
+    pub status: Option<u16>,
+    pub traffic: TrafficTotals,
+    pub duration: Duration,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct ConnectionLogFilter {
+    pub proxy_id: Option<String>,
+    pub profile: Option<String>,
+    /// Matched case-insensitively anywhere in the target.
+    pub host: Option<String>,
+    /// Only requests that failed or got an error status.
+    #[serde(default)]
+    pub failed_only: bool,
+    pub since: Option<DateTime<Utc>>,
+    /// Most recent entries to return.
+    pub limit: Option<usize>,
+}
+
+impl ConnectionLogFilter {
+    fn matches(&self, entry: &ConnectionEntry) -> bool {
+        self.proxy_id
+            .as_ref()
+            .is_none_or(|proxy_id| entry.proxy_id == *proxy_id)
+            && self
+                .profile
+                .as_ref()
+                .is_none_or(|profile| entry.profile == *profile)
+            && self.host.as_ref().is_none_or(|host| {
+                entry
+                    .target
+                    .to_ascii_lowercase()
+                    .contains(&host.to_ascii_lowercase())
+            })
+            && (!self.failed_only || entry.failed())
+            && self.since.is_none_or(|since| entry.started_at >= since)
+    }
+}
+
+impl ConnectionEntry {
+    fn failed(&self) -> bool {
+        self.error.is_some() || self.status.is_none_or(|status| status >= 400)
+    }
+}
+
+struct LogFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+}
+
+impl LogFile {
+    fn open(path: PathBuf) -> std::io::Result<Self> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let size = file.metadata()?.len();
+        Ok(Self { path, file, size })
+    }
+
+    fn append(&mut self, entry: &ConnectionEntry) -> std::io::Result<()> {
+        let mut line = serde_json::to_string(entry)?;
+        line.push('\n');
+        if self.size + line.len() as u64 > MAX_FILE_SIZE {
+            self.rotate()?;
+        }
+
+        self.file.write_all(line.as_bytes())?;
+        self.size += line.len() as u64;
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> std::io::Result<()> {
+        let rotated = |index: usize| PathBuf::from(format!("{}.{}", self.path.display(), index));
+        for index in (1..ROTATED_FILES).rev() {
+            if rotated(index).exists() {
+                std::fs::rename(rotated(index), rotated(index + 1))?;
+            }
+        }
+        std::fs::rename(&self.path, rotated(1))?;
+
+        *self = Self::open(self.path.clone())?;
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct LogState {
+    entries: VecDeque<ConnectionEntry>,
+    next_id: u64,
+    /// Opened when a session that logs to file records its first entry.
+    file: Option<LogFile>,
+}
+
+/// Keeps the most recent requests handled by local forwarders and, when
+/// enabled in settings, appends them to a rotating file in the settings
+/// directory.
+#[derive(Clone)]
+pub struct ConnectionLog {
+    state: Arc<Mutex<LogState>>,
+    sender: broadcast::Sender<ConnectionEntry>,
+}
+
+impl Default for ConnectionLog {
+    fn default() -> Self {
+        Self {
+            state: Arc::default(),
+            sender: broadcast::channel(256).0,
+        }
+    }
+}
+
+impl ConnectionLog {
+    /// A logger for the forwarder of one browser session, keeping at most
+    /// `limit` entries in memory.
+    pub fn logger(
+        &self,
+        profile: &str,
+        proxy_id: &str,
+        limit: usize,
+        to_file: bool,
+    ) -> ConnectionLogger {
+        ConnectionLogger {
+            log: self.clone(),
+            profile: profile.to_string(),
+            proxy_id: proxy_id.to_string(),
+            limit,
+            to_file,
+        }
+    }
+
+    /// Matching entries, newest first.
+    pub fn query(&self, filter: &ConnectionLogFilter) -> Vec<ConnectionEntry> {
+        self.state
+            .lock()
+            .unwrap()
+            .entries
+            .iter()
+            .rev()
+            .filter(|entry| filter.matches(entry))
+            .take(filter.limit.unwrap_or(usize::MAX))
+            .cloned()
+            .collect()
+    }
+
+    pub fn clear(&self) {
+        self.state.lock().unwrap().entries.clear();
+    }
+
+    fn record(&self, mut entry: ConnectionEntry, limit: usize, to_file: bool) {
+        {
+            let mut state = self.state.lock().unwrap();
+            entry.id = state.next_id;
+            state.next_id += 1;
+
+            if to_file {
+                if state.file.is_none() {
+                    match log_path().and_then(|path| Ok(LogFile::open(path)?)) {
+                        Ok(file) => state.file = Some(file),
+                        Err(e) => eprintln!("Failed to open connection log file: {}", e),
+                    }
+                }
+                if let Some(file) = state.file.as_mut() {
+                    if let Err(e) = file.append(&entry) {
+                        eprintln!("Failed to write connection log file: {}", e);
+                    }
+                }
+            }
+
+            state.entries.push_back(entry.clone());
+            while state.entries.len() > limit.max(1) {
+                state.entries.pop_front();
+            }
+        }
+
+        // Nobody listening is fine
+        let _ = self.sender.send(entry);
+    }
+}
+
+/// Records the requests of one browser session's forwarder.
+#[derive(Clone)]
+pub struct ConnectionLogger {
+    log: ConnectionLog,
+    profile: String,
+    proxy_id: String,
+    limit: usize,
+    to_file: bool,
+}
+
+impl ConnectionLogger {
+    pub fn record(&self, record: ConnectionRecord) {
+        let entry = ConnectionEntry {
+            id: 0,
+            started_at: record.started_at,
+            profile: self.profile.clone(),
+            proxy_id: self.proxy_id.clone(),
+            method: record.method,
+            target: record.target,
+            upstream: record.upstream,
+            status: record.status,
+            traffic: record.traffic,
+            duration_ms: record.duration.as_millis() as u64,
+            error: record.error,
+        };
+        self.log.record(entry, self.limit, self.to_file);
+    }
+}
+
+fn log_path() -> Result<PathBuf, Box<dyn std::error::Error>> {
+    Ok(SettingsManager::new()?
+        .get_settings_dir()?
+        .join("logs")
+        .join(LOG_FILE))
+}
+
+/// Streams new entries to the frontend as they are recorded.
+pub fn spawn(app_handle: tauri::AppHandle) {
+    let mut receiver = app_handle.state::<ConnectionLog>().sender.subscribe();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            match receiver.recv().await {
+                Ok(entry) => {
+                    if let Err(e) = app_handle.emit(CONNECTION_LOGGED_EVENT, entry) {
+                        eprintln!("Failed to emit connection log event: {}", e);
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    eprintln!("Connection log event stream skipped {} entries", skipped);
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    });
+}
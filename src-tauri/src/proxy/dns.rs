@@ -0,0 +1,315 @@
+use super::bypass::BypassRule;
+use crate::browser::Browser;
+use reqwest::Url;
+use serde::Serialize;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// How long the leak test waits for the browser to request every probe.
+const LEAK_TEST_TIMEOUT: Duration = Duration::from_secs(15);
+const PROBE_POLL: Duration = Duration::from_millis(200);
+
+const SOCKS_VERSION: u8 = 5;
+const ATYP_IPV4: u8 = 1;
+const ATYP_DOMAIN: u8 = 3;
+const ATYP_IPV6: u8 = 4;
+
+/// Chromium flags that keep hostname resolution on the SOCKS5 proxy.
+///
+/// Every local lookup is mapped to NOTFOUND except localhost, the proxy
+/// itself and host rules on the bypass list, which the browser has to reach
+/// directly. CIDR bypass rules get no exclusion: they still go direct for IP
+/// literals, but a hostname inside the range cannot be resolved in this mode.
+/// `<local>` has no resolver equivalent and is rejected by validation.
+/// Prefetching is disabled so no speculative lookups are attempted, and
+/// WebRTC may only use UDP through the proxy, which keeps STUN from resolving
+/// or revealing local addresses.
+pub fn remote_dns_args(proxy_server: &str, bypass: &[String]) -> Vec<String> {
+    let mut rules = vec![
+        "MAP * ~NOTFOUND".to_string(),
+        "EXCLUDE localhost".to_string(),
+    ];
+
+    if let Some(host) = Url::parse(proxy_server)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_string))
+    {
+        rules.push(format!("EXCLUDE {}", host));
+    }
+    for rule in bypass {
+        if let Ok(BypassRule::Host { pattern, .. }) = BypassRule::parse(rule) {
+            rules.push(format!("EXCLUDE {}", pattern));
+        }
+    }
+
+    vec![
+        format!("--host-resolver-rules={}", rules.join(" , ")),
+        "--force-webrtc-ip-handling-policy=disable_non_proxied_udp".to_string(),
+        "--dns-prefetch-disable".to_string(),
+    ]
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct DnsProbe {
+    pub hostname: String,
+    /// Whether the browser asked the proxy to resolve the hostname.
+    pub resolved_remotely: bool,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct DnsLeakReport {
+    pub passed: bool,
+    pub probes: Vec<DnsProbe>,
+    /// Connections the browser made to IP addresses it resolved itself.
+    pub local_lookups: Vec<String>,
+}
+
+/// A CONNECT request received by the stub proxy.
+enum Target {
+    Domain(String),
+    Address(String),
+}
+
+#[derive(Default)]
+struct Observed {
+    domains: Vec<String>,
+    addresses: Vec<String>,
+}
+
+/// Launches `browser` headless against a stub SOCKS5 proxy that also
+/// serves the probe pages, then reports which probe hostnames reached the
+/// proxy unresolved. A probe that never arrives or arrives as an IP address
+/// was looked up by the browser itself. `remote_dns` adds the same flags a
+/// launch in remote DNS mode would.
+pub async fn leak_test(browser: &Browser, remote_dns: bool) -> Result<DnsLeakReport, String> {
+    let listener = TcpListener::bind("127.0.0.1:0")
+        .await
+        .map_err(|e| format!("Failed to start DNS leak test proxy: {}", e))?;
+    let proxy_addr = listener
+        .local_addr()
+        .map_err(|e| format!("Failed to start DNS leak test proxy: {}", e))?;
+
+    let token = probe_token();
+    let probes: Vec<String> = ["page", "image", "script", "frame"]
+        .iter()
+        .map(|kind| format!("{}-{}.dns-leak.test", kind, token))
+        .collect();
+
+    let observed = Arc::new(Mutex::new(Observed::default()));
+    let page = Arc::new((probes[0].clone(), probe_page(&probes)));
+    let accept_task = {
+        let observed = observed.clone();
+        tokio::spawn(async move {
+            while let Ok((client, _)) = listener.accept().await {
+                let observed = observed.clone();
+                let page = page.clone();
+                tokio::spawn(async move {
+                    let (page_host, page) = page.as_ref();
+                    let _ = serve_probe(client, &observed, page_host, page).await;
+                });
+            }
+        })
+    };
+
+    let profile_dir = std::env::temp_dir().join(format!("browser-proxy-launcher-dns-{}", token));
+    let result = run_browser(
+        browser,
+        remote_dns,
+        &format!("socks5://{}", proxy_addr),
+        &profile_dir,
+        &probes,
+        &observed,
+    )
+    .await;
+
+    accept_task.abort();
+    let _ = std::fs::remove_dir_all(&profile_dir);
+    result?;
+
+    let observed = observed.lock().unwrap();
+    let probes: Vec<DnsProbe> = probes
+        .into_iter()
+        .map(|hostname| DnsProbe {
+            resolved_remotely: observed.domains.contains(&hostname),
+            hostname,
+        })
+        .collect();
+    let local_lookups = observed.addresses.clone();
+
+    Ok(DnsLeakReport {
+        passed: local_lookups.is_empty() && probes.iter().all(|probe| probe.resolved_remotely),
+        probes,
+        local_lookups,
+    })
+}
+
+async fn run_browser(
+    browser: &Browser,
+    remote_dns: bool,
+    proxy_server: &str,
+    profile_dir: &Path,
+    probes: &[String],
+    observed: &Mutex<Observed>,
+) -> Result<(), String> {
+    let mut command = browser.command(&[profile_dir]);
+    command
+        .arg("--headless=new")
+        .arg("--no-first-run")
+        .arg("--no-default-browser-check")
+        .arg(format!("--user-data-dir={}", profile_dir.to_string_lossy()))
+        .arg(format!("--proxy-server={}", proxy_server));
+    if remote_dns {
+        command.args(remote_dns_args(proxy_server, &[]));
+    }
+    let mut child = command
+        .arg(format!("http://{}/", probes[0]))
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| format!("Failed to launch browser for DNS leak test: {}", e))?;
+
+    let deadline = tokio::time::Instant::now() + LEAK_TEST_TIMEOUT;
+    while tokio::time::Instant::now() < deadline {
+        let done = {
+            let observed = observed.lock().unwrap();
+            probes.iter().all(|probe| observed.domains.contains(probe))
+        };
+        if done {
+            break;
+        }
+        if let Ok(Some(status)) = child.try_wait() {
+            if !observed.lock().unwrap().domains.contains(&probes[0]) {
+                return Err(format!("Browser exited during DNS leak test ({})", status));
+            }
+            break;
+        }
+        tokio::time::sleep(PROBE_POLL).await;
+    }
+
+    let _ = child.kill().await;
+    Ok(())
+}
+
+/// Handles one stub proxy connection: records the requested target, accepts
+/// it and answers HTTP requests on the tunnel, serving the probe page to
+/// `page_host` and an empty page to every other probe.
+async fn serve_probe(
+    mut client: TcpStream,
+    observed: &Mutex<Observed>,
+    page_host: &str,
+    page: &str,
+) -> std::io::Result<()> {
+    let mut header = [0u8; 2];
+    client.read_exact(&mut header).await?;
+    let mut methods = vec![0u8; header[1] as usize];
+    client.read_exact(&mut methods).await?;
+    if header[0] != SOCKS_VERSION {
+        return Ok(());
+    }
+    // No authentication
+    client.write_all(&[SOCKS_VERSION, 0]).await?;
+
+    let mut request = [0u8; 4];
+    client.read_exact(&mut request).await?;
+    let target = match request[3] {
+        ATYP_IPV4 => {
+            let mut address = [0u8; 4];
+            client.read_exact(&mut address).await?;
+            Target::Address(std::net::Ipv4Addr::from(address).to_string())
+        }
+        ATYP_IPV6 => {
+            let mut address = [0u8; 16];
+            client.read_exact(&mut address).await?;
+            Target::Address(std::net::Ipv6Addr::from(address).to_string())
+        }
+        ATYP_DOMAIN => {
+            let mut len = [0u8; 1];
+            client.read_exact(&mut len).await?;
+            let mut domain = vec![0u8; len[0] as usize];
+            client.read_exact(&mut domain).await?;
+            Target::Domain(String::from_utf8_lossy(&domain).to_ascii_lowercase())
+        }
+        _ => return Ok(()),
+    };
+    let mut port = [0u8; 2];
+    client.read_exact(&mut port).await?;
+
+    let body = match target {
+        Target::Domain(domain) => {
+            let body = if domain == page_host { page } else { "" };
+            observed.lock().unwrap().domains.push(domain);
+            body
+        }
+        Target::Address(address) => {
+            observed.lock().unwrap().addresses.push(address);
+            ""
+        }
+    };
+
+    client
+        .write_all(&[SOCKS_VERSION, 0, 0, ATYP_IPV4, 0, 0, 0, 0, 0, 0])
+        .await?;
+
+    // Only plain HTTP is served; TLS attempts are simply closed
+    let mut buffer = [0u8; 4096];
+    let read = client.read(&mut buffer).await?;
+    if !buffer[..read].starts_with(b"GET ") {
+        return Ok(());
+    }
+    let response = format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: text/html\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        body.len(),
+        body
+    );
+    client.write_all(response.as_bytes()).await
+}
+
+/// The page served for every probe. Loading it from the first probe makes
+/// the browser fetch each remaining probe hostname as a subresource.
+fn probe_page(probes: &[String]) -> String {
+    let subresources = &probes[1..];
+    let mut page = String::from("<!doctype html><html><body>");
+    if let [image, script, frame, ..] = subresources {
+        page.push_str(&format!("<img src=\"http://{}/probe.png\">", image));
+        page.push_str(&format!(
+            "<script src=\"http://{}/probe.js\"></script>",
+            script
+        ));
+        page.push_str(&format!("<iframe src=\"http://{}/\"></iframe>", frame));
+    }
+    page.push_str("</body></html>");
+    page
+}
+
+fn probe_token() -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_nanos())
+        .unwrap_or_default();
+    format!("{:x}{:x}", nanos, std::process::id())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn excludes_proxy_localhost_and_bypassed_hosts() {
+        let bypass = vec![
+            "*.corp".to_string(),
+            "https://.example.com:8443".to_string(),
+            "10.0.0.0/8".to_string(),
+            "<-loopback>".to_string(),
+        ];
+        assert_eq!(
+            remote_dns_args("socks5://proxy.example.net:1080", &bypass),
+            vec![
+                "--host-resolver-rules=MAP * ~NOTFOUND , EXCLUDE localhost , EXCLUDE proxy.example.net , EXCLUDE *.corp , EXCLUDE *.example.com",
+                "--force-webrtc-ip-handling-policy=disable_non_proxied_udp",
+                "--dns-prefetch-disable",
+            ]
+        );
+    }
+}
@@ -0,0 +1,929 @@
+use super::auth::Credentials;
+use super::connection_log::{ConnectionLogger, ConnectionRecord};
+use super::kill_switch::KillSwitch;
+use super::usage::{Metered, SessionMeter, TrafficTotals};
+use crate::settings::{ProxyConfig, ProxyKind};
+use chrono::Utc;
+use reqwest::Url;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+use tokio_native_tls::{native_tls, TlsConnector};
+use tokio_socks::tcp::{Socks4Stream, Socks5Stream};
+use tokio_socks::IntoTargetAddr;
+
+const MAX_HEAD_SIZE: usize = 64 * 1024;
+
+/// A bidirectional byte stream to or through a proxy.
+pub trait ProxyStream: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> ProxyStream for T {}
+
+pub type BoxedStream = Box<dyn ProxyStream>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpstreamProtocol {
+    Http,
+    /// An HTTP proxy reached over TLS.
+    Https,
+    /// SOCKS4 resolves hostnames locally, SOCKS4a lets the proxy resolve them.
+    Socks4,
+    Socks4a,
+    Socks5,
+}
+
+/// The proxy the local forwarder relays browser traffic to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Upstream {
+    pub protocol: UpstreamProtocol,
+    pub host: String,
+    pub port: u16,
+    pub credentials: Option<Credentials>,
+}
+
+impl Upstream {
+    /// Builds the upstream for a proxy the forwarder can relay to.
+    pub fn for_proxy(kind: &ProxyKind) -> Option<Self> {
+        let (protocol, server) = match kind {
+            ProxyKind::Http(server) => (UpstreamProtocol::Http, server),
+            ProxyKind::Https(server) => (UpstreamProtocol::Https, server),
+            ProxyKind::Socks4(server) => (UpstreamProtocol::Socks4, server),
+            ProxyKind::Socks4a(server) => (UpstreamProtocol::Socks4a, server),
+            ProxyKind::Socks5(server) => (UpstreamProtocol::Socks5, server),
+            // An existing Tor is a plain SOCKS5 proxy without credentials
+            ProxyKind::Tor(tor) => {
+                return (!tor.launched()).then(|| Upstream {
+                    protocol: UpstreamProtocol::Socks5,
+                    host: tor.host.clone(),
+                    port: tor.port,
+                    credentials: None,
+                })
+            }
+            ProxyKind::Pac { .. }
+            | ProxyKind::Chain { .. }
+            | ProxyKind::Rules
+            | ProxyKind::Ssh(_)
+            | ProxyKind::Helper(_) => return None,
+        };
+
+        Some(Upstream {
+            protocol,
+            host: server.host.clone(),
+            port: server.port,
+            credentials: server.credentials(),
+        })
+    }
+
+    /// The hops the forwarder relays through for `proxy`: the proxy itself,
+    /// or every hop of a chain in order.
+    pub fn route(proxy: &ProxyConfig, proxies: &[ProxyConfig]) -> Result<Vec<Self>, String> {
+        let kinds: Vec<&ProxyKind> = match &proxy.kind {
+            ProxyKind::Chain { .. } => proxy
+                .chain_hops(proxies)?
+                .into_iter()
+                .map(|hop| &hop.kind)
+                .collect(),
+            kind => vec![kind],
+        };
+
+        kinds
+            .into_iter()
+            .map(|kind| {
+                Self::for_proxy(kind)
+                    .ok_or_else(|| format!("Proxy '{}' cannot be forwarded", proxy.name))
+            })
+            .collect()
+    }
+
+    fn address(&self) -> String {
+        format!("{}:{}", self.host, self.port)
+    }
+
+    /// Whether plain HTTP requests are relayed to the proxy as-is rather
+    /// than sent to the origin through a tunnel.
+    fn relays_http(&self) -> bool {
+        matches!(
+            self.protocol,
+            UpstreamProtocol::Http | UpstreamProtocol::Https
+        )
+    }
+
+    /// Opens a connection to the proxy itself.
+    async fn connect(&self) -> io::Result<BoxedStream> {
+        let stream = TcpStream::connect(self.address()).await?;
+        self.secure(Box::new(stream)).await
+    }
+
+    /// Wraps a connection to this proxy in TLS when the proxy requires it.
+    async fn secure(&self, stream: BoxedStream) -> io::Result<BoxedStream> {
+        if self.protocol != UpstreamProtocol::Https {
+            return Ok(stream);
+        }
+
+        let connector = native_tls::TlsConnector::new()
+            .map(TlsConnector::from)
+            .map_err(io::Error::other)?;
+        let stream = connector
+            .connect(&self.host, stream)
+            .await
+            .map_err(|e| io::Error::other(format!("TLS handshake with proxy failed: {}", e)))?;
+        Ok(Box::new(stream))
+    }
+
+    /// Asks the proxy at the other end of `stream` for a tunnel to `target`
+    /// (`host:port`).
+    async fn handshake(&self, mut stream: BoxedStream, target: &str) -> io::Result<BoxedStream> {
+        match self.protocol {
+            UpstreamProtocol::Http | UpstreamProtocol::Https => {
+                let mut request = format!("CONNECT {0} HTTP/1.1\r\nHost: {0}\r\n", target);
+                if let Some(credentials) = &self.credentials {
+                    request.push_str(&format!(
+                        "Proxy-Authorization: {}\r\n",
+                        credentials.basic_auth()
+                    ));
+                }
+                request.push_str("\r\n");
+                stream.write_all(request.as_bytes()).await?;
+
+                let (head, _) = read_head(&mut stream).await?;
+                let status = parse_status_code(&head)?;
+                if status != 200 {
+                    return Err(io::Error::other(format!(
+                        "Upstream proxy refused CONNECT to {} with status {}",
+                        target, status
+                    )));
+                }
+
+                Ok(stream)
+            }
+            UpstreamProtocol::Socks4 => {
+                // SOCKS4 only carries IPv4 addresses, so resolve locally
+                let address = tokio::net::lookup_host(target)
+                    .await?
+                    .find(SocketAddr::is_ipv4)
+                    .ok_or_else(|| {
+                        io::Error::other(format!("{} has no IPv4 address for SOCKS4", target))
+                    })?;
+                self.socks4_handshake(stream, address).await
+            }
+            // Hostnames are passed through so the upstream resolves them
+            UpstreamProtocol::Socks4a => self.socks4_handshake(stream, target).await,
+            UpstreamProtocol::Socks5 => {
+                let stream = match &self.credentials {
+                    Some(credentials) => {
+                        Socks5Stream::connect_with_password_and_socket(
+                            stream,
+                            target,
+                            &credentials.username,
+                            &credentials.password,
+                        )
+                        .await
+                    }
+                    None => Socks5Stream::connect_with_socket(stream, target).await,
+                }
+                .map_err(|e| io::Error::other(format!("SOCKS5 upstream failed: {}", e)))?;
+
+                Ok(Box::new(stream))
+            }
+        }
+    }
+
+    async fn socks4_handshake<'t, T>(
+        &self,
+        stream: BoxedStream,
+        target: T,
+    ) -> io::Result<BoxedStream>
+    where
+        T: IntoTargetAddr<'t>,
+    {
+        let stream = match &self.credentials {
+            Some(credentials) => {
+                Socks4Stream::connect_with_userid_and_socket(stream, target, &credentials.username)
+                    .await
+            }
+            None => Socks4Stream::connect_with_socket(stream, target).await,
+        }
+        .map_err(|e| io::Error::other(format!("SOCKS4 upstream failed: {}", e)))?;
+
+        Ok(Box::new(stream))
+    }
+}
+
+/// The browser session a forwarder relays for: where its traffic is
+/// accounted and, for fail-closed proxies, when it must be refused.
+#[derive(Clone)]
+pub struct ForwarderSession {
+    pub meter: Arc<SessionMeter>,
+    pub log: ConnectionLogger,
+    pub kill_switch: Option<KillSwitch>,
+}
+
+/// A local, unauthenticated HTTP proxy listening on 127.0.0.1 that relays
+/// every request to an upstream proxy, adding the upstream credentials.
+///
+/// The listener is shut down when the forwarder is dropped. With a session,
+/// every request is metered and logged for the browser using the forwarder,
+/// and refused while the session's kill switch is engaged.
+pub struct LocalForwarder {
+    local_addr: SocketAddr,
+    accept_task: JoinHandle<()>,
+}
+
+impl LocalForwarder {
+    /// Starts relaying through `hops` in order. The last hop reaches the
+    /// target; every earlier hop tunnels to the next one.
+    pub async fn start(hops: Vec<Upstream>, session: Option<ForwarderSession>) -> io::Result<Self> {
+        if hops.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Local forwarder needs at least one upstream",
+            ));
+        }
+
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let local_addr = listener.local_addr()?;
+        let route: Arc<str> = hops
+            .iter()
+            .map(Upstream::address)
+            .collect::<Vec<_>>()
+            .join(" -> ")
+            .into();
+        let hops = Arc::new(hops);
+
+        println!(
+            "Local forwarder listening on {} (upstream {})",
+            local_addr, route
+        );
+
+        let accept_task = tokio::spawn(async move {
+            loop {
+                let (client, _) = match listener.accept().await {
+                    Ok(connection) => connection,
+                    Err(e) => {
+                        eprintln!("Local forwarder failed to accept connection: {}", e);
+                        continue;
+                    }
+                };
+
+                let hops = hops.clone();
+                let route = route.clone();
+                let session = session.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = handle_client(client, &hops, &route, session.as_ref()).await {
+                        eprintln!("Local forwarder connection failed: {}", e);
+                    }
+                });
+            }
+        });
+
+        Ok(Self {
+            local_addr,
+            accept_task,
+        })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// The value to pass to the browser as `--proxy-server`.
+    pub fn proxy_server(&self) -> String {
+        format!("http://{}", self.local_addr)
+    }
+}
+
+impl Drop for LocalForwarder {
+    fn drop(&mut self) {
+        self.accept_task.abort();
+        println!("Local forwarder on {} stopped", self.local_addr);
+    }
+}
+
+/// Connects to the last of `hops`, tunnelling through every earlier hop.
+async fn connect_through(hops: &[Upstream]) -> io::Result<BoxedStream> {
+    let (first, rest) = hops
+        .split_first()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "No upstream proxy"))?;
+
+    let mut stream = first.connect().await?;
+    let mut current = first;
+    for next in rest {
+        stream = current.handshake(stream, &next.address()).await?;
+        stream = next.secure(stream).await?;
+        current = next;
+    }
+
+    Ok(stream)
+}
+
+/// Opens a tunnel to `target` (`host:port`) through every hop in order.
+async fn open_tunnel(hops: &[Upstream], target: &str) -> io::Result<BoxedStream> {
+    let stream = connect_through(hops).await?;
+    match hops.last() {
+        Some(last) => last.handshake(stream, target).await,
+        None => Ok(stream),
+    }
+}
+
+/// Connects hop by hop, returning how long reaching each hop took. Stops at
+/// the first hop that cannot be reached, so the result may be shorter than
+/// `hops`; the error is for the hop after the last returned duration.
+pub async fn trace_route(
+    hops: &[Upstream],
+    timeout: Duration,
+) -> (Vec<Duration>, Option<io::Error>) {
+    let mut latencies = Vec::new();
+    let mut stream: Option<BoxedStream> = None;
+
+    for (index, hop) in hops.iter().enumerate() {
+        let started = Instant::now();
+        let step = async {
+            match stream.take() {
+                None => hop.connect().await,
+                Some(previous) => {
+                    let tunnel = hops[index - 1].handshake(previous, &hop.address()).await?;
+                    hop.secure(tunnel).await
+                }
+            }
+        };
+
+        match tokio::time::timeout(timeout, step).await {
+            Ok(Ok(connected)) => {
+                latencies.push(started.elapsed());
+                stream = Some(connected);
+            }
+            Ok(Err(e)) => return (latencies, Some(e)),
+            Err(_) => {
+                let error = io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    format!("Timed out connecting to {}", hop.address()),
+                );
+                return (latencies, Some(error));
+            }
+        }
+    }
+
+    (latencies, None)
+}
+
+async fn handle_client(
+    mut client: TcpStream,
+    hops: &[Upstream],
+    route: &str,
+    session: Option<&ForwarderSession>,
+) -> io::Result<()> {
+    let started_at = Utc::now();
+    let started = Instant::now();
+    let (head, leftover) = read_head(&mut client).await?;
+    let request = RequestHead::parse(&head)?;
+
+    let mut outcome = RelayOutcome::default();
+    let blocked = session
+        .and_then(|session| session.kill_switch.as_ref())
+        .and_then(KillSwitch::blocked);
+    let result = match blocked {
+        // Refuse rather than relay; the browser must not find another route
+        Some(reason) => {
+            let error = io::Error::new(io::ErrorKind::ConnectionRefused, reason);
+            write_error(&mut client, &error).await;
+            outcome.status = Some(502);
+            Err(error)
+        }
+        None => {
+            relay(
+                &mut client,
+                hops,
+                &request,
+                &leftover,
+                session.map(|session| &session.meter),
+                &mut outcome,
+            )
+            .await
+        }
+    };
+
+    if let Some(session) = session {
+        session.log.record(ConnectionRecord {
+            started_at,
+            method: request.method.clone(),
+            target: request.authority(),
+            upstream: route.to_string(),
+            status: outcome.status,
+            traffic: outcome.traffic,
+            duration: started.elapsed(),
+            error: result.as_ref().err().map(ToString::to_string),
+        });
+    }
+
+    result
+}
+
+/// What the forwarder saw of a relayed request.
+#[derive(Default)]
+struct RelayOutcome {
+    status: Option<u16>,
+    traffic: TrafficTotals,
+}
+
+async fn relay(
+    client: &mut TcpStream,
+    hops: &[Upstream],
+    request: &RequestHead,
+    leftover: &[u8],
+    meter: Option<&Arc<SessionMeter>>,
+    outcome: &mut RelayOutcome,
+) -> io::Result<()> {
+    let tunnel = request.method.eq_ignore_ascii_case("CONNECT");
+
+    // Plain HTTP goes to the last hop as-is when it is an HTTP proxy
+    let (server, head) = if tunnel {
+        (open_tunnel(hops, &request.target).await, None)
+    } else {
+        match hops.last().filter(|last| last.relays_http()) {
+            Some(last) => (
+                connect_through(hops).await,
+                Some(request.to_proxy_request(last.credentials.as_ref())),
+            ),
+            None => {
+                let (target, head) = request.to_origin_request()?;
+                (open_tunnel(hops, &target).await, Some(head))
+            }
+        }
+    };
+
+    let mut server = match server {
+        Ok(server) => Metered::new(server, meter.cloned()),
+        Err(e) => {
+            write_error(client, &e).await;
+            outcome.status = Some(502);
+            return Err(e);
+        }
+    };
+
+    let result = async {
+        match &head {
+            Some(head) => server.write_all(head.as_bytes()).await?,
+            None => {
+                client
+                    .write_all(b"HTTP/1.1 200 Connection Established\r\n\r\n")
+                    .await?;
+                outcome.status = Some(200);
+            }
+        }
+        server.write_all(leftover).await?;
+        tokio::io::copy_bidirectional(client, &mut server).await?;
+        Ok(())
+    }
+    .await;
+
+    if !tunnel {
+        outcome.status = parse_status_code(server.response_start()).ok();
+    }
+    outcome.traffic = server.totals();
+    result
+}
+
+async fn write_error(client: &mut TcpStream, error: &io::Error) {
+    let body = format!("Local forwarder error: {}\r\n", error);
+    let response = format!(
+        "HTTP/1.1 502 Bad Gateway\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        body.len(),
+        body
+    );
+    let _ = client.write_all(response.as_bytes()).await;
+}
+
+/// Reads an HTTP message head, returning it together with any bytes that
+/// were read past the terminating blank line.
+async fn read_head<S>(stream: &mut S) -> io::Result<(Vec<u8>, Vec<u8>)>
+where
+    S: AsyncRead + Unpin,
+{
+    let mut buffer = Vec::with_capacity(4096);
+    let mut chunk = [0u8; 4096];
+
+    loop {
+        let read = stream.read(&mut chunk).await?;
+        if read == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "Connection closed before the request head was complete",
+            ));
+        }
+        buffer.extend_from_slice(&chunk[..read]);
+
+        if let Some(end) = buffer.windows(4).position(|w| w == b"\r\n\r\n") {
+            let leftover = buffer.split_off(end + 4);
+            return Ok((buffer, leftover));
+        }
+
+        if buffer.len() > MAX_HEAD_SIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "HTTP head exceeds maximum size",
+            ));
+        }
+    }
+}
+
+fn parse_status_code(head: &[u8]) -> io::Result<u16> {
+    let text = String::from_utf8_lossy(head);
+    text.lines()
+        .next()
+        .and_then(|line| line.split_whitespace().nth(1))
+        .and_then(|code| code.parse().ok())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Malformed status line"))
+}
+
+struct RequestHead {
+    method: String,
+    target: String,
+    version: String,
+    headers: Vec<(String, String)>,
+}
+
+impl RequestHead {
+    fn parse(head: &[u8]) -> io::Result<Self> {
+        let text = std::str::from_utf8(head)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Request head is not UTF-8"))?;
+        let mut lines = text.split("\r\n").filter(|line| !line.is_empty());
+
+        let request_line = lines
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Empty request"))?;
+        let mut parts = request_line.split_whitespace();
+        let (method, target, version) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(method), Some(target), Some(version)) => (method, target, version),
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Malformed request line: {}", request_line),
+                ))
+            }
+        };
+
+        let headers = lines
+            .filter_map(|line| line.split_once(':'))
+            .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+            .collect();
+
+        Ok(Self {
+            method: method.to_string(),
+            target: target.to_string(),
+            version: version.to_string(),
+            headers,
+        })
+    }
+
+    /// The `host:port` the request is for, or the raw target when it has
+    /// none.
+    fn authority(&self) -> String {
+        if self.method.eq_ignore_ascii_case("CONNECT") {
+            return self.target.clone();
+        }
+        Url::parse(&self.target)
+            .ok()
+            .and_then(|url| {
+                let host = url.host_str()?.to_string();
+                Some(format!("{}:{}", host, url.port_or_known_default()?))
+            })
+            .unwrap_or_else(|| self.target.clone())
+    }
+
+    /// Rebuilds the request for an upstream HTTP proxy, replacing any
+    /// proxy credentials sent by the browser and closing the connection
+    /// after one exchange so every request carries the injected header.
+    fn to_proxy_request(&self, credentials: Option<&Credentials>) -> String {
+        let mut head = format!("{} {} {}\r\n", self.method, self.target, self.version);
+
+        for (name, value) in &self.headers {
+            if is_hop_by_hop(name) {
+                continue;
+            }
+            head.push_str(&format!("{}: {}\r\n", name, value));
+        }
+
+        if let Some(credentials) = credentials {
+            head.push_str(&format!(
+                "Proxy-Authorization: {}\r\n",
+                credentials.basic_auth()
+            ));
+        }
+        head.push_str("Connection: close\r\n\r\n");
+        head
+    }
+
+    /// Rewrites an absolute-form proxy request into the origin-form request
+    /// sent directly to the target server, returning the target `host:port`.
+    fn to_origin_request(&self) -> io::Result<(String, String)> {
+        let url = Url::parse(&self.target).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Invalid request target {}: {}", self.target, e),
+            )
+        })?;
+        let host = url.host_str().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidData, "Request target has no host")
+        })?;
+        let port = url.port_or_known_default().unwrap_or(80);
+
+        let mut path = url.path().to_string();
+        if let Some(query) = url.query() {
+            path.push('?');
+            path.push_str(query);
+        }
+
+        let mut head = format!("{} {} {}\r\n", self.method, path, self.version);
+        for (name, value) in &self.headers {
+            if is_hop_by_hop(name) {
+                continue;
+            }
+            head.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        head.push_str("Connection: close\r\n\r\n");
+
+        Ok((format!("{}:{}", host, port), head))
+    }
+}
+
+fn is_hop_by_hop(name: &str) -> bool {
+    [
+        "proxy-authorization",
+        "proxy-connection",
+        "connection",
+        "keep-alive",
+    ]
+    .iter()
+    .any(|h| name.eq_ignore_ascii_case(h))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proxy::connection_log::ConnectionLog;
+    use crate::proxy::health::ProxyHealth;
+    use crate::proxy::monitor::HealthMonitor;
+    use crate::proxy::usage::UsageTracker;
+
+    fn upstream(protocol: UpstreamProtocol, addr: SocketAddr, user: &str) -> Upstream {
+        Upstream {
+            protocol,
+            host: addr.ip().to_string(),
+            port: addr.port(),
+            credentials: Some(Credentials {
+                username: user.to_string(),
+                password: "secret".to_string(),
+            }),
+        }
+    }
+
+    /// An HTTP proxy that records the head of the one request it accepts,
+    /// answers `reply` and then echoes whatever it receives.
+    async fn http_proxy_stub(reply: &'static str) -> (SocketAddr, JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let stub = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let (head, _) = read_head(&mut stream).await.unwrap();
+            stream.write_all(reply.as_bytes()).await.unwrap();
+            let (mut reader, mut writer) = stream.split();
+            let _ = tokio::io::copy(&mut reader, &mut writer).await;
+            String::from_utf8(head).unwrap()
+        });
+        (addr, stub)
+    }
+
+    /// Sends `request` to the forwarder and reads its reply until `expected`
+    /// bytes arrived or the connection closed.
+    async fn send(forwarder: &LocalForwarder, request: &str, expected: usize) -> String {
+        let mut client = TcpStream::connect(forwarder.local_addr()).await.unwrap();
+        client.write_all(request.as_bytes()).await.unwrap();
+        let mut reply = Vec::new();
+        let mut chunk = [0u8; 1024];
+        while reply.len() < expected {
+            match client.read(&mut chunk).await.unwrap() {
+                0 => break,
+                read => reply.extend_from_slice(&chunk[..read]),
+            }
+        }
+        String::from_utf8(reply).unwrap()
+    }
+
+    const ESTABLISHED: &str = "HTTP/1.1 200 Connection Established\r\n\r\n";
+
+    #[tokio::test]
+    async fn authenticates_connect_to_http_upstream() {
+        let (addr, stub) = http_proxy_stub(ESTABLISHED).await;
+        let forwarder =
+            LocalForwarder::start(vec![upstream(UpstreamProtocol::Http, addr, "user")], None)
+                .await
+                .unwrap();
+
+        let reply = send(
+            &forwarder,
+            "CONNECT example.com:443 HTTP/1.1\r\nHost: example.com:443\r\n\r\n",
+            ESTABLISHED.len(),
+        )
+        .await;
+        assert_eq!(reply, ESTABLISHED);
+        drop(forwarder);
+
+        let head = stub.await.unwrap();
+        assert!(head.starts_with("CONNECT example.com:443 HTTP/1.1\r\n"));
+        assert!(head.contains("Proxy-Authorization: Basic dXNlcjpzZWNyZXQ=\r\n"));
+    }
+
+    #[tokio::test]
+    async fn replaces_credentials_on_plain_http() {
+        let response = "HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok";
+        let (addr, stub) = http_proxy_stub(response).await;
+        let forwarder =
+            LocalForwarder::start(vec![upstream(UpstreamProtocol::Http, addr, "user")], None)
+                .await
+                .unwrap();
+
+        let reply = send(
+            &forwarder,
+            "GET http://example.com/page?q=1 HTTP/1.1\r\nHost: example.com\r\nProxy-Authorization: Basic Ym9ndXM=\r\nProxy-Connection: keep-alive\r\n\r\n",
+            response.len(),
+        )
+        .await;
+        assert_eq!(reply, response);
+
+        let head = stub.await.unwrap();
+        assert_eq!(
+            head,
+            "GET http://example.com/page?q=1 HTTP/1.1\r\nHost: example.com\r\nProxy-Authorization: Basic dXNlcjpzZWNyZXQ=\r\nConnection: close\r\n\r\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn authenticates_to_socks5_upstream() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let stub = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut greeting = [0u8; 2];
+            stream.read_exact(&mut greeting).await.unwrap();
+            let mut methods = vec![0u8; greeting[1] as usize];
+            stream.read_exact(&mut methods).await.unwrap();
+            assert!(methods.contains(&2), "username/password not offered");
+            stream.write_all(&[5, 2]).await.unwrap();
+
+            // RFC 1929 username/password request
+            let mut version = [0u8; 2];
+            stream.read_exact(&mut version).await.unwrap();
+            let mut username = vec![0u8; version[1] as usize];
+            stream.read_exact(&mut username).await.unwrap();
+            let mut len = [0u8; 1];
+            stream.read_exact(&mut len).await.unwrap();
+            let mut password = vec![0u8; len[0] as usize];
+            stream.read_exact(&mut password).await.unwrap();
+            stream.write_all(&[1, 0]).await.unwrap();
+
+            let mut request = [0u8; 5];
+            stream.read_exact(&mut request).await.unwrap();
+            assert_eq!(request[..4], [5, 1, 0, 3], "expected a domain CONNECT");
+            let mut target = vec![0u8; request[4] as usize + 2];
+            stream.read_exact(&mut target).await.unwrap();
+            stream
+                .write_all(&[5, 0, 0, 1, 0, 0, 0, 0, 0, 0])
+                .await
+                .unwrap();
+
+            let mut payload = [0u8; 4];
+            stream.read_exact(&mut payload).await.unwrap();
+            stream.write_all(&payload).await.unwrap();
+
+            let host_len = target.len() - 2;
+            let port = u16::from_be_bytes([target[host_len], target[host_len + 1]]);
+            (
+                String::from_utf8(username).unwrap(),
+                String::from_utf8(password).unwrap(),
+                format!("{}:{}", String::from_utf8_lossy(&target[..host_len]), port),
+            )
+        });
+
+        let forwarder =
+            LocalForwarder::start(vec![upstream(UpstreamProtocol::Socks5, addr, "user")], None)
+                .await
+                .unwrap();
+        let reply = send(
+            &forwarder,
+            "CONNECT example.com:443 HTTP/1.1\r\nHost: example.com:443\r\n\r\nping",
+            ESTABLISHED.len() + 4,
+        )
+        .await;
+        assert_eq!(reply, format!("{}ping", ESTABLISHED));
+
+        let (username, password, target) = stub.await.unwrap();
+        assert_eq!(username, "user");
+        assert_eq!(password, "secret");
+        assert_eq!(target, "example.com:443");
+    }
+
+    #[tokio::test]
+    async fn tunnels_through_two_hops() {
+        let (second, second_stub) = http_proxy_stub(ESTABLISHED).await;
+
+        // The first hop relays its tunnel to whatever it was asked for
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let first = listener.local_addr().unwrap();
+        let first_stub = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let (head, _) = read_head(&mut stream).await.unwrap();
+            let head = String::from_utf8(head).unwrap();
+            let target = head.split_whitespace().nth(1).unwrap().to_string();
+            let mut next = TcpStream::connect(&target).await.unwrap();
+            stream.write_all(ESTABLISHED.as_bytes()).await.unwrap();
+            let _ = tokio::io::copy_bidirectional(&mut stream, &mut next).await;
+            head
+        });
+
+        let forwarder = LocalForwarder::start(
+            vec![
+                upstream(UpstreamProtocol::Http, first, "first"),
+                upstream(UpstreamProtocol::Http, second, "second"),
+            ],
+            None,
+        )
+        .await
+        .unwrap();
+        let reply = send(
+            &forwarder,
+            "CONNECT example.com:443 HTTP/1.1\r\nHost: example.com:443\r\n\r\nping",
+            ESTABLISHED.len() + 4,
+        )
+        .await;
+        assert_eq!(reply, format!("{}ping", ESTABLISHED));
+        drop(forwarder);
+
+        let first_head = first_stub.await.unwrap();
+        assert!(first_head.starts_with(&format!("CONNECT {} HTTP/1.1\r\n", second)));
+        assert!(first_head.contains(&format!(
+            "Proxy-Authorization: {}\r\n",
+            Credentials {
+                username: "first".to_string(),
+                password: "secret".to_string(),
+            }
+            .basic_auth()
+        )));
+
+        let second_head = second_stub.await.unwrap();
+        assert!(second_head.starts_with("CONNECT example.com:443 HTTP/1.1\r\n"));
+        assert!(second_head.contains(&format!(
+            "Proxy-Authorization: {}\r\n",
+            Credentials {
+                username: "second".to_string(),
+                password: "secret".to_string(),
+            }
+            .basic_auth()
+        )));
+    }
+
+    #[tokio::test]
+    async fn refuses_while_kill_switch_is_engaged() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let monitor = HealthMonitor::default();
+        let down: ProxyHealth = serde_json::from_value(serde_json::json!({
+            "proxy_id": "work",
+            "status": "down",
+            "reachable": false,
+            "message": "Failed to connect",
+            "checked_at": "2026-01-01T00:00:00Z",
+        }))
+        .unwrap();
+        monitor.record(down, 10);
+
+        let session = ForwarderSession {
+            meter: UsageTracker::default().start_session("work", vec!["work".to_string()]),
+            log: ConnectionLog::default().logger("work", "work", 10, false),
+            kill_switch: Some(KillSwitch::new(monitor, vec!["work".to_string()])),
+        };
+        let forwarder = LocalForwarder::start(
+            vec![upstream(UpstreamProtocol::Http, addr, "user")],
+            Some(session),
+        )
+        .await
+        .unwrap();
+
+        let reply = send(
+            &forwarder,
+            "CONNECT example.com:443 HTTP/1.1\r\nHost: example.com:443\r\n\r\n",
+            usize::MAX,
+        )
+        .await;
+        assert!(reply.starts_with("HTTP/1.1 502 Bad Gateway\r\n"));
+        assert!(reply.contains("Kill switch engaged: proxy work is down"));
+
+        let upstream_contacted =
+            tokio::time::timeout(Duration::from_millis(200), listener.accept()).await;
+        assert!(upstream_contacted.is_err(), "request reached the upstream");
+    }
+}
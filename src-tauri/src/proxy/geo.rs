@@ -0,0 +1,161 @@
+use super::health::ProxyHealth;
+use maxminddb::Reader;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+/// Where an exit IP is located according to the configured databases.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+pub struct GeoLocation {
+    pub country_code: Option<String>,
+    pub country: Option<String>,
+    pub city: Option<String>,
+    pub asn: Option<u32>,
+    pub as_organization: Option<String>,
+}
+
+impl GeoLocation {
+    /// Fills the fields this location is missing from `other`.
+    fn merge(&mut self, other: GeoLocation) {
+        self.country_code = self.country_code.take().or(other.country_code);
+        self.country = self.country.take().or(other.country);
+        self.city = self.city.take().or(other.city);
+        self.asn = self.asn.or(other.asn);
+        self.as_organization = self.as_organization.take().or(other.as_organization);
+    }
+
+    fn is_empty(&self) -> bool {
+        *self == GeoLocation::default()
+    }
+}
+
+/// The fields read from a database record. City, Country, ASN and ISP
+/// databases each carry a subset of them.
+#[derive(Deserialize)]
+struct Record<'a> {
+    #[serde(borrow)]
+    country: Option<Place<'a>>,
+    #[serde(borrow)]
+    city: Option<Place<'a>>,
+    autonomous_system_number: Option<u32>,
+    autonomous_system_organization: Option<&'a str>,
+}
+
+#[derive(Deserialize)]
+struct Place<'a> {
+    iso_code: Option<&'a str>,
+    #[serde(borrow)]
+    names: Option<BTreeMap<&'a str, &'a str>>,
+}
+
+impl Place<'_> {
+    fn name(&self) -> Option<String> {
+        self.names
+            .as_ref()
+            .and_then(|names| names.get("en"))
+            .map(|name| name.to_string())
+    }
+}
+
+struct Database {
+    path: String,
+    modified: Option<SystemTime>,
+    /// `None` when the file could not be opened, so it is not retried until
+    /// it changes.
+    reader: Option<Arc<Reader<Vec<u8>>>>,
+}
+
+/// Looks up exit IPs in local MaxMind DB files such as GeoLite2-City and
+/// GeoLite2-ASN. Databases are opened on first use and reopened when the
+/// file changes.
+#[derive(Clone, Default)]
+pub struct GeoIp {
+    databases: Arc<Mutex<Vec<Database>>>,
+}
+
+impl GeoIp {
+    /// Combines the records for `ip` from every database in `paths`, earlier
+    /// databases taking precedence.
+    pub fn lookup(&self, paths: &[String], ip: IpAddr) -> Option<GeoLocation> {
+        let mut location = GeoLocation::default();
+
+        for reader in self.readers(paths) {
+            let Ok(record) = reader.lookup::<Record>(ip) else {
+                continue;
+            };
+            location.merge(GeoLocation {
+                country_code: record
+                    .country
+                    .as_ref()
+                    .and_then(|country| country.iso_code)
+                    .map(str::to_string),
+                country: record.country.as_ref().and_then(Place::name),
+                city: record.city.as_ref().and_then(Place::name),
+                asn: record.autonomous_system_number,
+                as_organization: record.autonomous_system_organization.map(str::to_string),
+            });
+        }
+
+        (!location.is_empty()).then_some(location)
+    }
+
+    /// Sets the location of the exit IP of `health`, if it has one.
+    pub fn locate(&self, health: &mut ProxyHealth, paths: &[String]) {
+        if paths.is_empty() {
+            return;
+        }
+        if let Some(ip) = health.exit_ip.as_deref().and_then(|ip| ip.parse().ok()) {
+            health.geo = self.lookup(paths, ip);
+        }
+    }
+
+    fn readers(&self, paths: &[String]) -> Vec<Arc<Reader<Vec<u8>>>> {
+        let mut databases = self.databases.lock().unwrap();
+        databases.retain(|database| paths.contains(&database.path));
+
+        paths
+            .iter()
+            .filter_map(|path| {
+                let modified = std::fs::metadata(path)
+                    .and_then(|metadata| metadata.modified())
+                    .ok();
+                let index = match databases.iter().position(|db| db.path == *path) {
+                    Some(index) if databases[index].modified == modified => index,
+                    existing => {
+                        let reader = match Reader::open_readfile(path) {
+                            Ok(reader) => {
+                                println!(
+                                    "Opened GeoIP database {} ({})",
+                                    path, reader.metadata.database_type
+                                );
+                                Some(Arc::new(reader))
+                            }
+                            Err(e) => {
+                                eprintln!("Failed to open GeoIP database {}: {}", path, e);
+                                None
+                            }
+                        };
+                        let database = Database {
+                            path: path.clone(),
+                            modified,
+                            reader,
+                        };
+                        match existing {
+                            Some(index) => {
+                                databases[index] = database;
+                                index
+                            }
+                            None => {
+                                databases.push(database);
+                                databases.len() - 1
+                            }
+                        }
+                    }
+                };
+                databases[index].reader.clone()
+            })
+            .collect()
+    }
+}
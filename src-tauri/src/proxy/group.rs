@@ -0,0 +1,96 @@
+use super::health::HealthStatus;
+use super::monitor::HealthMonitor;
+use crate::settings::{GroupStrategy, ProxyConfig, ProxyGroup};
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::{Arc, Mutex};
+
+/// The member each group picked most recently, so round-robin continues
+/// from it. Kept in memory rather than in settings.json, which the frontend
+/// owns; round-robin starts over with the first member after a restart.
+#[derive(Clone, Default)]
+pub struct GroupCursors {
+    last_member_ids: Arc<Mutex<HashMap<String, String>>>,
+}
+
+impl GroupCursors {
+    fn last(&self, group_id: &str) -> Option<String> {
+        self.last_member_ids.lock().unwrap().get(group_id).cloned()
+    }
+
+    fn advance(&self, group_id: &str, member_id: &str) {
+        self.last_member_ids
+            .lock()
+            .unwrap()
+            .insert(group_id.to_string(), member_id.to_string());
+    }
+}
+
+/// Picks the member of `group` to launch with and remembers it in
+/// `cursors`. Members whose latest health check found them down are skipped
+/// unless every member is down.
+pub fn select<'a>(
+    group: &ProxyGroup,
+    proxies: &'a [ProxyConfig],
+    monitor: &HealthMonitor,
+    cursors: &GroupCursors,
+) -> Result<&'a ProxyConfig, String> {
+    let members: Vec<&ProxyConfig> = group
+        .members
+        .iter()
+        .filter_map(|id| proxies.iter().find(|p| p.id == *id))
+        .collect();
+
+    let available: Vec<&ProxyConfig> = members
+        .iter()
+        .copied()
+        .filter(|member| {
+            monitor
+                .latest(&member.id)
+                .is_none_or(|health| health.status != HealthStatus::Down)
+        })
+        .collect();
+    let candidates = if available.is_empty() {
+        &members
+    } else {
+        &available
+    };
+
+    let selected = match group.strategy {
+        GroupStrategy::RoundRobin => {
+            let position = |id: &str| group.members.iter().position(|member| member == id);
+            let last = cursors.last(&group.id).as_deref().and_then(position);
+            candidates
+                .iter()
+                .find(|member| position(&member.id) > last)
+                .or(candidates.first())
+        }
+        GroupStrategy::Random => candidates.get(random_index(candidates.len())),
+        // Members without health data sort after every measured one
+        GroupStrategy::LeastLatency => candidates.iter().min_by_key(|member| {
+            monitor
+                .latest(&member.id)
+                .and_then(|health| health.ttfb_ms.or(health.connect_ms))
+                .unwrap_or(u64::MAX)
+        }),
+        GroupStrategy::Failover => candidates.first(),
+    };
+
+    let selected = selected
+        .copied()
+        .ok_or_else(|| format!("Proxy group '{}' has no members", group.name))?;
+    cursors.advance(&group.id, &selected.id);
+    Ok(selected)
+}
+
+fn random_index(len: usize) -> usize {
+    if len == 0 {
+        return 0;
+    }
+    // RandomState is seeded randomly per instance, which is plenty for
+    // spreading launches across a pool
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_usize(len);
+    hasher.finish() as usize % len
+}
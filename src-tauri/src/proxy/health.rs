@@ -0,0 +1,532 @@
+use super::bypass;
+use super::forwarder::{self, LocalForwarder, Upstream};
+use super::geo::GeoLocation;
+use super::helper::{HelperProcess, HelperSpec};
+use super::pac::{self, PacDirective};
+use super::pac_eval;
+use crate::settings::{PacRule, ProxyConfig, ProxyKind, ProxyServer};
+use chrono::{DateTime, Utc};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use std::error::Error as StdError;
+use std::io;
+use std::time::{Duration, Instant};
+use tokio::net::TcpStream;
+
+const CHECK_TIMEOUT: Duration = Duration::from_secs(10);
+const DEGRADED_TTFB: Duration = Duration::from_secs(3);
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthStatus {
+    Up,
+    Degraded,
+    Down,
+    /// The proxy is served by a helper that only runs with a browser
+    /// session, and no session uses it.
+    NotRunning,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureKind {
+    AuthFailed,
+    ConnectionRefused,
+    Timeout,
+    Unreachable,
+    BadResponse,
+    Config,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ProxyHealth {
+    pub proxy_id: String,
+    pub status: HealthStatus,
+    /// Whether a TCP connection to the proxy itself could be opened.
+    pub reachable: bool,
+    pub connect_ms: Option<u64>,
+    pub ttfb_ms: Option<u64>,
+    pub exit_ip: Option<String>,
+    /// Location of the exit IP from the configured GeoIP databases.
+    #[serde(default)]
+    pub geo: Option<GeoLocation>,
+    pub failure: Option<FailureKind>,
+    pub message: Option<String>,
+    /// Per-hop results for chains, in routing order.
+    #[serde(default)]
+    pub hops: Vec<HopHealth>,
+    pub checked_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct HopHealth {
+    pub proxy_id: String,
+    /// Time to reach this hop from the previous one, or to connect to it
+    /// for the first hop. Unset when the hop could not be reached.
+    pub latency_ms: Option<u64>,
+}
+
+impl ProxyHealth {
+    fn new(proxy_id: &str) -> Self {
+        Self {
+            proxy_id: proxy_id.to_string(),
+            status: HealthStatus::Down,
+            reachable: false,
+            connect_ms: None,
+            ttfb_ms: None,
+            exit_ip: None,
+            geo: None,
+            failure: None,
+            message: None,
+            hops: Vec::new(),
+            checked_at: Utc::now(),
+        }
+    }
+
+    fn fail(mut self, failure: FailureKind, message: impl Into<String>) -> Self {
+        self.status = HealthStatus::Down;
+        self.failure = Some(failure);
+        self.message = Some(message.into());
+        self
+    }
+
+    fn not_running(mut self) -> Self {
+        self.status = HealthStatus::NotRunning;
+        self.message = Some("Not running; no browser session uses this proxy".to_string());
+        self
+    }
+}
+
+/// How a check reaches a proxy served by a helper process.
+pub enum HelperCheck {
+    /// Start a helper for the check, as an explicit test does.
+    Start,
+    /// Go through the helper of a running browser session, given by its
+    /// proxy server, or report the proxy as not running without one.
+    /// Background checks never start helpers, which would collide with
+    /// the session's fixed ports.
+    Running(Option<String>),
+}
+
+/// The proxy endpoint a check connects through.
+struct Endpoint {
+    host: String,
+    port: u16,
+    url: Url,
+}
+
+impl Endpoint {
+    fn new(scheme: &str, host: &str, port: u16) -> Result<Self, String> {
+        let url = Url::parse(&format!("{}://{}:{}", scheme, host, port))
+            .map_err(|e| format!("Invalid proxy address {}:{}: {}", host, port, e))?;
+        Ok(Self {
+            host: host.to_string(),
+            port,
+            url,
+        })
+    }
+
+    fn with_credentials(mut self, server: &ProxyServer) -> Self {
+        if let Some(credentials) = server.credentials() {
+            let _ = self.url.set_username(&credentials.username);
+            let _ = self.url.set_password(Some(&credentials.password));
+        }
+        self
+    }
+}
+
+/// Resolves the endpoint to probe for `check_url`, or `None` when traffic
+/// goes direct.
+async fn resolve_endpoint(
+    proxy: &ProxyConfig,
+    check_url: &str,
+) -> Result<Option<Endpoint>, String> {
+    match &proxy.kind {
+        // socks5h lets the proxy resolve hostnames, matching the browser
+        ProxyKind::Socks5(server) => Ok(Some(
+            Endpoint::new("socks5h", &server.host, server.port)?.with_credentials(server),
+        )),
+        ProxyKind::Http(server)
+        | ProxyKind::Https(server)
+        | ProxyKind::Socks4(server)
+        | ProxyKind::Socks4a(server) => Ok(Some(
+            Endpoint::new(proxy.kind.type_name(), &server.host, server.port)?
+                .with_credentials(server),
+        )),
+        ProxyKind::Pac { url } => {
+            let script = pac::fetch_script(url).await?;
+            let check_url = check_url.to_string();
+            let evaluation =
+                tokio::task::spawn_blocking(move || pac_eval::evaluate(&script, &check_url))
+                    .await
+                    .map_err(|e| format!("Failed to evaluate PAC script: {}", e))??;
+            // Like the browser, use the first entry that can be understood
+            match evaluation
+                .entries
+                .iter()
+                .find_map(|entry| PacDirective::parse(entry))
+            {
+                Some(PacDirective::Proxy { scheme, host, port }) => {
+                    let scheme = if scheme == "socks5" {
+                        "socks5h"
+                    } else {
+                        &scheme
+                    };
+                    Ok(Some(Endpoint::new(scheme, &host, port)?))
+                }
+                Some(PacDirective::Direct) => Ok(None),
+                None => Err(format!(
+                    "PAC script returns no proxy for {}: {}",
+                    evaluation.url, evaluation.result
+                )),
+            }
+        }
+        ProxyKind::Chain { .. } => Err("Chains are checked through a local forwarder".to_string()),
+        ProxyKind::Rules => {
+            Err("PAC rules are checked through the proxy they route to".to_string())
+        }
+        ProxyKind::Tor(tor) if !tor.launched() => {
+            Ok(Some(Endpoint::new("socks5h", &tor.host, tor.port)?))
+        }
+        ProxyKind::Ssh(_) | ProxyKind::Helper(_) | ProxyKind::Tor(_) => {
+            Err("Helper proxies are checked through a helper started for the check".to_string())
+        }
+    }
+}
+
+/// Starts the helper of an SSH, helper or launched Tor proxy for the check,
+/// timing how long it took to become ready. Launched Tor keeps a data
+/// directory per proxy for checks, apart from the browser profiles.
+async fn check_helper(
+    proxy: &ProxyConfig,
+    health: &mut ProxyHealth,
+) -> Result<HelperProcess, (FailureKind, String)> {
+    let spec = match HelperSpec::for_proxy(proxy, &format!("health-check-{}", proxy.id)) {
+        Ok(Some(spec)) => spec,
+        Ok(None) => return Err((FailureKind::Config, "Proxy has no helper".to_string())),
+        Err(e) => return Err((FailureKind::Config, e)),
+    };
+
+    let started = Instant::now();
+    let helper = HelperProcess::start(spec).await.map_err(|e| {
+        let lower = e.to_ascii_lowercase();
+        let failure = if lower.contains("permission denied") {
+            FailureKind::AuthFailed
+        } else if lower.contains("connection refused") {
+            FailureKind::ConnectionRefused
+        } else if lower.contains("timed out") {
+            FailureKind::Timeout
+        } else if lower.contains("host key")
+            || lower.contains("failed to start")
+            || lower.contains("already in use")
+        {
+            FailureKind::Config
+        } else {
+            FailureKind::Unreachable
+        };
+        (failure, e)
+    })?;
+    health.reachable = true;
+    health.connect_ms = Some(started.elapsed().as_millis() as u64);
+    Ok(helper)
+}
+
+/// Connects through each hop of a chain in turn, recording its latency, and
+/// starts a forwarder the check request is then sent through.
+async fn check_chain(
+    proxy: &ProxyConfig,
+    proxies: &[ProxyConfig],
+    health: &mut ProxyHealth,
+) -> Result<LocalForwarder, (FailureKind, String)> {
+    let hop_configs = proxy
+        .chain_hops(proxies)
+        .map_err(|e| (FailureKind::Config, e))?;
+    let route = Upstream::route(proxy, proxies).map_err(|e| (FailureKind::Config, e))?;
+
+    let (latencies, error) = forwarder::trace_route(&route, CHECK_TIMEOUT).await;
+    health.hops = hop_configs
+        .iter()
+        .enumerate()
+        .map(|(index, hop)| HopHealth {
+            proxy_id: hop.id.clone(),
+            latency_ms: latencies
+                .get(index)
+                .map(|latency| latency.as_millis() as u64),
+        })
+        .collect();
+    health.reachable = !latencies.is_empty();
+    health.connect_ms = health.hops.first().and_then(|hop| hop.latency_ms);
+
+    if let Some(e) = error {
+        let hop = &hop_configs[latencies.len()];
+        let failure = match classify_io_error(&e) {
+            FailureKind::Unreachable if e.to_string().to_ascii_lowercase().contains("auth") => {
+                FailureKind::AuthFailed
+            }
+            failure => failure,
+        };
+        return Err((
+            failure,
+            format!("Failed to reach hop '{}': {}", hop.name, e),
+        ));
+    }
+
+    LocalForwarder::start(route, None).await.map_err(|e| {
+        (
+            FailureKind::Config,
+            format!("Failed to start local forwarder: {}", e),
+        )
+    })
+}
+
+/// Connects through `proxy` to `check_url`, timing the TCP connect to the
+/// proxy and the first response byte, and reporting the observed exit IP.
+/// `proxies` is used to resolve the hops of a chain.
+pub async fn check_proxy(
+    proxy: &ProxyConfig,
+    proxies: &[ProxyConfig],
+    rules: &[PacRule],
+    check_url: &str,
+    helper_check: HelperCheck,
+) -> ProxyHealth {
+    let mut health = ProxyHealth::new(&proxy.id);
+
+    // Mirror the browser, which sends bypassed hosts around the proxy
+    let bypassed = Url::parse(check_url).is_ok_and(|url| bypass::bypasses(&proxy.bypass, &url));
+    // Kept alive until the check request completes
+    let mut chain_forwarder = None;
+    let mut helper = None;
+    let endpoint = if bypassed {
+        None
+    } else if proxy.kind.runs_helper() {
+        let (scheme, host, port) = match helper_check {
+            HelperCheck::Start => {
+                let process = match check_helper(proxy, &mut health).await {
+                    Ok(process) => process,
+                    Err((failure, message)) => return health.fail(failure, message),
+                };
+                let address = (
+                    process.scheme().to_string(),
+                    process.host().to_string(),
+                    process.port(),
+                );
+                helper = Some(process);
+                address
+            }
+            HelperCheck::Running(Some(proxy_server)) => {
+                let address = Url::parse(&proxy_server).ok().and_then(|url| {
+                    Some((
+                        url.scheme().to_string(),
+                        url.host_str()?.to_string(),
+                        url.port()?,
+                    ))
+                });
+                match address {
+                    Some(address) => address,
+                    None => {
+                        return health.fail(
+                            FailureKind::Config,
+                            format!("Invalid helper address {}", proxy_server),
+                        )
+                    }
+                }
+            }
+            HelperCheck::Running(None) => return health.not_running(),
+        };
+        // socks5h lets the helper resolve hostnames, matching the browser
+        let scheme = match scheme.as_str() {
+            "socks5" => "socks5h",
+            scheme => scheme,
+        };
+        match Endpoint::new(scheme, &host, port) {
+            Ok(endpoint) => Some(endpoint),
+            Err(e) => return health.fail(FailureKind::Config, e),
+        }
+    } else if let ProxyKind::Chain { .. } = proxy.kind {
+        let local = match check_chain(proxy, proxies, &mut health).await {
+            Ok(local) => local,
+            Err((failure, message)) => return health.fail(failure, message),
+        };
+        let address = local.local_addr();
+        chain_forwarder = Some(local);
+        match Endpoint::new("http", &address.ip().to_string(), address.port()) {
+            Ok(endpoint) => Some(endpoint),
+            Err(e) => return health.fail(FailureKind::Config, e),
+        }
+    } else {
+        // Rules are checked through whichever proxy they pick for the check URL
+        let target = match proxy.kind {
+            ProxyKind::Rules => Url::parse(check_url)
+                .map_err(|e| format!("Invalid check URL: {}", e))
+                .and_then(|url| pac::route(rules, proxies, &url)),
+            _ => Ok(Some(proxy)),
+        };
+        let endpoint = match target {
+            Ok(Some(target)) => resolve_endpoint(target, check_url).await,
+            Ok(None) => Ok(None),
+            Err(e) => Err(e),
+        };
+        match endpoint {
+            Ok(endpoint) => endpoint,
+            Err(e) => return health.fail(FailureKind::Config, e),
+        }
+    };
+
+    let mut client = reqwest::Client::builder().timeout(CHECK_TIMEOUT);
+
+    match &endpoint {
+        Some(endpoint) => {
+            // A chain's connect time is that of its first hop, not the
+            // forwarder, and a helper's is how long it took to be ready
+            if chain_forwarder.is_none() && helper.is_none() {
+                match probe_tcp(&endpoint.host, endpoint.port, CHECK_TIMEOUT).await {
+                    Ok(elapsed) => {
+                        health.reachable = true;
+                        health.connect_ms = Some(elapsed.as_millis() as u64);
+                    }
+                    Err((failure, message)) => return health.fail(failure, message),
+                }
+            }
+
+            let upstream = match reqwest::Proxy::all(endpoint.url.clone()) {
+                Ok(upstream) => upstream,
+                Err(e) => return health.fail(FailureKind::Config, e.to_string()),
+            };
+            client = client.proxy(upstream);
+        }
+        None => {
+            health.reachable = true;
+            client = client.no_proxy();
+        }
+    }
+
+    let client = match client.build() {
+        Ok(client) => client,
+        Err(e) => return health.fail(FailureKind::Config, e.to_string()),
+    };
+
+    let started = Instant::now();
+    let response = match client.get(check_url).send().await {
+        Ok(response) => response,
+        Err(e) => {
+            let failure = classify_request_error(&e);
+            return health.fail(
+                failure,
+                format!("Check request failed: {}", error_chain(&e)),
+            );
+        }
+    };
+    let ttfb = started.elapsed();
+    health.ttfb_ms = Some(ttfb.as_millis() as u64);
+
+    let status = response.status();
+    if status == reqwest::StatusCode::PROXY_AUTHENTICATION_REQUIRED {
+        return health.fail(FailureKind::AuthFailed, "Proxy rejected the credentials");
+    }
+    if !status.is_success() {
+        return health.fail(
+            FailureKind::BadResponse,
+            format!("Check URL returned {}", status),
+        );
+    }
+
+    if let Ok(body) = response.text().await {
+        health.exit_ip = parse_exit_ip(&body);
+    }
+
+    if ttfb > DEGRADED_TTFB {
+        health.status = HealthStatus::Degraded;
+        health.message = Some(format!("Slow response ({} ms)", ttfb.as_millis()));
+    } else {
+        health.status = HealthStatus::Up;
+    }
+    if bypassed {
+        health.message = Some("Check URL bypasses the proxy and was fetched directly".to_string());
+    } else if endpoint.is_none() {
+        health.message = Some("PAC script routes traffic directly".to_string());
+    }
+
+    health
+}
+
+/// Opens and immediately closes a TCP connection, returning how long the
+/// connect took.
+pub async fn probe_tcp(
+    host: &str,
+    port: u16,
+    timeout: Duration,
+) -> Result<Duration, (FailureKind, String)> {
+    let started = Instant::now();
+    match tokio::time::timeout(timeout, TcpStream::connect((host, port))).await {
+        Ok(Ok(_)) => Ok(started.elapsed()),
+        Ok(Err(e)) => Err((
+            classify_io_error(&e),
+            format!("Failed to connect to {}:{}: {}", host, port, e),
+        )),
+        Err(_) => Err((
+            FailureKind::Timeout,
+            format!("Timed out connecting to {}:{}", host, port),
+        )),
+    }
+}
+
+/// Accepts both JSON responses with an `ip` field (ipify, ipinfo) and
+/// plain-text responses containing only the address.
+fn parse_exit_ip(body: &str) -> Option<String> {
+    if let Ok(json) = serde_json::from_str::<serde_json::Value>(body) {
+        if let Some(ip) = json.get("ip").and_then(|ip| ip.as_str()) {
+            return Some(ip.to_string());
+        }
+    }
+
+    let text = body.trim();
+    text.parse::<std::net::IpAddr>()
+        .ok()
+        .map(|ip| ip.to_string())
+}
+
+fn classify_io_error(error: &io::Error) -> FailureKind {
+    match error.kind() {
+        io::ErrorKind::ConnectionRefused => FailureKind::ConnectionRefused,
+        io::ErrorKind::TimedOut => FailureKind::Timeout,
+        _ => FailureKind::Unreachable,
+    }
+}
+
+fn classify_request_error(error: &reqwest::Error) -> FailureKind {
+    if error.is_timeout() {
+        return FailureKind::Timeout;
+    }
+
+    let mut source = error.source();
+    while let Some(cause) = source {
+        if let Some(io_error) = cause.downcast_ref::<io::Error>() {
+            match classify_io_error(io_error) {
+                FailureKind::Unreachable => {}
+                failure => return failure,
+            }
+        }
+        source = cause.source();
+    }
+
+    // SOCKS authentication failures only surface in the error message
+    let message = error_chain(error).to_ascii_lowercase();
+    if message.contains("auth") {
+        FailureKind::AuthFailed
+    } else if message.contains("refused") {
+        FailureKind::ConnectionRefused
+    } else {
+        FailureKind::Unreachable
+    }
+}
+
+fn error_chain(error: &dyn StdError) -> String {
+    let mut message = error.to_string();
+    let mut source = error.source();
+    while let Some(cause) = source {
+        message.push_str(": ");
+        message.push_str(&cause.to_string());
+        source = cause.source();
+    }
+    message
+}
@@ -0,0 +1,295 @@
+use super::{ssh, tor};
+use crate::settings::{HelperCommand, ProxyConfig, ProxyKind, ReadinessProbe};
+use regex::Regex;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+use tokio::net::TcpStream;
+use tokio::process::{Child, Command};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+/// Replaced in a helper's arguments by the port of its endpoint.
+pub const PORT_PLACEHOLDER: &str = "{port}";
+
+const READY_POLL: Duration = Duration::from_millis(100);
+
+/// Delay before restarting a helper that exited.
+const RESTART_DELAY: Duration = Duration::from_secs(2);
+
+/// Output lines kept to explain a helper that failed to start.
+const KEPT_OUTPUT_LINES: usize = 20;
+
+pub enum Readiness {
+    /// The endpoint accepts connections.
+    PortOpen,
+    /// A line of output matches.
+    Output(Regex),
+}
+
+/// How to run a helper process and reach the proxy it serves.
+pub struct HelperSpec {
+    /// Names the helper in logs and errors, e.g. `SSH tunnel to host:22`.
+    pub label: String,
+    pub program: String,
+    /// May contain `{port}`.
+    pub args: Vec<String>,
+    pub readiness: Readiness,
+    pub ready_timeout: Duration,
+    pub scheme: &'static str,
+    pub host: String,
+    /// 0 picks a free port on loopback.
+    pub port: u16,
+    /// A control port the helper serves besides its proxy, such as Tor's.
+    pub control_port: Option<u16>,
+}
+
+impl HelperSpec {
+    /// The helper serving `proxy` for a profile, for SSH tunnels, helper
+    /// proxies and launched Tor.
+    pub fn for_proxy(proxy: &ProxyConfig, profile_name: &str) -> Result<Option<Self>, String> {
+        match &proxy.kind {
+            ProxyKind::Ssh(server) => Ok(Some(ssh::helper_spec(server))),
+            ProxyKind::Helper(helper) => Self::for_command(&proxy.name, helper).map(Some),
+            ProxyKind::Tor(setup) if setup.launched() => {
+                tor::helper_spec(setup, profile_name).map(Some)
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn for_command(name: &str, helper: &HelperCommand) -> Result<Self, String> {
+        let readiness = match helper.readiness {
+            ReadinessProbe::Port => Readiness::PortOpen,
+            ReadinessProbe::Output => {
+                let pattern = helper.ready_pattern.as_deref().unwrap_or_default();
+                Readiness::Output(
+                    Regex::new(pattern).map_err(|e| format!("Invalid readiness pattern: {}", e))?,
+                )
+            }
+        };
+
+        Ok(Self {
+            label: format!("Helper '{}'", name),
+            program: helper.command.clone(),
+            args: helper.args.clone(),
+            readiness,
+            ready_timeout: Duration::from_secs(helper.ready_timeout_secs),
+            scheme: helper.endpoint_type.scheme(),
+            host: helper.host.clone(),
+            port: helper.port,
+            control_port: None,
+        })
+    }
+}
+
+/// A helper program serving a proxy for a browser session.
+///
+/// Once ready, the helper is restarted with the same arguments whenever it
+/// exits. The process is killed when this is dropped.
+pub struct HelperProcess {
+    label: String,
+    scheme: &'static str,
+    host: String,
+    port: u16,
+    control_port: Option<u16>,
+    supervisor: JoinHandle<()>,
+}
+
+impl HelperProcess {
+    /// Starts the helper and waits until it is ready.
+    pub async fn start(spec: HelperSpec) -> Result<Self, String> {
+        let port = match spec.port {
+            0 => free_port().map_err(|e| format!("Failed to pick a local port: {}", e))?,
+            port => {
+                // A helper left over from an earlier session would pass the probe
+                if TcpStream::connect((spec.host.as_str(), port)).await.is_ok() {
+                    return Err(format!(
+                        "{}: {}:{} is already in use",
+                        spec.label, spec.host, port
+                    ));
+                }
+                port
+            }
+        };
+        let args: Vec<String> = spec
+            .args
+            .iter()
+            .map(|arg| arg.replace(PORT_PLACEHOLDER, &port.to_string()))
+            .collect();
+
+        let child = spawn_ready(&spec, &args, port).await?;
+        println!("{} ready on {}:{}", spec.label, spec.host, port);
+
+        Ok(Self {
+            label: spec.label.clone(),
+            scheme: spec.scheme,
+            host: spec.host.clone(),
+            port,
+            control_port: spec.control_port,
+            supervisor: tokio::spawn(supervise(child, spec, args, port)),
+        })
+    }
+
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    pub fn control_port(&self) -> Option<u16> {
+        self.control_port
+    }
+
+    /// The endpoint's scheme, `http` or `socks5`.
+    pub fn scheme(&self) -> &'static str {
+        self.scheme
+    }
+
+    /// The value to pass to the browser as `--proxy-server`.
+    pub fn proxy_server(&self) -> String {
+        format!("{}://{}:{}", self.scheme, self.host, self.port)
+    }
+}
+
+impl Drop for HelperProcess {
+    fn drop(&mut self) {
+        // The supervisor owns the child, which is killed when it is dropped
+        self.supervisor.abort();
+        println!("{} stopped", self.label);
+    }
+}
+
+/// Looks `program` up like a shell would: as a path when it has a
+/// directory part, otherwise in `PATH`.
+pub fn find_program(program: &str) -> Option<PathBuf> {
+    let path = Path::new(program);
+    if path.components().count() > 1 {
+        return path.is_file().then(|| path.to_path_buf());
+    }
+
+    let names: Vec<String> = if cfg!(target_os = "windows") && path.extension().is_none() {
+        vec![format!("{}.exe", program), program.to_string()]
+    } else {
+        vec![program.to_string()]
+    };
+    std::env::split_paths(&std::env::var_os("PATH")?)
+        .flat_map(|dir| names.iter().map(move |name| dir.join(name)))
+        .find(|candidate| candidate.is_file())
+}
+
+pub fn free_port() -> std::io::Result<u16> {
+    Ok(std::net::TcpListener::bind("127.0.0.1:0")?
+        .local_addr()?
+        .port())
+}
+
+/// Logs every line of a helper's output and passes it on for the probe.
+fn forward_lines<R>(stream: R, label: String, sender: mpsc::UnboundedSender<String>)
+where
+    R: AsyncRead + Unpin + Send + 'static,
+{
+    tokio::spawn(async move {
+        let mut lines = BufReader::new(stream).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            eprintln!("{}: {}", label, line);
+            // Nobody listens once the helper is ready
+            let _ = sender.send(line);
+        }
+    });
+}
+
+/// Spawns the helper and waits for it to become ready, failing with its
+/// recent output when it exits first or takes too long.
+async fn spawn_ready(spec: &HelperSpec, args: &[String], port: u16) -> Result<Child, String> {
+    let mut child = Command::new(&spec.program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| format!("Failed to start {}: {}", spec.program, e))?;
+
+    let (sender, mut lines) = mpsc::unbounded_channel();
+    if let Some(stdout) = child.stdout.take() {
+        forward_lines(stdout, spec.label.clone(), sender.clone());
+    }
+    if let Some(stderr) = child.stderr.take() {
+        forward_lines(stderr, spec.label.clone(), sender);
+    }
+
+    let started = Instant::now();
+    let mut output: Vec<String> = Vec::new();
+    loop {
+        while let Ok(line) = lines.try_recv() {
+            if let Readiness::Output(pattern) = &spec.readiness {
+                if pattern.is_match(&line) {
+                    return Ok(child);
+                }
+            }
+            output.push(line);
+            if output.len() > KEPT_OUTPUT_LINES {
+                output.remove(0);
+            }
+        }
+
+        let status = child
+            .try_wait()
+            .map_err(|e| format!("Failed to check {}: {}", spec.label, e))?;
+        if let Some(status) = status {
+            // Collect what the helper printed before it exited
+            let deadline = tokio::time::sleep(Duration::from_secs(1));
+            tokio::pin!(deadline);
+            loop {
+                tokio::select! {
+                    line = lines.recv() => match line {
+                        Some(line) => output.push(line),
+                        None => break,
+                    },
+                    _ = &mut deadline => break,
+                }
+            }
+            return Err(format!(
+                "{} exited ({}) before it was ready: {}",
+                spec.label,
+                status,
+                output.join("\n").trim()
+            ));
+        }
+
+        if let Readiness::PortOpen = spec.readiness {
+            if TcpStream::connect((spec.host.as_str(), port)).await.is_ok() {
+                return Ok(child);
+            }
+        }
+        if started.elapsed() > spec.ready_timeout {
+            return Err(format!(
+                "Timed out waiting for {} to become ready",
+                spec.label
+            ));
+        }
+        tokio::time::sleep(READY_POLL).await;
+    }
+}
+
+async fn supervise(mut child: Child, spec: HelperSpec, args: Vec<String>, port: u16) {
+    loop {
+        match child.wait().await {
+            Ok(status) => eprintln!("{} exited ({}), restarting", spec.label, status),
+            Err(e) => eprintln!("Failed to wait for {}: {}", spec.label, e),
+        }
+
+        child = loop {
+            tokio::time::sleep(RESTART_DELAY).await;
+            match spawn_ready(&spec, &args, port).await {
+                Ok(child) => break child,
+                Err(e) => eprintln!("Failed to restart: {}", e),
+            }
+        };
+        println!("{} restarted", spec.label);
+    }
+}
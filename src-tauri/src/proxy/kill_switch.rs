@@ -0,0 +1,56 @@
+use super::health::{HealthStatus, ProxyHealth};
+use super::monitor::HealthMonitor;
+use serde::Serialize;
+
+pub const KILL_SWITCH_EVENT: &str = "kill-switch";
+
+/// Sent when a fail-closed proxy goes down or comes back.
+#[derive(Debug, Serialize, Clone)]
+pub struct KillSwitchChanged {
+    pub proxy_id: String,
+    /// Whether sessions using the proxy are now blocked.
+    pub engaged: bool,
+    pub message: Option<String>,
+}
+
+impl KillSwitchChanged {
+    /// The change between two results of a fail-closed proxy, if any.
+    pub fn between(previous: Option<HealthStatus>, health: &ProxyHealth) -> Option<Self> {
+        let was_down = previous == Some(HealthStatus::Down);
+        let is_down = health.status == HealthStatus::Down;
+        (was_down != is_down).then(|| Self {
+            proxy_id: health.proxy_id.clone(),
+            engaged: is_down,
+            message: health.message.clone(),
+        })
+    }
+}
+
+/// Refuses a session's connections while its proxy, or any hop of its
+/// chain, was found down by the latest health check. The browser then gets
+/// an error instead of a fallback to another route.
+#[derive(Clone)]
+pub struct KillSwitch {
+    monitor: HealthMonitor,
+    proxy_ids: Vec<String>,
+}
+
+impl KillSwitch {
+    pub fn new(monitor: HealthMonitor, proxy_ids: Vec<String>) -> Self {
+        Self { monitor, proxy_ids }
+    }
+
+    /// Why connections are blocked right now, if they are.
+    pub fn blocked(&self) -> Option<String> {
+        self.proxy_ids.iter().find_map(|proxy_id| {
+            let health = self.monitor.latest(proxy_id)?;
+            (health.status == HealthStatus::Down).then(|| {
+                format!(
+                    "Kill switch engaged: proxy {} is down ({})",
+                    proxy_id,
+                    health.message.as_deref().unwrap_or("no details")
+                )
+            })
+        })
+    }
+}
@@ -0,0 +1,17 @@
+pub mod auth;
+pub mod bypass;
+pub mod connection_log;
+pub mod dns;
+pub mod forwarder;
+pub mod geo;
+pub mod group;
+pub mod health;
+pub mod helper;
+pub mod kill_switch;
+pub mod monitor;
+pub mod pac;
+pub mod pac_eval;
+pub mod pac_server;
+pub mod ssh;
+pub mod tor;
+pub mod usage;
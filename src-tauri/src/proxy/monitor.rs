@@ -0,0 +1,174 @@
+use super::geo::GeoIp;
+use super::health::{self, HealthStatus, HelperCheck, ProxyHealth};
+use super::kill_switch::{KillSwitchChanged, KILL_SWITCH_EVENT};
+use crate::browser::session::SessionManager;
+use crate::settings::SettingsManager;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::{Emitter, Manager};
+
+pub const STATUS_CHANGED_EVENT: &str = "proxy-status-changed";
+
+/// How long to wait before re-reading settings while monitoring is disabled.
+const DISABLED_POLL: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Serialize, Clone)]
+pub struct ProxyStatusChanged {
+    pub proxy_id: String,
+    pub previous: Option<HealthStatus>,
+    pub health: ProxyHealth,
+}
+
+/// Keeps the most recent health check results for every proxy.
+#[derive(Clone, Default)]
+pub struct HealthMonitor {
+    history: Arc<Mutex<HashMap<String, VecDeque<ProxyHealth>>>>,
+}
+
+impl HealthMonitor {
+    /// Stores a result, keeping at most `limit` entries per proxy, and
+    /// returns the status of the previous result.
+    pub fn record(&self, health: ProxyHealth, limit: usize) -> Option<HealthStatus> {
+        let mut history = self.history.lock().unwrap();
+        let entries = history.entry(health.proxy_id.clone()).or_default();
+        let previous = entries.back().map(|last| last.status);
+
+        entries.push_back(health);
+        while entries.len() > limit.max(1) {
+            entries.pop_front();
+        }
+
+        previous
+    }
+
+    pub fn latest(&self, proxy_id: &str) -> Option<ProxyHealth> {
+        self.history
+            .lock()
+            .unwrap()
+            .get(proxy_id)
+            .and_then(|entries| entries.back().cloned())
+    }
+
+    pub fn latest_all(&self) -> Vec<ProxyHealth> {
+        self.history
+            .lock()
+            .unwrap()
+            .values()
+            .filter_map(|entries| entries.back().cloned())
+            .collect()
+    }
+
+    pub fn history(&self, proxy_id: &str) -> Vec<ProxyHealth> {
+        self.history
+            .lock()
+            .unwrap()
+            .get(proxy_id)
+            .map(|entries| entries.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Drops history for proxies that no longer exist.
+    fn retain(&self, proxy_ids: &HashSet<String>) {
+        self.history
+            .lock()
+            .unwrap()
+            .retain(|proxy_id, _| proxy_ids.contains(proxy_id));
+    }
+}
+
+/// Records a result and notifies the frontend when the proxy's status
+/// changed, and when a fail-closed proxy starts or stops blocking traffic.
+pub fn publish(
+    app_handle: &tauri::AppHandle,
+    health: ProxyHealth,
+    limit: usize,
+    fail_closed: bool,
+) {
+    let monitor = app_handle.state::<HealthMonitor>();
+    let previous = monitor.record(health.clone(), limit);
+
+    if fail_closed {
+        if let Some(event) = KillSwitchChanged::between(previous, &health) {
+            if let Err(e) = app_handle.emit(KILL_SWITCH_EVENT, event) {
+                eprintln!("Failed to emit kill switch event: {}", e);
+            }
+        }
+    }
+
+    if previous != Some(health.status) {
+        let event = ProxyStatusChanged {
+            proxy_id: health.proxy_id.clone(),
+            previous,
+            health,
+        };
+        if let Err(e) = app_handle.emit(STATUS_CHANGED_EVENT, event) {
+            eprintln!("Failed to emit proxy status event: {}", e);
+        }
+    }
+}
+
+/// Periodically probes every configured proxy in the background.
+pub fn spawn(app_handle: tauri::AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let interval = run_checks(&app_handle).await;
+            tokio::time::sleep(interval).await;
+        }
+    });
+}
+
+async fn run_checks(app_handle: &tauri::AppHandle) -> Duration {
+    let settings = match SettingsManager::new().and_then(|manager| manager.load_settings()) {
+        Ok(settings) => settings,
+        Err(e) => {
+            eprintln!("Health monitor failed to load settings: {}", e);
+            return DISABLED_POLL;
+        }
+    };
+
+    let proxy_ids = settings.proxies.iter().map(|p| p.id.clone()).collect();
+    app_handle.state::<HealthMonitor>().retain(&proxy_ids);
+
+    let Some(interval_secs) = settings.health_check_interval() else {
+        return DISABLED_POLL;
+    };
+
+    let proxies = Arc::new(settings.proxies);
+    let rules = Arc::new(settings.pac_rules);
+    let sessions = app_handle.state::<SessionManager>();
+    let mut checks = tokio::task::JoinSet::new();
+    for proxy in proxies.iter() {
+        // Helpers are only checked while a browser session runs them
+        let helper_check = HelperCheck::Running(sessions.helper_proxy_server(&proxy.id));
+        let proxy = proxy.clone();
+        let proxies = proxies.clone();
+        let rules = rules.clone();
+        let check_url = settings.health_check_url.clone();
+        checks.spawn(async move {
+            let health =
+                health::check_proxy(&proxy, &proxies, &rules, &check_url, helper_check).await;
+            (health, proxy.fail_closed)
+        });
+    }
+
+    while let Some(result) = checks.join_next().await {
+        match result {
+            Ok((mut health, fail_closed)) => {
+                app_handle
+                    .state::<GeoIp>()
+                    .locate(&mut health, &settings.geoip_databases);
+                publish(
+                    app_handle,
+                    health,
+                    settings.health_history_size,
+                    fail_closed,
+                )
+            }
+            Err(e) => eprintln!("Health check task failed: {}", e),
+        }
+    }
+
+    Duration::from_secs(interval_secs)
+}
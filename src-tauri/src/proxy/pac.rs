@@ -0,0 +1,295 @@
+use super::bypass::BypassRule;
+use crate::settings::{PacRule, ProxyConfig, ProxyKind};
+use reqwest::Url;
+use std::net::IpAddr;
+use std::path::PathBuf;
+
+/// A single entry of a PAC `FindProxyForURL` result, e.g. `PROXY host:port`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PacDirective {
+    Direct,
+    Proxy {
+        scheme: String,
+        host: String,
+        port: u16,
+    },
+}
+
+impl PacDirective {
+    pub fn parse(entry: &str) -> Option<Self> {
+        let mut parts = entry.split_whitespace();
+        let keyword = parts.next()?.to_ascii_uppercase();
+        if keyword == "DIRECT" {
+            return Some(PacDirective::Direct);
+        }
+
+        let scheme = match keyword.as_str() {
+            "PROXY" | "HTTP" => "http",
+            "HTTPS" => "https",
+            "SOCKS" | "SOCKS4" => "socks4",
+            "SOCKS5" => "socks5",
+            _ => return None,
+        };
+        let (host, port) = parts.next()?.rsplit_once(':')?;
+
+        Some(PacDirective::Proxy {
+            scheme: scheme.to_string(),
+            host: host.trim_matches(|c| c == '[' || c == ']').to_string(),
+            port: port.parse().ok()?,
+        })
+    }
+}
+
+/// The file a PAC "URL" refers to when it is a `file://` URL or an
+/// absolute path rather than a web address.
+pub fn local_path(url: &str) -> Option<PathBuf> {
+    if url.starts_with("file:") {
+        return Url::parse(url).ok()?.to_file_path().ok();
+    }
+    let path = PathBuf::from(url);
+    path.is_absolute().then_some(path)
+}
+
+pub async fn fetch_script(url: &str) -> Result<String, String> {
+    if let Some(path) = local_path(url) {
+        return tokio::fs::read_to_string(&path)
+            .await
+            .map_err(|e| format!("Failed to read PAC file {}: {}", path.display(), e));
+    }
+
+    let response = reqwest::get(url)
+        .await
+        .map_err(|e| format!("Failed to fetch PAC script: {}", e))?;
+    if !response.status().is_success() {
+        return Err(format!(
+            "Failed to fetch PAC script: server returned {}",
+            response.status()
+        ));
+    }
+    response
+        .text()
+        .await
+        .map_err(|e| format!("Failed to read PAC script: {}", e))
+}
+
+/// Builds a `FindProxyForURL` script returning the target of the first rule
+/// that matches the host, and DIRECT when none does.
+pub fn generate_script(rules: &[PacRule], proxies: &[ProxyConfig]) -> Result<String, String> {
+    let mut script = String::from("function FindProxyForURL(url, host) {\n");
+    script.push_str("  host = host.toLowerCase();\n");
+
+    for rule in rules {
+        script.push_str(&format!(
+            "  if ({}) return {};\n",
+            rule_condition(&rule.pattern)?,
+            quote(&rule_target(rule, proxies)?)
+        ));
+    }
+
+    script.push_str("  return \"DIRECT\";\n}\n");
+    Ok(script)
+}
+
+/// Wraps `script` so it never sends a request direct: DIRECT entries are
+/// dropped from every result and `blackhole` (a `PROXY host:port` entry
+/// that refuses everything) is appended as the last resort. A script that
+/// throws also falls back to the blackhole.
+pub fn fail_closed_script(script: &str, blackhole: &str) -> String {
+    format!(
+        r#"var __findProxyForURL = (function () {{
+{script}
+return FindProxyForURL;
+}})();
+
+function FindProxyForURL(url, host) {{
+  var entries;
+  try {{
+    entries = String(__findProxyForURL(url, host)).split(";");
+  }} catch (e) {{
+    entries = [];
+  }}
+  var result = [];
+  for (var i = 0; i < entries.length; i++) {{
+    var entry = entries[i].replace(/^\s+|\s+$/g, "");
+    if (entry && entry.toUpperCase() != "DIRECT") result.push(entry);
+  }}
+  result.push({blackhole});
+  return result.join("; ");
+}}
+"#,
+        script = script,
+        blackhole = quote(blackhole)
+    )
+}
+
+/// A script sending every request to `blackhole`, served in place of a
+/// fail-closed script that could not be loaded.
+pub fn blocking_script(blackhole: &str) -> String {
+    format!(
+        "function FindProxyForURL(url, host) {{\n  return {};\n}}\n",
+        quote(blackhole)
+    )
+}
+
+/// Checks every rule pattern and that each target can be returned from a
+/// PAC script.
+pub fn validate_rules(rules: &[PacRule], proxies: &[ProxyConfig]) -> Result<(), String> {
+    for rule in rules {
+        rule_condition(&rule.pattern)?;
+        rule_target(rule, proxies)?;
+    }
+    Ok(())
+}
+
+/// The proxy the rules send `url` through, or `None` when it goes direct.
+/// Hostnames are not resolved, so CIDR rules only match IP literals here.
+pub fn route<'a>(
+    rules: &[PacRule],
+    proxies: &'a [ProxyConfig],
+    url: &Url,
+) -> Result<Option<&'a ProxyConfig>, String> {
+    let rule = rules
+        .iter()
+        .find(|rule| BypassRule::parse(&rule.pattern).is_ok_and(|pattern| pattern.matches(url)));
+
+    match rule.and_then(|rule| rule.proxy_id.as_ref()) {
+        Some(proxy_id) => proxies
+            .iter()
+            .find(|p| p.id == *proxy_id)
+            .map(Some)
+            .ok_or_else(|| "PAC rule references a missing proxy".to_string()),
+        None => Ok(None),
+    }
+}
+
+fn rule_condition(pattern: &str) -> Result<String, String> {
+    let invalid = || format!("Invalid PAC rule pattern '{}'", pattern);
+
+    match BypassRule::parse(pattern).map_err(|_| invalid())? {
+        // isInNet resolves hostnames, so CIDR rules also match names that
+        // resolve into the range
+        BypassRule::Network {
+            address: IpAddr::V4(address),
+            prefix_len,
+        } => {
+            let mask = u32::MAX.checked_shl(32 - prefix_len as u32).unwrap_or(0);
+            Ok(format!(
+                "isInNet(host, {}, {})",
+                quote(&address.to_string()),
+                quote(&std::net::Ipv4Addr::from(mask).to_string())
+            ))
+        }
+        BypassRule::Network {
+            address: IpAddr::V6(address),
+            prefix_len,
+        } => Ok(format!(
+            "isInNetEx(host, {})",
+            quote(&format!("{}/{}", address, prefix_len))
+        )),
+        BypassRule::Host {
+            scheme: None,
+            pattern,
+            port: None,
+        } => Ok(format!("shExpMatch(host, {})", quote(&pattern))),
+        _ => Err(invalid()),
+    }
+}
+
+/// The PAC result for a rule. A PAC script can only name a server, so the
+/// stored credentials of a proxy would never be sent and the browser would
+/// prompt for them or fail; proxies with credentials, SOCKS4a and chains all
+/// need the local forwarder and cannot be rule targets.
+fn rule_target(rule: &PacRule, proxies: &[ProxyConfig]) -> Result<String, String> {
+    let Some(proxy_id) = &rule.proxy_id else {
+        return Ok("DIRECT".to_string());
+    };
+    let proxy = proxies
+        .iter()
+        .find(|p| p.id == *proxy_id)
+        .ok_or_else(|| format!("PAC rule '{}' references a missing proxy", rule.pattern))?;
+
+    let (keyword, server) = match &proxy.kind {
+        ProxyKind::Http(server) if server.credentials().is_none() => ("PROXY", server),
+        ProxyKind::Https(server) if server.credentials().is_none() => ("HTTPS", server),
+        ProxyKind::Socks4(server) if server.credentials().is_none() => ("SOCKS", server),
+        ProxyKind::Socks5(server) if server.credentials().is_none() => ("SOCKS5", server),
+        _ => {
+            return Err(format!(
+                "PAC rule '{}' cannot route through proxy '{}'; only HTTP, HTTPS and SOCKS4/SOCKS5 proxies without credentials can be used",
+                rule.pattern, proxy.name
+            ))
+        }
+    };
+
+    Ok(format!("{} {}:{}", keyword, server.host, server.port))
+}
+
+fn quote(value: &str) -> String {
+    serde_json::to_string(value).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn proxy(id: &str, proxy_type: &str, username: Option<&str>) -> ProxyConfig {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "name": id,
+            "proxy_type": proxy_type,
+            "host": format!("{}.example.net", id),
+            "port": 8080,
+            "username": username,
+            "password": username.map(|_| "secret"),
+        }))
+        .unwrap()
+    }
+
+    fn rule(pattern: &str, proxy_id: Option<&str>) -> PacRule {
+        PacRule {
+            pattern: pattern.to_string(),
+            proxy_id: proxy_id.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn generates_script_from_rules() {
+        let proxies = vec![
+            proxy("web", "http", None),
+            proxy("tls", "https", None),
+            proxy("socks", "socks5", None),
+        ];
+        let rules = vec![
+            rule("*.corp", Some("web")),
+            rule("10.0.0.0/8", Some("socks")),
+            rule("example.com", None),
+            rule("*", Some("tls")),
+        ];
+        assert_eq!(
+            generate_script(&rules, &proxies).unwrap(),
+            r#"function FindProxyForURL(url, host) {
+  host = host.toLowerCase();
+  if (shExpMatch(host, "*.corp")) return "PROXY web.example.net:8080";
+  if (isInNet(host, "10.0.0.0", "255.0.0.0")) return "SOCKS5 socks.example.net:8080";
+  if (shExpMatch(host, "example.com")) return "DIRECT";
+  if (shExpMatch(host, "*")) return "HTTPS tls.example.net:8080";
+  return "DIRECT";
+}
+"#
+        );
+    }
+
+    #[test]
+    fn rejects_proxies_with_credentials() {
+        for proxy_type in ["http", "https", "socks5"] {
+            let proxies = vec![proxy("auth", proxy_type, Some("user"))];
+            let error = generate_script(&[rule("*.corp", Some("auth"))], &proxies).unwrap_err();
+            assert!(
+                error.contains("cannot route through proxy 'auth'"),
+                "{}: {}",
+                proxy_type,
+                error
+            );
+        }
+    }
+}
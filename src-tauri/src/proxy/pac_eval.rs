@@ -0,0 +1,489 @@
+use reqwest::Url;
+use rquickjs::{CatchResultExt, Context, Function, Object, Runtime};
+use serde::Serialize;
+use std::net::{IpAddr, ToSocketAddrs, UdpSocket};
+use std::time::{Duration, Instant};
+
+/// How long a script may run before it is interrupted.
+const EVALUATION_TIMEOUT: Duration = Duration::from_secs(5);
+const MEMORY_LIMIT: usize = 32 * 1024 * 1024;
+
+/// The PAC helper functions browsers provide to `FindProxyForURL`. DNS and
+/// local address lookups are implemented natively.
+const PAC_PRELUDE: &str = r#"
+var __days = ["SUN", "MON", "TUE", "WED", "THU", "FRI", "SAT"];
+var __months = ["JAN", "FEB", "MAR", "APR", "MAY", "JUN",
+                "JUL", "AUG", "SEP", "OCT", "NOV", "DEC"];
+
+function isPlainHostName(host) {
+  return host.indexOf(".") < 0 && host.indexOf(":") < 0;
+}
+
+function dnsDomainIs(host, domain) {
+  return host.length >= domain.length &&
+    host.substring(host.length - domain.length) == domain;
+}
+
+function localHostOrDomainIs(host, hostdom) {
+  return host == hostdom || hostdom.lastIndexOf(host + ".", 0) == 0;
+}
+
+function dnsResolve(host) {
+  var ip = __dnsResolve(host);
+  return ip === undefined ? null : ip;
+}
+
+function isResolvable(host) {
+  return dnsResolve(host) !== null;
+}
+
+function isResolvableEx(host) {
+  return dnsResolveEx(host) !== "";
+}
+
+function dnsDomainLevels(host) {
+  return host.split(".").length - 1;
+}
+
+function __ipBits(ip) {
+  if (ip.indexOf(":") < 0) {
+    var octets = ip.split(".");
+    if (octets.length != 4) return null;
+    var bits = "";
+    for (var i = 0; i < 4; i++) {
+      var octet = parseInt(octets[i], 10);
+      if (isNaN(octet) || octet < 0 || octet > 255) return null;
+      bits += ("00000000" + octet.toString(2)).slice(-8);
+    }
+    return bits;
+  }
+  var halves = ip.split("::");
+  if (halves.length > 2) return null;
+  var head = halves[0] ? halves[0].split(":") : [];
+  var tail = halves.length == 2 && halves[1] ? halves[1].split(":") : [];
+  var missing = 8 - head.length - tail.length;
+  if (missing < 0 || (halves.length == 1 && missing != 0)) return null;
+  var groups = head.concat(new Array(missing + 1).join("0").split(""), tail);
+  var bits = "";
+  for (var i = 0; i < groups.length; i++) {
+    var group = parseInt(groups[i], 16);
+    if (isNaN(group)) return null;
+    bits += ("0000000000000000" + group.toString(2)).slice(-16);
+  }
+  return bits;
+}
+
+function isInNet(host, pattern, mask) {
+  var ip = /^\d+\.\d+\.\d+\.\d+$/.test(host) ? host : dnsResolve(host);
+  if (ip === null) return false;
+  var ipBits = __ipBits(ip), patternBits = __ipBits(pattern), maskBits = __ipBits(mask);
+  if (ipBits === null || patternBits === null || maskBits === null) return false;
+  for (var i = 0; i < 32; i++) {
+    if (maskBits[i] == "1" && ipBits[i] != patternBits[i]) return false;
+  }
+  return true;
+}
+
+function isInNetEx(ip, prefix) {
+  var parts = prefix.split("/");
+  var ipBits = __ipBits(ip), prefixBits = __ipBits(parts[0]);
+  var length = parseInt(parts[1], 10);
+  if (ipBits === null || prefixBits === null || isNaN(length)) return false;
+  if (ipBits.length != prefixBits.length || length > prefixBits.length) return false;
+  return ipBits.substring(0, length) == prefixBits.substring(0, length);
+}
+
+function sortIpAddressList(list) {
+  var ips = list.split(";");
+  for (var i = 0; i < ips.length; i++) {
+    if (__ipBits(ips[i]) === null) return false;
+  }
+  // IPv6 addresses first, then by address
+  return ips.sort(function (a, b) {
+    var aBits = __ipBits(a), bBits = __ipBits(b);
+    if (aBits.length != bBits.length) return bBits.length - aBits.length;
+    return aBits < bBits ? -1 : aBits > bBits ? 1 : 0;
+  }).join(";");
+}
+
+function getClientVersion() {
+  return "1.0";
+}
+
+function shExpMatch(str, shexp) {
+  var pattern = shexp
+    .replace(/[.+^${}()|[\]\\]/g, "\\$&")
+    .replace(/\*/g, ".*")
+    .replace(/\?/g, ".");
+  return new RegExp("^" + pattern + "$").test(str);
+}
+
+function __useGmt(args) {
+  if (args.length > 0 && args[args.length - 1] == "GMT") {
+    args.pop();
+    return true;
+  }
+  return false;
+}
+
+function __inRange(start, value, end) {
+  return start <= end ? start <= value && value <= end : value >= start || value <= end;
+}
+
+function weekdayRange() {
+  var args = Array.prototype.slice.call(arguments);
+  var gmt = __useGmt(args);
+  var now = new Date();
+  var day = gmt ? now.getUTCDay() : now.getDay();
+  var start = __days.indexOf(args[0]);
+  var end = args.length > 1 ? __days.indexOf(args[1]) : start;
+  if (start < 0 || end < 0) return false;
+  return __inRange(start, day, end);
+}
+
+function dateRange() {
+  var args = Array.prototype.slice.call(arguments);
+  var gmt = __useGmt(args);
+  var now = new Date();
+  var current = {
+    day: gmt ? now.getUTCDate() : now.getDate(),
+    month: gmt ? now.getUTCMonth() : now.getMonth(),
+    year: gmt ? now.getUTCFullYear() : now.getFullYear()
+  };
+  function parse(values) {
+    var date = {};
+    for (var i = 0; i < values.length; i++) {
+      var value = values[i];
+      if (typeof value == "string") {
+        date.month = __months.indexOf(value);
+        if (date.month < 0) return null;
+      } else if (value > 31) {
+        date.year = value;
+      } else {
+        date.day = value;
+      }
+    }
+    return date;
+  }
+  function key(date, fields) {
+    return (fields.year ? date.year * 10000 : 0) +
+      (fields.month ? (date.month + 1) * 100 : 0) +
+      (fields.day ? date.day : 0);
+  }
+  if (args.length == 0 || args.length % 2 == 1 && args.length != 1) return false;
+  var half = args.length == 1 ? 1 : args.length / 2;
+  var start = parse(args.slice(0, half));
+  var end = args.length == 1 ? start : parse(args.slice(half));
+  if (start === null || end === null) return false;
+  var fields = {
+    year: start.year !== undefined,
+    month: start.month !== undefined,
+    day: start.day !== undefined
+  };
+  return __inRange(key(start, fields), key(current, fields), key(end, fields));
+}
+
+function timeRange() {
+  var args = Array.prototype.slice.call(arguments);
+  var gmt = __useGmt(args);
+  var now = new Date();
+  var hours = gmt ? now.getUTCHours() : now.getHours();
+  var current = hours * 3600 +
+    (gmt ? now.getUTCMinutes() : now.getMinutes()) * 60 +
+    (gmt ? now.getUTCSeconds() : now.getSeconds());
+  switch (args.length) {
+    case 1:
+      return hours == args[0];
+    case 2:
+      return __inRange(args[0] * 3600, current, args[1] * 3600);
+    case 4:
+      return __inRange(args[0] * 3600 + args[1] * 60, current,
+                       args[2] * 3600 + args[3] * 60);
+    case 6:
+      return __inRange(args[0] * 3600 + args[1] * 60 + args[2], current,
+                       args[3] * 3600 + args[4] * 60 + args[5]);
+    default:
+      return false;
+  }
+}
+
+function alert(message) {}
+"#;
+
+#[derive(Debug, Serialize, Clone)]
+pub struct PacEvaluation {
+    pub url: String,
+    /// The string `FindProxyForURL` returned, e.g. `PROXY host:8080; DIRECT`.
+    pub result: String,
+    /// The entries of the result in the order the browser tries them.
+    pub entries: Vec<String>,
+}
+
+/// Runs `FindProxyForURL` from `script` for `url`. This blocks on DNS
+/// lookups the script makes, so call it off the async runtime.
+///
+/// Like Chromium, the path and query of `https://` URLs are stripped before
+/// they are passed to the script.
+pub fn evaluate(script: &str, url: &str) -> Result<PacEvaluation, String> {
+    let parsed = Url::parse(url).map_err(|e| format!("Invalid URL {}: {}", url, e))?;
+    let host = parsed
+        .host_str()
+        .ok_or_else(|| format!("URL {} has no host", url))?
+        .trim_matches(|c| c == '[' || c == ']')
+        .to_string();
+    let script_url = match parsed.scheme() {
+        "https" | "wss" => format!("{}/", parsed.origin().ascii_serialization()),
+        _ => parsed.to_string(),
+    };
+
+    let runtime = Runtime::new().map_err(|e| format!("Failed to start PAC engine: {}", e))?;
+    runtime.set_memory_limit(MEMORY_LIMIT);
+    let deadline = Instant::now() + EVALUATION_TIMEOUT;
+    runtime.set_interrupt_handler(Some(Box::new(move || Instant::now() > deadline)));
+    let context =
+        Context::full(&runtime).map_err(|e| format!("Failed to start PAC engine: {}", e))?;
+
+    let result = context.with(|ctx| -> Result<String, String> {
+        let globals = ctx.globals();
+        register(
+            &globals,
+            "__dnsResolve",
+            Function::new(ctx.clone(), |host: String| {
+                resolve(&host)
+                    .into_iter()
+                    .find(IpAddr::is_ipv4)
+                    .map(|ip| ip.to_string())
+            }),
+        )?;
+        register(
+            &globals,
+            "dnsResolveEx",
+            Function::new(ctx.clone(), |host: String| join(&resolve(&host))),
+        )?;
+        register(
+            &globals,
+            "myIpAddress",
+            Function::new(ctx.clone(), || {
+                local_ip(false)
+                    .map(|ip| ip.to_string())
+                    .unwrap_or_else(|| "127.0.0.1".to_string())
+            }),
+        )?;
+        register(
+            &globals,
+            "myIpAddressEx",
+            Function::new(ctx.clone(), || {
+                let ips: Vec<IpAddr> = [local_ip(true), local_ip(false)]
+                    .into_iter()
+                    .flatten()
+                    .collect();
+                join(&ips)
+            }),
+        )?;
+
+        ctx.eval::<(), _>(PAC_PRELUDE)
+            .catch(&ctx)
+            .map_err(|e| format!("Failed to start PAC engine: {}", e))?;
+        ctx.eval::<(), _>(script)
+            .catch(&ctx)
+            .map_err(|e| format!("Failed to load PAC script: {}", e))?;
+
+        let find_proxy: Function = globals
+            .get("FindProxyForURL")
+            .map_err(|_| "PAC script does not define FindProxyForURL".to_string())?;
+        find_proxy
+            .call::<_, String>((script_url.as_str(), host.as_str()))
+            .catch(&ctx)
+            .map_err(|e| format!("FindProxyForURL failed: {}", e))
+    });
+    // The interrupt surfaces as a generic exception, so name the cause
+    let result = result.map_err(|e| {
+        if Instant::now() > deadline {
+            format!(
+                "PAC script did not finish within {} seconds",
+                EVALUATION_TIMEOUT.as_secs()
+            )
+        } else {
+            e
+        }
+    })?;
+
+    Ok(PacEvaluation {
+        url: url.to_string(),
+        entries: result
+            .split(';')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .map(str::to_string)
+            .collect(),
+        result,
+    })
+}
+
+fn register<'js>(
+    globals: &Object<'js>,
+    name: &str,
+    function: rquickjs::Result<Function<'js>>,
+) -> Result<(), String> {
+    function
+        .and_then(|function| globals.set(name, function))
+        .map_err(|e| format!("Failed to start PAC engine: {}", e))
+}
+
+fn resolve(host: &str) -> Vec<IpAddr> {
+    (host, 0)
+        .to_socket_addrs()
+        .map(|addrs| addrs.map(|addr| addr.ip()).collect())
+        .unwrap_or_default()
+}
+
+fn join(ips: &[IpAddr]) -> String {
+    ips.iter()
+        .map(IpAddr::to_string)
+        .collect::<Vec<_>>()
+        .join(";")
+}
+
+/// The address of the interface used to reach the internet. Connecting a
+/// UDP socket sends nothing; it only picks the route.
+fn local_ip(ipv6: bool) -> Option<IpAddr> {
+    let (bind, target) = if ipv6 {
+        ("[::]:0", "[2001:4860:4860::8888]:80")
+    } else {
+        ("0.0.0.0:0", "8.8.8.8:80")
+    };
+
+    let socket = UdpSocket::bind(bind).ok()?;
+    socket.connect(target).ok()?;
+    socket.local_addr().ok().map(|addr| addr.ip())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Evaluates a script whose `FindProxyForURL` body is `body`.
+    fn find_proxy(body: &str, url: &str) -> Result<String, String> {
+        let script = format!("function FindProxyForURL(url, host) {{ {} }}", body);
+        evaluate(&script, url).map(|evaluation| evaluation.result)
+    }
+
+    fn check(condition: &str) -> bool {
+        let body = format!("return ({}) ? \"yes\" : \"no\";", condition);
+        find_proxy(&body, "http://example.com/").unwrap() == "yes"
+    }
+
+    #[test]
+    fn splits_result_entries() {
+        let evaluation = evaluate(
+            "function FindProxyForURL(url, host) { return \"PROXY a:8080; SOCKS5 b:1080;DIRECT\"; }",
+            "http://example.com/",
+        )
+        .unwrap();
+        assert_eq!(
+            evaluation.entries,
+            ["PROXY a:8080", "SOCKS5 b:1080", "DIRECT"]
+        );
+    }
+
+    #[test]
+    fn matches_hosts() {
+        assert!(check("shExpMatch(\"www.example.com\", \"*.example.*\")"));
+        assert!(check("shExpMatch(\"a.b\", \"a?b\")"));
+        assert!(!check("shExpMatch(\"axb.c\", \"a.b.c\")"));
+        assert!(check(
+            "dnsDomainIs(\"www.corp.example\", \".corp.example\")"
+        ));
+        assert!(!check("dnsDomainIs(\"example\", \".corp.example\")"));
+        assert!(check("isPlainHostName(\"intranet\")"));
+        assert!(!check("isPlainHostName(\"::1\")"));
+    }
+
+    #[test]
+    fn matches_networks() {
+        assert!(check("isInNet(\"10.1.2.3\", \"10.0.0.0\", \"255.0.0.0\")"));
+        assert!(!check("isInNet(\"11.1.2.3\", \"10.0.0.0\", \"255.0.0.0\")"));
+        assert!(check(
+            "isInNet(\"192.168.1.77\", \"192.168.1.0\", \"255.255.255.128\")"
+        ));
+        assert!(!check(
+            "isInNet(\"192.168.1.200\", \"192.168.1.0\", \"255.255.255.128\")"
+        ));
+        assert!(check("isInNetEx(\"10.1.2.3\", \"10.0.0.0/8\")"));
+        assert!(check("isInNetEx(\"2001:db8::1\", \"2001:db8::/32\")"));
+        assert!(!check("isInNetEx(\"2001:db9::1\", \"2001:db8::/32\")"));
+        assert!(check("isInNetEx(\"fe80::1:2\", \"fe80::/10\")"));
+        // Address families never match each other
+        assert!(!check("isInNetEx(\"10.0.0.1\", \"::/0\")"));
+    }
+
+    #[test]
+    fn expands_ipv6_bits() {
+        assert!(check(
+            "__ipBits(\"::1\") == new Array(128).join(\"0\") + \"1\""
+        ));
+        assert!(check(
+            "__ipBits(\"2001:db8::\").substring(0, 32) == \"00100000000000010000110110111000\""
+        ));
+        assert!(check("__ipBits(\"1:2:3:4:5:6:7:8\").length == 128"));
+        assert!(check("__ipBits(\"1::2::3\") === null"));
+        assert!(check("__ipBits(\"1:2:3\") === null"));
+        assert!(check("__ipBits(\"10.0.0.256\") === null"));
+        assert!(check(
+            "sortIpAddressList(\"10.0.0.2;::1;10.0.0.1\") == \"::1;10.0.0.1;10.0.0.2\""
+        ));
+    }
+
+    #[test]
+    fn checks_dates_and_times() {
+        assert!(check("dateRange(new Date().getUTCFullYear(), \"GMT\")"));
+        assert!(!check(
+            "dateRange(new Date().getUTCFullYear() + 1, \"GMT\")"
+        ));
+        assert!(check("dateRange(\"JAN\", \"DEC\")"));
+        assert!(check("dateRange(1, 31)"));
+        assert!(!check("dateRange(\"JAN\", \"FEB\", \"MAR\")"));
+        assert!(!check("dateRange(\"NOV\", \"JAN\", 1)"));
+        assert!(check("timeRange(0, 24, \"GMT\")"));
+        assert!(!check("timeRange(1, 2, 3)"));
+        assert!(check("weekdayRange(\"SUN\", \"SAT\")"));
+    }
+
+    #[test]
+    fn strips_https_paths() {
+        assert_eq!(
+            find_proxy(
+                "return url;",
+                "https://example.com:8443/account?token=secret"
+            )
+            .unwrap(),
+            "https://example.com:8443/"
+        );
+        assert_eq!(
+            find_proxy("return url;", "http://example.com/account?token=secret").unwrap(),
+            "http://example.com/account?token=secret"
+        );
+        assert_eq!(
+            find_proxy("return host;", "https://[2001:db8::1]/").unwrap(),
+            "2001:db8::1"
+        );
+    }
+
+    #[test]
+    fn reports_script_errors() {
+        assert!(evaluate("var x = 1;", "http://example.com/")
+            .unwrap_err()
+            .contains("does not define FindProxyForURL"));
+        assert!(
+            find_proxy("return undefinedFunction();", "http://example.com/")
+                .unwrap_err()
+                .starts_with("FindProxyForURL failed")
+        );
+    }
+
+    #[test]
+    fn interrupts_endless_scripts() {
+        let error = find_proxy("while (true) {}", "http://example.com/").unwrap_err();
+        assert_eq!(error, "PAC script did not finish within 5 seconds");
+    }
+}
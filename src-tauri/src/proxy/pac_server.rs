@@ -0,0 +1,164 @@
+use super::pac;
+use crate::settings::{ProxyKind, SettingsManager};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::Mutex;
+
+/// Path of the script generated from the PAC rules in settings.
+pub const RULES_PATH: &str = "/rules.pac";
+
+/// Path a PAC or rules proxy's script is served from when the browser
+/// cannot load it itself: local PAC files, and scripts of fail-closed
+/// proxies, which are wrapped so they never return DIRECT.
+pub fn proxy_script_path(proxy_id: &str) -> String {
+    format!("/proxy/{}.pac", proxy_id)
+}
+
+const MAX_REQUEST_SIZE: usize = 8 * 1024;
+
+/// Serves PAC scripts made by the app on a loopback port, since Chromium
+/// only loads `--proxy-pac-url` reliably over HTTP. The server starts on
+/// first use and scripts are generated per request, so rule edits apply
+/// whenever the browser next fetches the script.
+///
+/// The server doubles as the blackhole proxy of fail-closed scripts: any
+/// request proxied through it is refused.
+#[derive(Clone, Default)]
+pub struct PacServer {
+    addr: Arc<Mutex<Option<SocketAddr>>>,
+}
+
+impl PacServer {
+    /// The URL the script at `path` is served from, starting the server if
+    /// it is not running yet.
+    pub async fn url(&self, path: &str) -> Result<String, String> {
+        let mut addr = self.addr.lock().await;
+        let local_addr = match *addr {
+            Some(local_addr) => local_addr,
+            None => {
+                let local_addr = start()
+                    .await
+                    .map_err(|e| format!("Failed to start PAC server: {}", e))?;
+                println!("PAC server listening on {}", local_addr);
+                *addr = Some(local_addr);
+                local_addr
+            }
+        };
+        Ok(format!("http://{}{}", local_addr, path))
+    }
+}
+
+async fn start() -> std::io::Result<SocketAddr> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let local_addr = listener.local_addr()?;
+
+    tokio::spawn(async move {
+        while let Ok((client, _)) = listener.accept().await {
+            tokio::spawn(async move {
+                if let Err(e) = serve(client, local_addr).await {
+                    eprintln!("PAC server connection failed: {}", e);
+                }
+            });
+        }
+    });
+
+    Ok(local_addr)
+}
+
+async fn serve(mut client: TcpStream, local_addr: SocketAddr) -> std::io::Result<()> {
+    let mut request = Vec::new();
+    let mut buffer = [0u8; 1024];
+    while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+        let read = client.read(&mut buffer).await?;
+        if read == 0 || request.len() + read > MAX_REQUEST_SIZE {
+            return Ok(());
+        }
+        request.extend_from_slice(&buffer[..read]);
+    }
+
+    let request = String::from_utf8_lossy(&request);
+    let mut request_line = request
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .split_whitespace();
+    let response = match (request_line.next(), request_line.next()) {
+        // Proxied requests only arrive here from fail-closed scripts
+        (Some(_), Some(target)) if !target.starts_with('/') => response(
+            "403 Forbidden",
+            "text/plain",
+            "Blocked by kill switch: this request would have bypassed the proxy",
+        ),
+        (Some("GET"), Some(path)) => match script(path, local_addr).await {
+            Ok(Some(script)) => response("200 OK", "application/x-ns-proxy-autoconfig", &script),
+            Ok(None) => response("404 Not Found", "text/plain", "Not found"),
+            Err(e) => {
+                eprintln!("Failed to serve PAC script {}: {}", path, e);
+                response("500 Internal Server Error", "text/plain", &e)
+            }
+        },
+        _ => response("405 Method Not Allowed", "text/plain", "Method not allowed"),
+    };
+
+    client.write_all(response.as_bytes()).await
+}
+
+/// The script served at `path`, or `None` when there is none. Remote PAC
+/// scripts are only served for fail-closed proxies; otherwise the browser
+/// loads them itself.
+async fn script(path: &str, local_addr: SocketAddr) -> Result<Option<String>, String> {
+    let proxy_id = path
+        .strip_prefix("/proxy/")
+        .and_then(|rest| rest.strip_suffix(".pac"));
+    if path != RULES_PATH && proxy_id.is_none() {
+        return Ok(None);
+    }
+
+    let settings = SettingsManager::new()
+        .and_then(|manager| manager.load_settings())
+        .map_err(|e| format!("Failed to load settings: {}", e))?;
+
+    let Some(proxy_id) = proxy_id else {
+        return pac::generate_script(&settings.pac_rules, &settings.proxies).map(Some);
+    };
+    let Some(proxy) = settings.proxies.iter().find(|p| p.id == proxy_id) else {
+        return Ok(None);
+    };
+    let script = match &proxy.kind {
+        ProxyKind::Pac { url } if proxy.fail_closed || pac::local_path(url).is_some() => {
+            pac::fetch_script(url).await
+        }
+        ProxyKind::Rules if proxy.fail_closed => {
+            pac::generate_script(&settings.pac_rules, &settings.proxies)
+        }
+        _ => return Ok(None),
+    };
+    if !proxy.fail_closed {
+        return script.map(Some);
+    }
+
+    // A script that cannot be loaded must not make the browser go direct
+    let blackhole = format!("PROXY {}", local_addr);
+    Ok(Some(match script {
+        Ok(script) => pac::fail_closed_script(&script, &blackhole),
+        Err(e) => {
+            eprintln!(
+                "Blocking all requests of fail-closed proxy {}: {}",
+                proxy.name, e
+            );
+            pac::blocking_script(&blackhole)
+        }
+    }))
+}
+
+fn response(status: &str, content_type: &str, body: &str) -> String {
+    format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    )
+}
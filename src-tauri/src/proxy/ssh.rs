@@ -0,0 +1,161 @@
+use super::helper::{HelperSpec, Readiness, PORT_PLACEHOLDER};
+use crate::settings::{KnownHostsPolicy, SshServer};
+use std::time::Duration;
+
+/// How long to wait for the forwarded port to accept connections, which
+/// ssh only opens once it has authenticated.
+const READY_TIMEOUT: Duration = Duration::from_secs(20);
+
+#[cfg(target_os = "windows")]
+const NULL_DEVICE: &str = "NUL";
+#[cfg(not(target_os = "windows"))]
+const NULL_DEVICE: &str = "/dev/null";
+
+/// Runs `ssh -N -D` as a helper serving a SOCKS5 proxy on loopback.
+pub fn helper_spec(server: &SshServer) -> HelperSpec {
+    HelperSpec {
+        label: format!("SSH tunnel to {}:{}", server.host, server.port),
+        program: "ssh".to_string(),
+        args: ssh_args(server),
+        readiness: Readiness::PortOpen,
+        ready_timeout: READY_TIMEOUT,
+        scheme: "socks5",
+        host: "127.0.0.1".to_string(),
+        port: 0,
+        control_port: None,
+    }
+}
+
+/// Arguments for `ssh` to serve a SOCKS5 proxy on `127.0.0.1:{port}`.
+///
+/// Batch mode makes ssh fail instead of prompting for a password, passphrase
+/// or unknown host key, and keepalives make it exit when the server stops
+/// answering, so the supervisor can restart it.
+fn ssh_args(server: &SshServer) -> Vec<String> {
+    let mut args = vec![
+        "-N".to_string(),
+        "-D".to_string(),
+        format!("127.0.0.1:{}", PORT_PLACEHOLDER),
+        "-p".to_string(),
+        server.port.to_string(),
+        "-o".to_string(),
+        "BatchMode=yes".to_string(),
+        "-o".to_string(),
+        "ExitOnForwardFailure=yes".to_string(),
+        "-o".to_string(),
+        "ServerAliveInterval=15".to_string(),
+        "-o".to_string(),
+        "ServerAliveCountMax=3".to_string(),
+        "-o".to_string(),
+    ];
+    match server.known_hosts {
+        KnownHostsPolicy::Strict => args.push("StrictHostKeyChecking=yes".to_string()),
+        KnownHostsPolicy::AcceptNew => args.push("StrictHostKeyChecking=accept-new".to_string()),
+        KnownHostsPolicy::Off => {
+            args.push("StrictHostKeyChecking=no".to_string());
+            args.push("-o".to_string());
+            args.push(format!("UserKnownHostsFile={}", NULL_DEVICE));
+        }
+    }
+    if let Some(identity_file) = &server.identity_file {
+        args.push("-i".to_string());
+        args.push(identity_file.clone());
+        args.push("-o".to_string());
+        args.push("IdentitiesOnly=yes".to_string());
+    }
+    if let Some(username) = &server.username {
+        args.push("-l".to_string());
+        args.push(username.clone());
+    }
+    args.push("--".to_string());
+    args.push(server.host.clone());
+    args
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn server(known_hosts: KnownHostsPolicy) -> SshServer {
+        SshServer {
+            host: "bastion.example".to_string(),
+            port: 2222,
+            username: None,
+            identity_file: None,
+            known_hosts,
+        }
+    }
+
+    #[test]
+    fn strict_host_keys_in_batch_mode() {
+        let args = ssh_args(&server(KnownHostsPolicy::Strict));
+        assert_eq!(
+            args,
+            [
+                "-N".to_string(),
+                "-D".to_string(),
+                format!("127.0.0.1:{}", PORT_PLACEHOLDER),
+                "-p".to_string(),
+                "2222".to_string(),
+                "-o".to_string(),
+                "BatchMode=yes".to_string(),
+                "-o".to_string(),
+                "ExitOnForwardFailure=yes".to_string(),
+                "-o".to_string(),
+                "ServerAliveInterval=15".to_string(),
+                "-o".to_string(),
+                "ServerAliveCountMax=3".to_string(),
+                "-o".to_string(),
+                "StrictHostKeyChecking=yes".to_string(),
+                "--".to_string(),
+                "bastion.example".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn known_hosts_policies() {
+        let args = ssh_args(&server(KnownHostsPolicy::AcceptNew));
+        assert!(args.contains(&"StrictHostKeyChecking=accept-new".to_string()));
+        assert!(!args
+            .iter()
+            .any(|arg| arg.starts_with("UserKnownHostsFile=")));
+
+        let args = ssh_args(&server(KnownHostsPolicy::Off));
+        let policy = args
+            .iter()
+            .position(|arg| arg == "StrictHostKeyChecking=no")
+            .unwrap();
+        assert_eq!(
+            args[policy + 1..policy + 3],
+            [
+                "-o".to_string(),
+                format!("UserKnownHostsFile={}", NULL_DEVICE)
+            ]
+        );
+    }
+
+    #[test]
+    fn identity_and_user_before_host() {
+        let mut server = server(KnownHostsPolicy::Strict);
+        server.username = Some("alice".to_string());
+        server.identity_file = Some("/home/alice/.ssh/id_ed25519".to_string());
+        // A host that looks like an option must not be parsed as one
+        server.host = "-oProxyCommand=evil".to_string();
+
+        let args = ssh_args(&server);
+        assert_eq!(
+            args[args.len() - 8..],
+            [
+                "-i",
+                "/home/alice/.ssh/id_ed25519",
+                "-o",
+                "IdentitiesOnly=yes",
+                "-l",
+                "alice",
+                "--",
+                "-oProxyCommand=evil",
+            ]
+        );
+    }
+}
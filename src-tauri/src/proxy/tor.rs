@@ -0,0 +1,332 @@
+use super::helper::{self, HelperSpec, Readiness, PORT_PLACEHOLDER};
+use crate::settings::{SettingsManager, TorSetup};
+use regex::Regex;
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+
+/// How long a launched Tor may take to build its first circuits. With a
+/// fresh data directory it has to download the consensus first.
+const READY_TIMEOUT: Duration = Duration::from_secs(120);
+
+const CONTROL_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The data directory of the Tor launched for a profile, which keeps its
+/// guards and cached directory between sessions.
+pub fn data_dir(profile_name: &str) -> Result<PathBuf, String> {
+    let settings_dir = SettingsManager::new()
+        .and_then(|manager| manager.get_settings_dir())
+        .map_err(|e| format!("Failed to get settings directory: {}", e))?;
+    Ok(settings_dir.join("tor").join(profile_name))
+}
+
+/// Runs `tor` as a helper serving SOCKS5 on loopback, ready once it has
+/// bootstrapped. Its control port uses cookie authentication.
+pub fn helper_spec(tor: &TorSetup, profile_name: &str) -> Result<HelperSpec, String> {
+    let data_dir = data_dir(profile_name)?;
+    std::fs::create_dir_all(&data_dir)
+        .map_err(|e| format!("Failed to create Tor data directory: {}", e))?;
+    // A configured control port belongs to an existing Tor, not this one
+    let control_port =
+        helper::free_port().map_err(|e| format!("Failed to pick a local port: {}", e))?;
+
+    let args = vec![
+        // Keep the system torrc, which may claim the default ports, out of it
+        "--ignore-missing-torrc".to_string(),
+        "-f".to_string(),
+        data_dir.join("torrc").to_string_lossy().to_string(),
+        "--SocksPort".to_string(),
+        format!("127.0.0.1:{}", PORT_PLACEHOLDER),
+        "--ControlPort".to_string(),
+        format!("127.0.0.1:{}", control_port),
+        "--CookieAuthentication".to_string(),
+        "1".to_string(),
+        "--DataDirectory".to_string(),
+        data_dir.to_string_lossy().to_string(),
+        // Tor exits by itself if the app goes away without stopping it
+        "--__OwningControllerProcess".to_string(),
+        std::process::id().to_string(),
+        "--Log".to_string(),
+        "notice stdout".to_string(),
+    ];
+
+    Ok(HelperSpec {
+        label: format!("Tor for profile {}", profile_name),
+        program: tor.tor_path.clone().unwrap_or_else(|| "tor".to_string()),
+        args,
+        readiness: Readiness::Output(Regex::new(r"Bootstrapped 100%").map_err(|e| e.to_string())?),
+        ready_timeout: READY_TIMEOUT,
+        scheme: "socks5",
+        host: "127.0.0.1".to_string(),
+        port: tor.port,
+        control_port: Some(control_port),
+    })
+}
+
+/// Asks the Tor behind a control port for new circuits, so that new
+/// connections leave through a different exit.
+pub async fn new_identity(host: &str, port: u16, password: Option<&str>) -> Result<(), String> {
+    tokio::time::timeout(CONTROL_TIMEOUT, async {
+        let stream = TcpStream::connect((host, port)).await.map_err(|e| {
+            format!(
+                "Failed to connect to Tor control port {}:{}: {}",
+                host, port, e
+            )
+        })?;
+        let mut control = BufReader::new(stream);
+
+        let info = command(&mut control, "PROTOCOLINFO 1").await?;
+        let authenticate = authenticate_command(&info, password).await?;
+        command(&mut control, &authenticate).await?;
+        command(&mut control, "SIGNAL NEWNYM").await?;
+        let _ = command(&mut control, "QUIT").await;
+        Ok(())
+    })
+    .await
+    .map_err(|_| format!("Timed out talking to Tor control port {}:{}", host, port))?
+}
+
+/// Sends a control command and returns the lines of a successful reply.
+async fn command(control: &mut BufReader<TcpStream>, line: &str) -> Result<Vec<String>, String> {
+    // Only the keyword goes into errors, the rest may be a password
+    let keyword = line.split_whitespace().next().unwrap_or_default();
+    control
+        .get_mut()
+        .write_all(format!("{}\r\n", line).as_bytes())
+        .await
+        .map_err(|e| format!("Failed to send {} to Tor: {}", keyword, e))?;
+
+    let mut reply = Vec::new();
+    loop {
+        let mut raw = String::new();
+        if control
+            .read_line(&mut raw)
+            .await
+            .map_err(|e| format!("Failed to read Tor's reply to {}: {}", keyword, e))?
+            == 0
+        {
+            return Err(format!(
+                "Tor closed the control connection after {}",
+                keyword
+            ));
+        }
+        let raw = raw.trim_end();
+        // Checking the separator byte first keeps the slices on ASCII boundaries
+        let separator = raw.as_bytes().get(3).copied();
+        if !matches!(separator, Some(b' ' | b'-' | b'+')) {
+            return Err(format!("Unexpected reply from Tor: {}", raw));
+        }
+        let (status, text) = (&raw[..3], &raw[4..]);
+        if status != "250" {
+            return Err(format!("Tor refused {}: {} {}", keyword, status, text));
+        }
+        reply.push(text.to_string());
+        match separator {
+            Some(b' ') => return Ok(reply),
+            // A data reply runs until a line holding a single dot
+            Some(b'+') => loop {
+                let mut data = String::new();
+                if control.read_line(&mut data).await.unwrap_or(0) == 0 || data.trim_end() == "." {
+                    break;
+                }
+            },
+            _ => {}
+        }
+    }
+}
+
+/// Picks an authentication the control port offers, from the methods
+/// listed in a `PROTOCOLINFO` reply.
+async fn authenticate_command(info: &[String], password: Option<&str>) -> Result<String, String> {
+    let auth = info
+        .iter()
+        .find_map(|line| line.strip_prefix("AUTH METHODS="))
+        .ok_or_else(|| "Tor did not list its authentication methods".to_string())?;
+    let (methods, rest) = auth.split_once(' ').unwrap_or((auth, ""));
+    let methods: Vec<&str> = methods.split(',').collect();
+
+    if methods.contains(&"NULL") {
+        return Ok("AUTHENTICATE".to_string());
+    }
+    if let (Some(password), true) = (password, methods.contains(&"HASHEDPASSWORD")) {
+        return Ok(format!("AUTHENTICATE {}", quote(password)));
+    }
+    if methods.contains(&"COOKIE") {
+        let path = rest
+            .strip_prefix("COOKIEFILE=")
+            .and_then(unquote)
+            .ok_or_else(|| "Tor did not name its cookie file".to_string())?;
+        let cookie = tokio::fs::read(&path)
+            .await
+            .map_err(|e| format!("Failed to read Tor cookie file {}: {}", path, e))?;
+        let hex: String = cookie.iter().map(|byte| format!("{:02x}", byte)).collect();
+        return Ok(format!("AUTHENTICATE {}", hex));
+    }
+    if methods.contains(&"HASHEDPASSWORD") {
+        return Err("Tor control port needs a password".to_string());
+    }
+    Err(format!(
+        "Tor control port offers no supported authentication ({})",
+        methods.join(", ")
+    ))
+}
+
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Reads a quoted string from the start of `value`.
+fn unquote(value: &str) -> Option<String> {
+    let mut chars = value.strip_prefix('"')?.chars();
+    let mut unquoted = String::new();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => return Some(unquoted),
+            '\\' => unquoted.push(chars.next()?),
+            c => unquoted.push(c),
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncReadExt;
+    use tokio::net::TcpListener;
+
+    /// Sends `line` to a stub control port answering with `reply`, returning
+    /// the parsed reply and what the stub received.
+    async fn stub_command(
+        line: &str,
+        reply: &'static str,
+    ) -> (Result<Vec<String>, String>, String) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let stub = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut received = Vec::new();
+            while !received.ends_with(b"\r\n") {
+                let mut byte = [0u8; 1];
+                if stream.read(&mut byte).await.unwrap() == 0 {
+                    break;
+                }
+                received.push(byte[0]);
+            }
+            stream.write_all(reply.as_bytes()).await.unwrap();
+            String::from_utf8(received).unwrap()
+        });
+
+        let mut control = BufReader::new(TcpStream::connect(addr).await.unwrap());
+        let result = command(&mut control, line).await;
+        (result, stub.await.unwrap())
+    }
+
+    #[test]
+    fn quotes_and_unquotes() {
+        assert_eq!(quote(r#"pa"ss\word"#), r#""pa\"ss\\word""#);
+        assert_eq!(
+            unquote(r#""/var/run/tor/control.authcookie" rest"#).as_deref(),
+            Some("/var/run/tor/control.authcookie")
+        );
+        assert_eq!(
+            unquote(&quote(r#"pa"ss\word"#)).as_deref(),
+            Some(r#"pa"ss\word"#)
+        );
+        assert_eq!(unquote("unquoted"), None);
+        assert_eq!(unquote(r#""unterminated"#), None);
+    }
+
+    #[tokio::test]
+    async fn authenticates_without_credentials() {
+        let info = [
+            "PROTOCOLINFO 1".to_string(),
+            "AUTH METHODS=NULL".to_string(),
+        ];
+        assert_eq!(
+            authenticate_command(&info, Some("secret")).await.unwrap(),
+            "AUTHENTICATE"
+        );
+    }
+
+    #[tokio::test]
+    async fn authenticates_with_password() {
+        let info = ["AUTH METHODS=HASHEDPASSWORD".to_string()];
+        assert_eq!(
+            authenticate_command(&info, Some("se\"cret")).await.unwrap(),
+            r#"AUTHENTICATE "se\"cret""#
+        );
+        assert!(authenticate_command(&info, None).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn authenticates_with_cookie() {
+        let path = std::env::temp_dir().join(format!(
+            "browser-proxy-launcher-tor-cookie-{}",
+            std::process::id()
+        ));
+        std::fs::write(&path, [0x00, 0x1f, 0xab, 0xff]).unwrap();
+
+        let info = [format!(
+            "AUTH METHODS=COOKIE,SAFECOOKIE,HASHEDPASSWORD COOKIEFILE={}",
+            quote(&path.to_string_lossy())
+        )];
+        let result = authenticate_command(&info, None).await;
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(result.unwrap(), "AUTHENTICATE 001fabff");
+    }
+
+    #[tokio::test]
+    async fn reads_multi_line_replies() {
+        let (reply, received) = stub_command(
+            "PROTOCOLINFO 1",
+            "250-PROTOCOLINFO 1\r\n250-AUTH METHODS=NULL\r\n250-VERSION Tor=\"0.4.8.9\"\r\n250 OK\r\n",
+        )
+        .await;
+        assert_eq!(received, "PROTOCOLINFO 1\r\n");
+        assert_eq!(
+            reply.unwrap(),
+            [
+                "PROTOCOLINFO 1",
+                "AUTH METHODS=NULL",
+                "VERSION Tor=\"0.4.8.9\"",
+                "OK",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn skips_data_replies() {
+        let (reply, _) = stub_command(
+            "GETINFO config-text",
+            "250+config-text=\r\nSocksPort 9050\r\nControlPort 9051\r\n.\r\n250 OK\r\n",
+        )
+        .await;
+        assert_eq!(reply.unwrap(), ["config-text=", "OK"]);
+    }
+
+    #[tokio::test]
+    async fn reports_refused_commands() {
+        let (reply, _) = stub_command(
+            "AUTHENTICATE \"wrong\"",
+            "515 Authentication failed: Password did not match HashedControlPassword value\r\n",
+        )
+        .await;
+        let error = reply.unwrap_err();
+        assert!(error.starts_with("Tor refused AUTHENTICATE: 515"));
+        assert!(!error.contains("wrong"));
+    }
+
+    #[tokio::test]
+    async fn rejects_malformed_replies() {
+        for reply in ["250é OK\r\n", "250\r\n", "25\r\n", "250xOK\r\n"] {
+            let (result, _) = stub_command("SIGNAL NEWNYM", reply).await;
+            assert!(
+                result.unwrap_err().starts_with("Unexpected reply from Tor"),
+                "{:?}",
+                reply
+            );
+        }
+    }
+}
@@ -0,0 +1,387 @@
+use crate::settings::SettingsManager;
+use chrono::{DateTime, Local, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tauri::Manager;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+const USAGE_FILE: &str = "usage.json";
+
+/// How often traffic of running sessions is added to the daily totals.
+const FLUSH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Daily totals older than this are dropped from the usage file.
+const RETENTION_DAYS: i64 = 400;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TrafficTotals {
+    /// Bytes sent to the upstream proxy.
+    pub bytes_up: u64,
+    /// Bytes received from the upstream proxy.
+    pub bytes_down: u64,
+    pub connections: u64,
+}
+
+impl TrafficTotals {
+    fn add(&mut self, other: TrafficTotals) {
+        self.bytes_up += other.bytes_up;
+        self.bytes_down += other.bytes_down;
+        self.connections += other.connections;
+    }
+
+    fn is_empty(&self) -> bool {
+        *self == TrafficTotals::default()
+    }
+}
+
+#[derive(Default)]
+struct Counters {
+    bytes_up: AtomicU64,
+    bytes_down: AtomicU64,
+    connections: AtomicU64,
+}
+
+impl Counters {
+    fn snapshot(&self) -> TrafficTotals {
+        TrafficTotals {
+            bytes_up: self.bytes_up.load(Ordering::Relaxed),
+            bytes_down: self.bytes_down.load(Ordering::Relaxed),
+            connections: self.connections.load(Ordering::Relaxed),
+        }
+    }
+
+    fn take(&self) -> TrafficTotals {
+        TrafficTotals {
+            bytes_up: self.bytes_up.swap(0, Ordering::Relaxed),
+            bytes_down: self.bytes_down.swap(0, Ordering::Relaxed),
+            connections: self.connections.swap(0, Ordering::Relaxed),
+        }
+    }
+}
+
+/// Counts the traffic a browser session sends through its local forwarder.
+///
+/// Traffic is billed to every proxy in `proxy_ids`, so a chain's usage also
+/// shows up on each of its hops. Whatever has not been flushed yet is added
+/// to the daily totals when the last connection using the meter closes.
+pub struct SessionMeter {
+    profile: String,
+    proxy_ids: Vec<String>,
+    started_at: DateTime<Utc>,
+    total: Counters,
+    /// Traffic not yet added to the daily totals.
+    pending: Counters,
+    tracker: UsageTracker,
+}
+
+impl SessionMeter {
+    fn record_connection(&self) {
+        self.total.connections.fetch_add(1, Ordering::Relaxed);
+        self.pending.connections.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn record_up(&self, bytes: usize) {
+        self.total
+            .bytes_up
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+        self.pending
+            .bytes_up
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    fn record_down(&self, bytes: usize) {
+        self.total
+            .bytes_down
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+        self.pending
+            .bytes_down
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+}
+
+impl Drop for SessionMeter {
+    fn drop(&mut self) {
+        let pending = self.pending.take();
+        if !pending.is_empty() {
+            let mut state = self.tracker.state.lock().unwrap();
+            state.add(&self.proxy_ids, pending);
+            state.save();
+        }
+    }
+}
+
+/// Bytes of a response kept by `Metered`, enough for an HTTP status line.
+const RESPONSE_START_SIZE: usize = 16;
+
+/// Wraps a connection to an upstream proxy, counting the bytes sent in both
+/// directions and adding them to the session's meter, if any.
+pub struct Metered<S> {
+    inner: S,
+    meter: Option<Arc<SessionMeter>>,
+    totals: TrafficTotals,
+    response_start: Vec<u8>,
+}
+
+impl<S> Metered<S> {
+    /// Wraps a new connection, counting it for the session.
+    pub fn new(inner: S, meter: Option<Arc<SessionMeter>>) -> Self {
+        if let Some(meter) = &meter {
+            meter.record_connection();
+        }
+        Self {
+            inner,
+            meter,
+            totals: TrafficTotals {
+                connections: 1,
+                ..TrafficTotals::default()
+            },
+            response_start: Vec::new(),
+        }
+    }
+
+    /// Traffic of this connection so far.
+    pub fn totals(&self) -> TrafficTotals {
+        self.totals
+    }
+
+    /// The first bytes received over this connection.
+    pub fn response_start(&self) -> &[u8] {
+        &self.response_start
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for Metered<S> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let before = buf.filled().len();
+        let result = Pin::new(&mut self.inner).poll_read(cx, buf);
+        if let Poll::Ready(Ok(())) = result {
+            let read = &buf.filled()[before..];
+            let missing = RESPONSE_START_SIZE.saturating_sub(self.response_start.len());
+            let start = &read[..missing.min(read.len())];
+            self.response_start.extend_from_slice(start);
+            self.totals.bytes_down += read.len() as u64;
+            if let Some(meter) = &self.meter {
+                meter.record_down(read.len());
+            }
+        }
+        result
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for Metered<S> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let result = Pin::new(&mut self.inner).poll_write(cx, buf);
+        if let Poll::Ready(Ok(written)) = result {
+            self.totals.bytes_up += written as u64;
+            if let Some(meter) = &self.meter {
+                meter.record_up(written);
+            }
+        }
+        result
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct DailyUsage {
+    pub date: NaiveDate,
+    pub proxy_id: String,
+    #[serde(flatten)]
+    pub totals: TrafficTotals,
+}
+
+/// Traffic of a browser session that is still running.
+#[derive(Debug, Serialize, Clone)]
+pub struct SessionUsage {
+    pub profile: String,
+    /// The proxy the session was launched with.
+    pub proxy_id: String,
+    pub started_at: DateTime<Utc>,
+    #[serde(flatten)]
+    pub totals: TrafficTotals,
+}
+
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct ProxyUsage {
+    pub daily: Vec<DailyUsage>,
+    pub sessions: Vec<SessionUsage>,
+}
+
+/// Daily totals per proxy, as stored in the usage file.
+type DailyTotals = BTreeMap<NaiveDate, BTreeMap<String, TrafficTotals>>;
+
+#[derive(Default)]
+struct UsageState {
+    /// Loaded from the usage file on first use.
+    daily: Option<DailyTotals>,
+    sessions: Vec<Weak<SessionMeter>>,
+}
+
+impl UsageState {
+    fn daily(&mut self) -> &mut DailyTotals {
+        self.daily.get_or_insert_with(|| match load() {
+            Ok(daily) => daily,
+            Err(e) => {
+                eprintln!("Failed to load traffic usage: {}", e);
+                DailyTotals::new()
+            }
+        })
+    }
+
+    fn add(&mut self, proxy_ids: &[String], totals: TrafficTotals) {
+        let today = self.daily().entry(Local::now().date_naive()).or_default();
+        for proxy_id in proxy_ids {
+            today.entry(proxy_id.clone()).or_default().add(totals);
+        }
+    }
+
+    fn save(&mut self) {
+        let oldest = Local::now().date_naive() - chrono::Duration::days(RETENTION_DAYS);
+        let daily = self.daily();
+        daily.retain(|date, _| *date >= oldest);
+
+        let result = usage_path().and_then(|path| {
+            let json = serde_json::to_string_pretty(daily)?;
+            std::fs::write(path, json)?;
+            Ok(())
+        });
+        if let Err(e) = result {
+            eprintln!("Failed to save traffic usage: {}", e);
+        }
+    }
+}
+
+/// Meters browser sessions and keeps daily traffic totals per proxy in
+/// `usage.json` next to the settings.
+#[derive(Clone, Default)]
+pub struct UsageTracker {
+    state: Arc<Mutex<UsageState>>,
+}
+
+impl UsageTracker {
+    /// Starts metering a session that sends its traffic through `proxy_ids`.
+    pub fn start_session(&self, profile: &str, proxy_ids: Vec<String>) -> Arc<SessionMeter> {
+        let meter = Arc::new(SessionMeter {
+            profile: profile.to_string(),
+            proxy_ids,
+            started_at: Utc::now(),
+            total: Counters::default(),
+            pending: Counters::default(),
+            tracker: self.clone(),
+        });
+        self.state
+            .lock()
+            .unwrap()
+            .sessions
+            .push(Arc::downgrade(&meter));
+        meter
+    }
+
+    /// Writes the traffic of running sessions to the usage file.
+    pub fn flush(&self) {
+        let meters = self.sessions();
+        let mut state = self.state.lock().unwrap();
+
+        let mut flushed = false;
+        for meter in &meters {
+            let pending = meter.pending.take();
+            if !pending.is_empty() {
+                state.add(&meter.proxy_ids, pending);
+                flushed = true;
+            }
+        }
+        if flushed {
+            state.save();
+        }
+    }
+
+    /// Daily totals and running sessions, optionally only for one proxy.
+    pub fn usage(&self, proxy_id: Option<&str>) -> ProxyUsage {
+        self.flush();
+
+        let meters = self.sessions();
+        let sessions = meters
+            .iter()
+            .filter(|meter| proxy_id.is_none_or(|id| meter.proxy_ids.iter().any(|p| p == id)))
+            .map(|meter| SessionUsage {
+                profile: meter.profile.clone(),
+                proxy_id: meter.proxy_ids.first().cloned().unwrap_or_default(),
+                started_at: meter.started_at,
+                totals: meter.total.snapshot(),
+            })
+            .collect();
+
+        let mut state = self.state.lock().unwrap();
+        let daily = state
+            .daily()
+            .iter()
+            .flat_map(|(date, proxies)| {
+                proxies
+                    .iter()
+                    .filter(|(id, _)| proxy_id.is_none_or(|proxy_id| proxy_id == id.as_str()))
+                    .map(|(id, totals)| DailyUsage {
+                        date: *date,
+                        proxy_id: id.clone(),
+                        totals: *totals,
+                    })
+            })
+            .collect();
+
+        ProxyUsage { daily, sessions }
+    }
+
+    /// The meters of running sessions. They must be dropped after the state
+    /// lock is released, since dropping the last reference to a meter locks
+    /// it to record the remaining traffic.
+    fn sessions(&self) -> Vec<Arc<SessionMeter>> {
+        let mut state = self.state.lock().unwrap();
+        state.sessions.retain(|meter| meter.strong_count() > 0);
+        state.sessions.iter().filter_map(Weak::upgrade).collect()
+    }
+}
+
+fn usage_path() -> Result<PathBuf, Box<dyn std::error::Error>> {
+    Ok(SettingsManager::new()?.get_settings_dir()?.join(USAGE_FILE))
+}
+
+fn load() -> Result<DailyTotals, Box<dyn std::error::Error>> {
+    let path = usage_path()?;
+    if !path.exists() {
+        return Ok(DailyTotals::new());
+    }
+
+    let contents = std::fs::read_to_string(path)?;
+    Ok(serde_json::from_str(&contents)?)
+}
+
+/// Periodically writes the traffic of running sessions to the usage file.
+pub fn spawn(app_handle: tauri::AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            tokio::time::sleep(FLUSH_INTERVAL).await;
+            app_handle.state::<UsageTracker>().flush();
+        }
+    });
+}
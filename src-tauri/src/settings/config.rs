@@ -1,4 +1,6 @@
 use crate::browser::Browser;
+use crate::proxy::auth::Credentials;
+use crate::proxy::bypass;
 use serde::{Deserialize, Serialize};
 
 fn default_browser() -> String {
@@ -9,20 +11,594 @@ fn default_launch_url() -> String {
     "https://ipinfo.io".to_string()
 }
 
+fn default_health_check_url() -> String {
+    "https://api.ipify.org?format=json".to_string()
+}
+
+fn default_health_check_interval_secs() -> u64 {
+    300
+}
+
+/// The longest background checks may be apart while a proxy fails closed,
+/// since the kill switch only reacts to their results.
+const FAIL_CLOSED_CHECK_INTERVAL_SECS: u64 = 15;
+
+fn default_health_history_size() -> usize {
+    20
+}
+
+fn default_connection_log_size() -> usize {
+    1000
+}
+
+fn default_helper_host() -> String {
+    "127.0.0.1".to_string()
+}
+
+fn default_ready_timeout_secs() -> u64 {
+    30
+}
+
+fn default_tor_socks_port() -> u16 {
+    9050
+}
+
 fn default_theme() -> String {
     "system".to_string()
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct ProxyConfig {
-    pub id: String,
-    pub name: String,
-    pub proxy_type: String, // "http", "socks5", "pac"
+/// A proxy server address with optional credentials. SOCKS4 proxies only
+/// take a user ID, which is stored as the username.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct ProxyServer {
     pub host: String,
     pub port: u16,
     pub username: Option<String>,
     pub password: Option<String>,
-    pub url: Option<String>, // PAC URL
+}
+
+impl ProxyServer {
+    pub fn credentials(&self) -> Option<Credentials> {
+        self.username.as_ref().map(|username| Credentials {
+            username: username.clone(),
+            password: self.password.clone().unwrap_or_default(),
+        })
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum KnownHostsPolicy {
+    /// Only connect to hosts already in `known_hosts`.
+    #[default]
+    Strict,
+    /// Record the key of hosts seen for the first time, reject changed keys.
+    AcceptNew,
+    /// Skip host key verification.
+    Off,
+}
+
+/// An SSH server the app opens a dynamic port forward to. Authentication is
+/// by key, from `identity_file` or the SSH agent, since the tunnel runs
+/// without a terminal to prompt on.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct SshServer {
+    pub host: String,
+    pub port: u16,
+    pub username: Option<String>,
+    pub identity_file: Option<String>,
+    #[serde(default)]
+    pub known_hosts: KnownHostsPolicy,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReadinessProbe {
+    /// The endpoint accepts connections.
+    #[default]
+    Port,
+    /// A line the helper prints matches `ready_pattern`.
+    Output,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum EndpointType {
+    Http,
+    #[default]
+    Socks5,
+}
+
+impl EndpointType {
+    pub fn scheme(&self) -> &'static str {
+        match self {
+            EndpointType::Http => "http",
+            EndpointType::Socks5 => "socks5",
+        }
+    }
+}
+
+/// A program the app runs for each browser session that serves a proxy on
+/// a local port, such as a VPN client or a cloud tunnel.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct HelperCommand {
+    pub command: String,
+    /// `{port}` is replaced by the endpoint port.
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub readiness: ReadinessProbe,
+    /// Regex for the `Output` probe, matched against stdout and stderr.
+    #[serde(default)]
+    pub ready_pattern: Option<String>,
+    #[serde(default = "default_ready_timeout_secs")]
+    pub ready_timeout_secs: u64,
+    #[serde(default)]
+    pub endpoint_type: EndpointType,
+    #[serde(default = "default_helper_host")]
+    pub host: String,
+    /// 0 picks a free port, which the helper learns through `{port}`.
+    pub port: u16,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TorMode {
+    /// A Tor already running, such as the system service or Tor Browser.
+    #[default]
+    Existing,
+    /// A `tor` the app starts for each browser session, with its own data
+    /// directory per profile.
+    Launch,
+}
+
+/// A Tor SOCKS port, and the control port used to request new circuits.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct TorSetup {
+    #[serde(default)]
+    pub tor_mode: TorMode,
+    /// Where an existing Tor listens; a launched one uses loopback.
+    #[serde(default = "default_helper_host")]
+    pub host: String,
+    /// SOCKS port. 0 lets a launched Tor pick a free one.
+    #[serde(default = "default_tor_socks_port")]
+    pub port: u16,
+    /// Control port of an existing Tor. A launched Tor gets a free one.
+    #[serde(default)]
+    pub control_port: Option<u16>,
+    /// For the control port of an existing Tor with `HashedControlPassword`.
+    /// Cookie authentication, which a launched Tor uses, needs no settings.
+    #[serde(default)]
+    pub control_password: Option<String>,
+    /// The `tor` binary to launch, looked up in PATH when not set.
+    #[serde(default)]
+    pub tor_path: Option<String>,
+}
+
+impl TorSetup {
+    pub fn launched(&self) -> bool {
+        self.tor_mode == TorMode::Launch
+    }
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+#[serde(tag = "proxy_type", rename_all = "lowercase")]
+pub enum ProxyKind {
+    Http(ProxyServer),
+    Https(ProxyServer),
+    Socks4(ProxyServer),
+    Socks4a(ProxyServer),
+    Socks5(ProxyServer),
+    Pac {
+        url: String,
+    },
+    /// Routes through other proxies in order, referenced by ID.
+    Chain {
+        hops: Vec<String>,
+    },
+    /// Routes each host by the app's PAC rules, served from a local PAC
+    /// endpoint.
+    Rules,
+    /// A SOCKS5 proxy on loopback provided by `ssh -D`, started for each
+    /// browser session.
+    Ssh(SshServer),
+    /// A proxy served by a helper program started for each browser session.
+    Helper(HelperCommand),
+    /// The SOCKS5 port of an existing Tor, or of one started for each
+    /// browser session.
+    Tor(TorSetup),
+}
+
+impl ProxyKind {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            ProxyKind::Http(_) => "http",
+            ProxyKind::Https(_) => "https",
+            ProxyKind::Socks4(_) => "socks4",
+            ProxyKind::Socks4a(_) => "socks4a",
+            ProxyKind::Socks5(_) => "socks5",
+            ProxyKind::Pac { .. } => "pac",
+            ProxyKind::Chain { .. } => "chain",
+            ProxyKind::Rules => "rules",
+            ProxyKind::Ssh(_) => "ssh",
+            ProxyKind::Helper(_) => "helper",
+            ProxyKind::Tor(_) => "tor",
+        }
+    }
+
+    /// Whether the proxy is served by a process the app starts for each
+    /// browser session.
+    pub fn runs_helper(&self) -> bool {
+        match self {
+            ProxyKind::Ssh(_) | ProxyKind::Helper(_) => true,
+            ProxyKind::Tor(tor) => tor.launched(),
+            _ => false,
+        }
+    }
+
+    /// Whether the browser reaches the proxy over SOCKS5 and can leave
+    /// hostname lookups to it.
+    pub fn resolves_remotely(&self) -> bool {
+        match self {
+            ProxyKind::Socks5(_) | ProxyKind::Ssh(_) | ProxyKind::Tor(_) => true,
+            ProxyKind::Helper(helper) => helper.endpoint_type == EndpointType::Socks5,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(try_from = "RawProxyConfig")]
+pub struct ProxyConfig {
+    pub id: String,
+    pub name: String,
+    #[serde(flatten)]
+    pub kind: ProxyKind,
+    /// Hosts the browser connects to directly, in `--proxy-bypass-list` syntax.
+    #[serde(default)]
+    pub bypass: Vec<String>,
+    /// Leave every hostname lookup to the proxy. Only proxies reached over
+    /// SOCKS5 resolve hostnames for the browser.
+    #[serde(default)]
+    pub remote_dns: bool,
+    /// Block the session's traffic while the proxy is down and never let it
+    /// go direct, instead of relying on the browser's fallbacks. Not
+    /// available for proxies served by a helper process.
+    #[serde(default)]
+    pub fail_closed: bool,
+}
+
+impl ProxyConfig {
+    /// Checks the proxy on its own and, for chains, against `proxies`.
+    pub fn validate(&self, proxies: &[ProxyConfig]) -> Result<(), String> {
+        if let ProxyKind::Chain { .. } = self.kind {
+            self.chain_hops(proxies)?;
+        }
+        // Helper traffic does not pass the forwarder enforcing the kill switch
+        if self.fail_closed && self.kind.runs_helper() {
+            return Err(format!(
+                "Proxy '{}' cannot fail closed; the kill switch does not cover SSH tunnels, helper processes or a launched Tor",
+                self.name
+            ));
+        }
+        if self.remote_dns && !self.kind.resolves_remotely() {
+            return Err(format!(
+                "Proxy '{}' can only use remote DNS with a SOCKS5 proxy, SSH tunnel, Tor or SOCKS5 helper",
+                self.name
+            ));
+        }
+        if self.bypass.is_empty() {
+            return Ok(());
+        }
+        if matches!(self.kind, ProxyKind::Pac { .. } | ProxyKind::Rules) {
+            return Err(format!(
+                "PAC proxy '{}' cannot have bypass rules; the script decides which hosts go direct",
+                self.name
+            ));
+        }
+        bypass::validate(&self.bypass).map_err(|e| format!("Proxy '{}': {}", self.name, e))?;
+        // Host resolver rules match hostname patterns and cannot express
+        // "no dot", so dotless hosts would fail to resolve instead of going direct
+        if self.remote_dns
+            && self
+                .bypass
+                .iter()
+                .any(|rule| bypass::BypassRule::parse(rule) == Ok(bypass::BypassRule::Local))
+        {
+            return Err(format!(
+                "Proxy '{}' cannot bypass <local> with remote DNS; list the local hostnames instead",
+                self.name
+            ));
+        }
+        Ok(())
+    }
+
+    /// Looks up the proxies a chain routes through, in order. Every hop
+    /// must be a single proxy server; PAC scripts, rules, SSH tunnels,
+    /// helpers, launched Tor and nested chains are rejected.
+    pub fn chain_hops<'a>(
+        &self,
+        proxies: &'a [ProxyConfig],
+    ) -> Result<Vec<&'a ProxyConfig>, String> {
+        let ProxyKind::Chain { hops } = &self.kind else {
+            return Err(format!("Proxy '{}' is not a chain", self.name));
+        };
+
+        hops.iter()
+            .map(|hop_id| {
+                let hop = proxies
+                    .iter()
+                    .find(|p| p.id == *hop_id)
+                    .ok_or_else(|| format!("Chain '{}' references a missing proxy", self.name))?;
+                let single_server = !hop.kind.runs_helper()
+                    && !matches!(
+                        hop.kind,
+                        ProxyKind::Pac { .. } | ProxyKind::Chain { .. } | ProxyKind::Rules
+                    );
+                if !single_server {
+                    return Err(format!(
+                        "Chain '{}' cannot route through {} proxy '{}'",
+                        self.name,
+                        hop.kind.type_name().to_uppercase(),
+                        hop.name
+                    ));
+                }
+                Ok(hop)
+            })
+            .collect()
+    }
+}
+
+/// The flat proxy layout written by earlier versions, where every proxy
+/// carried host, port and url regardless of its type. The frontend still
+/// sends this shape, so it is the format all proxies are read through.
+#[derive(Deserialize)]
+struct RawProxyConfig {
+    id: String,
+    name: String,
+    proxy_type: String,
+    #[serde(default)]
+    host: Option<String>,
+    #[serde(default)]
+    port: Option<u16>,
+    #[serde(default)]
+    username: Option<String>,
+    #[serde(default)]
+    password: Option<String>,
+    #[serde(default)]
+    url: Option<String>,
+    #[serde(default)]
+    identity_file: Option<String>,
+    #[serde(default)]
+    known_hosts: KnownHostsPolicy,
+    #[serde(default)]
+    command: Option<String>,
+    #[serde(default)]
+    args: Vec<String>,
+    #[serde(default)]
+    readiness: ReadinessProbe,
+    #[serde(default)]
+    ready_pattern: Option<String>,
+    #[serde(default)]
+    ready_timeout_secs: Option<u64>,
+    #[serde(default)]
+    endpoint_type: EndpointType,
+    #[serde(default)]
+    tor_mode: TorMode,
+    #[serde(default)]
+    control_port: Option<u16>,
+    #[serde(default)]
+    control_password: Option<String>,
+    #[serde(default)]
+    tor_path: Option<String>,
+    #[serde(default)]
+    bypass: Vec<String>,
+    #[serde(default)]
+    remote_dns: bool,
+    #[serde(default)]
+    fail_closed: bool,
+    #[serde(default)]
+    hops: Vec<String>,
+}
+
+impl RawProxyConfig {
+    fn server(&self) -> Result<ProxyServer, String> {
+        let host =
+            non_empty(&self.host).ok_or_else(|| format!("Proxy '{}' has no host", self.name))?;
+        let port = self
+            .port
+            .filter(|port| *port != 0)
+            .ok_or_else(|| format!("Proxy '{}' has no port", self.name))?;
+
+        Ok(ProxyServer {
+            host,
+            port,
+            username: non_empty(&self.username),
+            // A password without a username is meaningless
+            password: non_empty(&self.username).and(self.password.clone()),
+        })
+    }
+
+    fn socks4_server(&self) -> Result<ProxyServer, String> {
+        let server = self.server()?;
+        if non_empty(&server.password).is_some() {
+            return Err(format!(
+                "SOCKS4 proxy '{}' supports a user ID but not a password",
+                self.name
+            ));
+        }
+        Ok(ProxyServer {
+            password: None,
+            ..server
+        })
+    }
+
+    fn ssh_server(&self) -> Result<SshServer, String> {
+        let server = self.server()?;
+        if non_empty(&server.password).is_some() {
+            return Err(format!(
+                "SSH proxy '{}' authenticates with a key or the SSH agent, not a password",
+                self.name
+            ));
+        }
+        // ssh would read a leading dash as an option
+        if server.host.starts_with('-') {
+            return Err(format!("SSH proxy '{}' has an invalid host", self.name));
+        }
+        Ok(SshServer {
+            host: server.host,
+            port: server.port,
+            username: server.username,
+            identity_file: non_empty(&self.identity_file),
+            known_hosts: self.known_hosts,
+        })
+    }
+
+    fn helper_command(&self) -> Result<HelperCommand, String> {
+        let command = non_empty(&self.command)
+            .ok_or_else(|| format!("Helper proxy '{}' has no command", self.name))?;
+        let port = self.port.unwrap_or(0);
+        if port == 0 && !self.args.iter().any(|arg| arg.contains("{port}")) {
+            return Err(format!(
+                "Helper proxy '{}' needs a port, or {{port}} in its arguments to be given one",
+                self.name
+            ));
+        }
+
+        let ready_pattern = non_empty(&self.ready_pattern);
+        if self.readiness == ReadinessProbe::Output {
+            let pattern = ready_pattern
+                .as_deref()
+                .ok_or_else(|| format!("Helper proxy '{}' has no readiness pattern", self.name))?;
+            regex::Regex::new(pattern).map_err(|e| {
+                format!(
+                    "Helper proxy '{}' has an invalid readiness pattern: {}",
+                    self.name, e
+                )
+            })?;
+        }
+
+        Ok(HelperCommand {
+            command,
+            args: self.args.clone(),
+            readiness: self.readiness,
+            ready_pattern,
+            ready_timeout_secs: self
+                .ready_timeout_secs
+                .filter(|secs| *secs > 0)
+                .unwrap_or_else(default_ready_timeout_secs),
+            endpoint_type: self.endpoint_type,
+            host: non_empty(&self.host).unwrap_or_else(default_helper_host),
+            port,
+        })
+    }
+
+    fn tor_setup(&self) -> Result<TorSetup, String> {
+        let launched = self.tor_mode == TorMode::Launch;
+        let port = match self.port {
+            Some(port) => port,
+            None if launched => 0,
+            None => default_tor_socks_port(),
+        };
+        if port == 0 && !launched {
+            return Err(format!("Tor proxy '{}' has no SOCKS port", self.name));
+        }
+
+        Ok(TorSetup {
+            tor_mode: self.tor_mode,
+            host: non_empty(&self.host)
+                .filter(|_| !launched)
+                .unwrap_or_else(default_helper_host),
+            port,
+            // A launched Tor gets a free control port with cookie authentication
+            control_port: self.control_port.filter(|port| *port != 0 && !launched),
+            control_password: non_empty(&self.control_password).filter(|_| !launched),
+            tor_path: non_empty(&self.tor_path),
+        })
+    }
+}
+
+impl TryFrom<RawProxyConfig> for ProxyConfig {
+    type Error = String;
+
+    fn try_from(raw: RawProxyConfig) -> Result<Self, Self::Error> {
+        let kind = match raw.proxy_type.as_str() {
+            "http" => ProxyKind::Http(raw.server()?),
+            "https" => ProxyKind::Https(raw.server()?),
+            "socks4" => ProxyKind::Socks4(raw.socks4_server()?),
+            "socks4a" => ProxyKind::Socks4a(raw.socks4_server()?),
+            "socks5" => ProxyKind::Socks5(raw.server()?),
+            "pac" => ProxyKind::Pac {
+                url: non_empty(&raw.url)
+                    .ok_or_else(|| format!("PAC proxy '{}' has no URL", raw.name))?,
+            },
+            "chain" if raw.hops.is_empty() => {
+                return Err(format!("Chain '{}' has no hops", raw.name))
+            }
+            "chain" => ProxyKind::Chain {
+                hops: raw.hops.clone(),
+            },
+            "rules" => ProxyKind::Rules,
+            "ssh" => ProxyKind::Ssh(raw.ssh_server()?),
+            "helper" => ProxyKind::Helper(raw.helper_command()?),
+            "tor" => ProxyKind::Tor(raw.tor_setup()?),
+            other => {
+                return Err(format!(
+                    "Proxy '{}' has unsupported type '{}'",
+                    raw.name, other
+                ))
+            }
+        };
+
+        Ok(ProxyConfig {
+            id: raw.id,
+            name: raw.name,
+            kind,
+            bypass: raw.bypass,
+            remote_dns: raw.remote_dns,
+            fail_closed: raw.fail_closed,
+        })
+    }
+}
+
+fn non_empty(value: &Option<String>) -> Option<String> {
+    value
+        .as_deref()
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(str::to_string)
+}
+
+/// Routes hosts matching `pattern` through a proxy, or directly when
+/// `proxy_id` is not set. Patterns are domain globs such as
+/// `*.example.com` or CIDR ranges such as `10.0.0.0/8`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PacRule {
+    pub pattern: String,
+    #[serde(default)]
+    pub proxy_id: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum GroupStrategy {
+    RoundRobin,
+    Random,
+    /// The member with the fastest latest health check.
+    LeastLatency,
+    /// The first member in order that is not down.
+    Failover,
+}
+
+/// A pool of equivalent proxies a site can use instead of a single proxy.
+/// The member is chosen every time the site is launched.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ProxyGroup {
+    pub id: String,
+    pub name: String,
+    pub strategy: GroupStrategy,
+    /// Member proxy IDs, in order.
+    pub members: Vec<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -31,7 +607,7 @@ pub struct SiteConfig {
     pub name: String,
     pub url: String,
     pub browser_id: String,
-    pub proxy_id: Option<String>,
+    pub proxy_id: Option<String>, // a proxy or a proxy group
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -47,10 +623,33 @@ pub struct AppSettings {
     #[serde(default)]
     pub ignore_cert_errors: bool,
     #[serde(default)]
+    pub preflight_blocks_launch: bool,
+    #[serde(default = "default_health_check_url")]
+    pub health_check_url: String,
+    #[serde(default = "default_health_check_interval_secs")]
+    pub health_check_interval_secs: u64, // 0 disables background checks; capped for fail-closed proxies
+    #[serde(default = "default_health_history_size")]
+    pub health_history_size: usize,
+    /// Local MaxMind DB files (e.g. GeoLite2-City and GeoLite2-ASN) used to
+    /// locate exit IPs.
+    #[serde(default)]
+    pub geoip_databases: Vec<String>,
+    /// Requests kept in memory by the connection log.
+    #[serde(default = "default_connection_log_size")]
+    pub connection_log_size: usize,
+    /// Also append the connection log to a rotating file.
+    #[serde(default)]
+    pub connection_log_to_file: bool,
+    #[serde(default)]
     pub browsers: Vec<Browser>,
     #[serde(default)]
     pub proxies: Vec<ProxyConfig>,
     #[serde(default)]
+    pub proxy_groups: Vec<ProxyGroup>,
+    /// Rules for proxies of the `rules` type, first match wins.
+    #[serde(default)]
+    pub pac_rules: Vec<PacRule>,
+    #[serde(default)]
     pub sites: Vec<SiteConfig>,
 }
 
@@ -62,9 +661,49 @@ impl Default for AppSettings {
             theme: "system".to_string(),
             launch_on_startup: false,
             ignore_cert_errors: false,
+            preflight_blocks_launch: false,
+            health_check_url: default_health_check_url(),
+            health_check_interval_secs: default_health_check_interval_secs(),
+            health_history_size: default_health_history_size(),
+            geoip_databases: Vec::new(),
+            connection_log_size: default_connection_log_size(),
+            connection_log_to_file: false,
             browsers: Vec::new(),
             proxies: Vec::new(),
+            proxy_groups: Vec::new(),
+            pac_rules: Vec::new(),
             sites: Vec::new(),
         }
     }
 }
+
+impl AppSettings {
+    /// Seconds between background health checks, or `None` when they are
+    /// disabled. Fail-closed proxies shorten the interval and keep the checks
+    /// running.
+    pub fn health_check_interval(&self) -> Option<u64> {
+        let fail_closed = self.proxies.iter().any(|proxy| proxy.fail_closed);
+        match self.health_check_interval_secs {
+            0 if !fail_closed => None,
+            _ if fail_closed => Some(
+                self.health_check_interval_secs
+                    .clamp(1, FAIL_CLOSED_CHECK_INTERVAL_SECS),
+            ),
+            interval => Some(interval),
+        }
+    }
+
+    /// Background checks cannot be disabled while a proxy fails closed.
+    pub fn validate_health_checks(&self) -> Result<(), String> {
+        if self.health_check_interval_secs != 0 {
+            return Ok(());
+        }
+        match self.proxies.iter().find(|proxy| proxy.fail_closed) {
+            Some(proxy) => Err(format!(
+                "Background health checks cannot be disabled while proxy '{}' fails closed",
+                proxy.name
+            )),
+            None => Ok(()),
+        }
+    }
+}
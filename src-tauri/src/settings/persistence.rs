@@ -1,5 +1,6 @@
-use super::AppSettings;
-use serde_json;
+use super::{AppSettings, ProxyConfig};
+use serde::Deserialize;
+use serde_json::{self, Value};
 use std::fs;
 use std::path::PathBuf;
 
@@ -28,7 +29,23 @@ impl SettingsManager {
         }
 
         let contents = fs::read_to_string(&self.config_path)?;
-        let settings: AppSettings = serde_json::from_str(&contents)?;
+        let value: Value = serde_json::from_str(&contents)?;
+        // Name the offending proxy rather than failing with a bare serde error;
+        // skipping it would let the next save delete it for good
+        if let Some(proxies) = value.get("proxies").and_then(Value::as_array) {
+            for proxy in proxies {
+                if let Err(e) = ProxyConfig::deserialize(proxy) {
+                    return Err(format!(
+                        "Proxy {} in {} could not be loaded: {}",
+                        proxy.get("id").unwrap_or(&Value::Null),
+                        self.config_path.display(),
+                        e
+                    )
+                    .into());
+                }
+            }
+        }
+        let settings: AppSettings = serde_json::from_value(value)?;
         Ok(settings)
     }
 
@@ -0,0 +1,12 @@
+"use client";
+
+import { useRouter } from "next/navigation";
+import { ConnectionLog } from "@/components/ConnectionLog";
+import { useAppData } from "@/hooks/useAppData";
+
+export default function Connections() {
+  const router = useRouter();
+  const { proxies } = useAppData();
+
+  return <ConnectionLog proxies={proxies} onBack={() => router.push("/")} />;
+}
@@ -8,6 +8,7 @@ import { ProxyList } from "@/components/ProxyList";
 import { SiteDialog } from "@/components/SiteDialog";
 import { ProxyDialog } from "@/components/ProxyDialog";
 import { ConfirmDialog } from "@/components/ConfirmDialog";
+import { KillSwitchBanner } from "@/components/KillSwitchBanner";
 import { TauriAPI } from "@/lib/tauri-api";
 import { ProxyConfig, SiteConfig } from "@/types";
 import { useAppData } from "@/hooks/useAppData";
@@ -15,7 +16,8 @@ import { useSaveSettings } from "@/hooks/useSettings";
 
 export default function HomePage() {
   const router = useRouter();
-  const { settings, browsers, sites, proxies, isLoading, error } = useAppData();
+  const { settings, browsers, sites, proxies, proxyGroups, isLoading, error } =
+    useAppData();
   const saveSettingsMutation = useSaveSettings();
   const [showSiteDialog, setShowSiteDialog] = useState(false);
   const [siteDialogMode, setSiteDialogMode] = useState<"add" | "edit">("add");
@@ -230,15 +232,19 @@ export default function HomePage() {
     <div className="min-h-screen bg-background">
       <Header
         onSettingsClick={() => router.push("/settings")}
+        onConnectionsClick={() => router.push("/connections")}
         onAboutClick={() => router.push("/about")}
       />
 
       <div className="container mx-auto px-6 pb-6 space-y-6 max-w-4xl">
+        <KillSwitchBanner proxies={proxies} />
+
         {/* Sites Section */}
         <SitesList
           sites={sites}
           browsers={browsers}
           proxies={proxies}
+          proxyGroups={proxyGroups}
           onLaunch={handleLaunchSite}
           onEdit={handleEditSite}
           onDelete={handleDeleteSite}
@@ -274,6 +280,7 @@ export default function HomePage() {
         site={editingSite || undefined}
         onSave={handleSaveSite}
         proxies={proxies}
+        proxyGroups={proxyGroups}
         browsers={browsers}
         defaultBrowser={settings?.default_browser}
       />
@@ -283,6 +290,7 @@ export default function HomePage() {
         onOpenChange={setShowProxyDialog}
         mode={proxyDialogMode}
         proxy={editingProxy || undefined}
+        proxies={proxies}
         onSave={handleSaveProxy}
       />
 
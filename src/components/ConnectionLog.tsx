@@ -0,0 +1,187 @@
+import { ConnectionEntry, ConnectionLogFilter, ProxyConfig } from "@/types";
+import { Button } from "@/components/ui/button";
+import { Card, CardContent, CardHeader, CardTitle } from "@/components/ui/card";
+import { Badge } from "@/components/ui/badge";
+import { Input } from "@/components/ui/input";
+import { Switch } from "@/components/ui/switch";
+import {
+  Select,
+  SelectContent,
+  SelectItem,
+  SelectTrigger,
+  SelectValue,
+} from "@/components/ui/select";
+import { ArrowLeft, Trash2 } from "lucide-react";
+import { useEffect, useMemo, useState } from "react";
+import { listen } from "@tauri-apps/api/event";
+import { TauriAPI } from "@/lib/tauri-api";
+
+// Entries shown at once; older ones stay in the backend log
+const MAX_ENTRIES = 500;
+
+const isFailed = (entry: ConnectionEntry) =>
+  !!entry.error || entry.status == null || entry.status >= 400;
+
+// Mirrors the filtering done by get_connection_log for live entries
+const matchesFilter = (entry: ConnectionEntry, filter: ConnectionLogFilter) =>
+  (!filter.proxy_id || entry.proxy_id === filter.proxy_id) &&
+  (!filter.host ||
+    entry.target.toLowerCase().includes(filter.host.toLowerCase())) &&
+  (!filter.failed_only || isFailed(entry));
+
+interface ConnectionLogProps {
+  proxies: ProxyConfig[];
+  onBack: () => void;
+}
+
+export function ConnectionLog({ proxies, onBack }: ConnectionLogProps) {
+  const [entries, setEntries] = useState<ConnectionEntry[]>([]);
+  const [host, setHost] = useState("");
+  const [proxyId, setProxyId] = useState("all");
+  const [failedOnly, setFailedOnly] = useState(false);
+
+  const filter = useMemo(
+    (): ConnectionLogFilter => ({
+      host: host.trim() || undefined,
+      proxy_id: proxyId === "all" ? undefined : proxyId,
+      failed_only: failedOnly,
+    }),
+    [host, proxyId, failedOnly]
+  );
+
+  // Reload the log whenever the filter changes
+  useEffect(() => {
+    TauriAPI.getConnectionLog({ ...filter, limit: MAX_ENTRIES }).then(
+      setEntries
+    );
+  }, [filter]);
+
+  // Prepend requests as the forwarders log them
+  useEffect(() => {
+    const unlisten = listen<ConnectionEntry>("connection-logged", (event) => {
+      if (matchesFilter(event.payload, filter)) {
+        setEntries((prevEntries) =>
+          [event.payload, ...prevEntries].slice(0, MAX_ENTRIES)
+        );
+      }
+    });
+    return () => {
+      unlisten.then((stop) => stop());
+    };
+  }, [filter]);
+
+  const handleClear = async () => {
+    try {
+      await TauriAPI.clearConnectionLog();
+      setEntries([]);
+    } catch (error) {
+      alert(`Failed to clear connection log: ${error}`);
+    }
+  };
+
+  const proxyName = (id: string) =>
+    proxies.find((proxy) => proxy.id === id)?.name || id;
+
+  return (
+    <div className="min-h-screen bg-background">
+      <div className="container mx-auto p-6 max-w-4xl">
+        {/* Header */}
+        <div className="flex items-center gap-4 mb-6">
+          <Button
+            variant="ghost"
+            size="sm"
+            onClick={onBack}
+            className="text-foreground"
+          >
+            <ArrowLeft className="h-4 w-4 mr-2" />
+            Back to Home
+          </Button>
+          <h1 className="text-2xl font-bold text-foreground">
+            Connection Log
+          </h1>
+        </div>
+
+        <Card>
+          <CardHeader>
+            <CardTitle>Requests Through Local Forwarders</CardTitle>
+          </CardHeader>
+          <CardContent className="space-y-4">
+            <p className="text-sm text-muted-foreground">
+              Only sessions relayed by the app&apos;s local forwarder are
+              logged: authenticated proxies, SOCKS4a and chains. A site missing
+              here while its session is running is not going through the proxy.
+            </p>
+
+            <div className="flex items-center gap-2">
+              <Input
+                placeholder="Filter by host"
+                value={host}
+                onChange={(e) => setHost(e.target.value)}
+              />
+              <Select value={proxyId} onValueChange={setProxyId}>
+                <SelectTrigger className="w-48">
+                  <SelectValue />
+                </SelectTrigger>
+                <SelectContent>
+                  <SelectItem value="all">All proxies</SelectItem>
+                  {proxies.map((proxy) => (
+                    <SelectItem key={proxy.id} value={proxy.id}>
+                      {proxy.name}
+                    </SelectItem>
+                  ))}
+                </SelectContent>
+              </Select>
+              <div className="flex items-center gap-2 whitespace-nowrap">
+                <Switch checked={failedOnly} onCheckedChange={setFailedOnly} />
+                <span className="text-sm text-foreground">Failed only</span>
+              </div>
+              <Button
+                type="button"
+                size="sm"
+                variant="ghost"
+                onClick={handleClear}
+                title="Clear log"
+              >
+                <Trash2 className="h-4 w-4" />
+              </Button>
+            </div>
+
+            <div className="space-y-1 font-mono text-xs">
+              {entries.map((entry) => (
+                <div
+                  key={entry.id}
+                  className="flex items-center gap-2 p-2 border rounded"
+                  title={entry.error || `via ${entry.upstream}`}
+                >
+                  <span className="text-muted-foreground">
+                    {new Date(entry.started_at).toLocaleTimeString()}
+                  </span>
+                  <Badge
+                    variant={isFailed(entry) ? "destructive" : "outline"}
+                    className="text-xs"
+                  >
+                    {entry.status ?? "—"}
+                  </Badge>
+                  <span className="w-16">{entry.method}</span>
+                  <span className="flex-1 truncate">{entry.target}</span>
+                  <span className="text-muted-foreground">
+                    {proxyName(entry.proxy_id)}
+                  </span>
+                  <span className="text-muted-foreground w-28 text-right">
+                    {entry.bytes_up + entry.bytes_down} B ·{" "}
+                    {entry.duration_ms} ms
+                  </span>
+                </div>
+              ))}
+              {entries.length === 0 && (
+                <p className="text-sm text-muted-foreground font-sans">
+                  No requests logged yet.
+                </p>
+              )}
+            </div>
+          </CardContent>
+        </Card>
+      </div>
+    </div>
+  );
+}
@@ -1,12 +1,17 @@
 import { Button } from "@/components/ui/button";
-import { Settings, Info } from "lucide-react";
+import { Settings, Info, ScrollText } from "lucide-react";
 
 interface HeaderProps {
   onSettingsClick: () => void;
+  onConnectionsClick: () => void;
   onAboutClick: () => void;
 }
 
-export function Header({ onSettingsClick, onAboutClick }: HeaderProps) {
+export function Header({
+  onSettingsClick,
+  onConnectionsClick,
+  onAboutClick,
+}: HeaderProps) {
   return (
     <div className="container mx-auto p-6 max-w-4xl">
       <div className="flex items-center gap-4 mb-6">
@@ -20,6 +25,15 @@ export function Header({ onSettingsClick, onAboutClick }: HeaderProps) {
             <Settings className="h-4 w-4 mr-2" />
             Settings
           </Button>
+          <Button
+            variant="ghost"
+            size="sm"
+            onClick={onConnectionsClick}
+            className="text-foreground"
+          >
+            <ScrollText className="h-4 w-4 mr-2" />
+            Connections
+          </Button>
           <Button
             variant="ghost"
             size="sm"
@@ -0,0 +1,54 @@
+import { ProxyConfig } from "@/types";
+import { Card, CardContent } from "@/components/ui/card";
+import { ShieldAlert } from "lucide-react";
+import { useEffect } from "react";
+import { listen } from "@tauri-apps/api/event";
+import { useQueryClient } from "@tanstack/react-query";
+import { useProxyHealth } from "@/hooks/useProxyHealth";
+
+interface KillSwitchBannerProps {
+  proxies: ProxyConfig[];
+}
+
+// Lists fail-closed proxies whose traffic is currently blocked
+export function KillSwitchBanner({ proxies }: KillSwitchBannerProps) {
+  const queryClient = useQueryClient();
+  const { data: healthList = [] } = useProxyHealth();
+
+  // Refresh right away instead of waiting for the next poll
+  useEffect(() => {
+    const unlisten = listen("kill-switch", () => {
+      queryClient.invalidateQueries({ queryKey: ["proxy-health"] });
+    });
+    return () => {
+      unlisten.then((stop) => stop());
+    };
+  }, [queryClient]);
+
+  const blocked = proxies.filter(
+    (proxy) =>
+      proxy.fail_closed &&
+      healthList.find((health) => health.proxy_id === proxy.id)?.status ===
+        "down"
+  );
+  if (blocked.length === 0) {
+    return null;
+  }
+
+  return (
+    <Card className="border-destructive">
+      <CardContent className="flex items-start gap-3 pt-6">
+        <ShieldAlert className="h-5 w-5 text-destructive shrink-0" />
+        <div className="text-sm">
+          <p className="font-medium text-foreground">Kill switch engaged</p>
+          {blocked.map((proxy) => (
+            <p key={proxy.id} className="text-muted-foreground">
+              {proxy.name} is down, so browsers using it are blocked until it
+              recovers.
+            </p>
+          ))}
+        </div>
+      </CardContent>
+    </Card>
+  );
+}
@@ -0,0 +1,137 @@
+import { PacRule, ProxyConfig } from "@/types";
+import { Button } from "@/components/ui/button";
+import { Card, CardContent, CardHeader, CardTitle } from "@/components/ui/card";
+import { Input } from "@/components/ui/input";
+import {
+  Select,
+  SelectContent,
+  SelectItem,
+  SelectTrigger,
+  SelectValue,
+} from "@/components/ui/select";
+import { Plus, Trash2 } from "lucide-react";
+import { useEffect, useState } from "react";
+import { useSavePacRules } from "@/hooks/useSettings";
+
+interface PacRulesCardProps {
+  rules: PacRule[];
+  proxies: ProxyConfig[];
+}
+
+// Proxies a PAC script can return; the others need the local forwarder
+const isRuleTarget = (proxy: ProxyConfig) =>
+  ["http", "https", "socks4", "socks5"].includes(proxy.proxy_type) &&
+  !proxy.username;
+
+export function PacRulesCard({ rules, proxies }: PacRulesCardProps) {
+  const [currentRules, setCurrentRules] = useState(rules);
+  const savePacRulesMutation = useSavePacRules();
+
+  // Update rules when prop changes
+  useEffect(() => {
+    setCurrentRules(rules);
+  }, [rules]);
+
+  const updateRule = (index: number, rule: PacRule) => {
+    setCurrentRules((prevRules) =>
+      prevRules.map((prevRule, i) => (i === index ? rule : prevRule))
+    );
+  };
+
+  const handleSaveRules = async () => {
+    const cleanedRules = currentRules
+      .map((rule) => ({ ...rule, pattern: rule.pattern.trim() }))
+      .filter((rule) => rule.pattern.length > 0);
+
+    try {
+      await savePacRulesMutation.mutateAsync(cleanedRules);
+      setCurrentRules(cleanedRules);
+      console.log("PAC rules saved successfully");
+    } catch (error) {
+      console.error("Failed to save PAC rules:", error);
+      alert(`Failed to save PAC rules: ${error}`);
+    }
+  };
+
+  return (
+    <Card>
+      <CardHeader>
+        <CardTitle>PAC Rules</CardTitle>
+      </CardHeader>
+      <CardContent className="space-y-4">
+        <p className="text-sm text-muted-foreground">
+          Used by proxies of the PAC Rules type. The first matching rule
+          decides; hosts matching no rule connect directly.
+        </p>
+
+        <div className="space-y-2">
+          {currentRules.map((rule, index) => (
+            <div key={index} className="flex items-center gap-2">
+              <Input
+                placeholder="*.example.com or 10.0.0.0/8"
+                value={rule.pattern}
+                onChange={(e) =>
+                  updateRule(index, { ...rule, pattern: e.target.value })
+                }
+              />
+              <Select
+                value={rule.proxy_id || "direct"}
+                onValueChange={(value) =>
+                  updateRule(index, {
+                    ...rule,
+                    proxy_id: value === "direct" ? undefined : value,
+                  })
+                }
+              >
+                <SelectTrigger className="w-48">
+                  <SelectValue />
+                </SelectTrigger>
+                <SelectContent>
+                  <SelectItem value="direct">DIRECT</SelectItem>
+                  {proxies.filter(isRuleTarget).map((proxy) => (
+                    <SelectItem key={proxy.id} value={proxy.id}>
+                      {proxy.name}
+                    </SelectItem>
+                  ))}
+                </SelectContent>
+              </Select>
+              <Button
+                type="button"
+                size="sm"
+                variant="ghost"
+                onClick={() =>
+                  setCurrentRules((prevRules) =>
+                    prevRules.filter((_, i) => i !== index)
+                  )
+                }
+                title="Remove rule"
+              >
+                <Trash2 className="h-4 w-4" />
+              </Button>
+            </div>
+          ))}
+        </div>
+
+        <div className="flex gap-2">
+          <Button
+            type="button"
+            variant="outline"
+            onClick={() =>
+              setCurrentRules((prevRules) => [...prevRules, { pattern: "" }])
+            }
+          >
+            <Plus className="h-4 w-4 mr-1" />
+            Add Rule
+          </Button>
+          <Button
+            type="button"
+            onClick={handleSaveRules}
+            disabled={savePacRulesMutation.isPending}
+          >
+            Save Rules
+          </Button>
+        </div>
+      </CardContent>
+    </Card>
+  );
+}
@@ -3,7 +3,7 @@
 import { useForm } from "react-hook-form";
 import { zodResolver } from "@hookform/resolvers/zod";
 import * as z from "zod";
-import { useEffect } from "react";
+import { useEffect, useState } from "react";
 import { Button } from "@/components/ui/button";
 import {
   Dialog,
@@ -23,6 +23,7 @@ import {
   FormMessage,
 } from "@/components/ui/form";
 import { Input } from "@/components/ui/input";
+import { Switch } from "@/components/ui/switch";
 import {
   Select,
   SelectContent,
@@ -30,21 +31,50 @@ import {
   SelectTrigger,
   SelectValue,
 } from "@/components/ui/select";
-import { ProxyConfig } from "@/types";
+import { PacEvaluation, ProxyConfig } from "@/types";
+import { TauriAPI } from "@/lib/tauri-api";
 
 const proxyFormSchema = z
   .object({
     name: z.string().min(1, "Proxy name is required").trim(),
-    type: z.enum(["http", "socks5", "pac"]),
+    type: z.enum([
+      "http",
+      "https",
+      "socks4",
+      "socks4a",
+      "socks5",
+      "pac",
+      "chain",
+      "rules",
+      "ssh",
+      "helper",
+      "tor",
+    ]),
     host: z.string().optional(),
     port: z
       .number()
-      .min(1, "Port must be between 1-65535")
+      .min(0, "Port must be between 1-65535")
       .max(65535, "Port must be between 1-65535")
       .optional(),
     username: z.string().optional(),
     password: z.string().optional(),
     url: z.string().optional(),
+    identity_file: z.string().optional(),
+    known_hosts: z.enum(["strict", "accept_new", "off"]).optional(),
+    command: z.string().optional(),
+    args: z.string().optional(),
+    readiness: z.enum(["port", "output"]).optional(),
+    ready_pattern: z.string().optional(),
+    ready_timeout_secs: z.number().min(1).optional(),
+    endpoint_type: z.enum(["http", "socks5"]).optional(),
+    tor_mode: z.enum(["existing", "launch"]).optional(),
+    control_port: z.number().min(0).max(65535).optional(),
+    control_password: z.string().optional(),
+    tor_path: z.string().optional(),
+    bypass: z.string().optional(),
+    remote_dns: z.boolean().optional(),
+    fail_closed: z.boolean().optional(),
+    hops: z.array(z.string()).optional(),
   })
   .refine(
     (data) => {
@@ -52,13 +82,73 @@ const proxyFormSchema = z
       if (data.type === "pac") {
         return data.url && data.url.trim().length > 0;
       }
+      // Chain type requires at least one hop
+      if (data.type === "chain") {
+        return (data.hops || []).length > 0;
+      }
+      // Rules type is configured in Settings
+      if (data.type === "rules") {
+        return true;
+      }
+      // Helper type requires a command, the app picks a port when it is 0
+      if (data.type === "helper") {
+        return !!data.command && data.command.trim().length > 0;
+      }
+      // A launched Tor runs on loopback
+      if (data.type === "tor" && data.tor_mode === "launch") {
+        return true;
+      }
       // Non-PAC types require host and port
       return data.host && data.host.trim().length > 0 && data.port;
     },
     {
-      message: "PAC type requires URL, other types require host and port",
+      message:
+        "PAC type requires URL, chains require a hop, helpers require a command, other types require host and port",
       path: ["url"],
     }
+  )
+  .refine(
+    (data) =>
+      !(data.type === "socks4" || data.type === "socks4a") ||
+      !data.password,
+    {
+      message: "SOCKS4 supports a user ID but not a password",
+      path: ["password"],
+    }
+  )
+  .refine((data) => data.type !== "ssh" || !data.password, {
+    message: "SSH tunnels authenticate with a key or the SSH agent",
+    path: ["password"],
+  })
+  .refine(
+    (data) =>
+      data.type === "helper" ||
+      (data.type === "tor" && data.tor_mode === "launch") ||
+      data.port !== 0,
+    {
+      message: "Port must be between 1-65535",
+      path: ["port"],
+    }
+  )
+  .refine(
+    (data) =>
+      data.type !== "helper" ||
+      !!data.port ||
+      (data.args || "").includes("{port}"),
+    {
+      message: "Use {port} in the arguments to let the app pick the port",
+      path: ["args"],
+    }
+  )
+  .refine(
+    (data) =>
+      data.type !== "helper" ||
+      data.readiness !== "output" ||
+      !!data.ready_pattern?.trim(),
+    {
+      message: "Enter the output that shows the helper is ready",
+      path: ["ready_pattern"],
+    }
   );
 
 type ProxyFormValues = z.infer<typeof proxyFormSchema>;
@@ -68,6 +158,7 @@ interface ProxyDialogProps {
   onOpenChange: (open: boolean) => void;
   mode: "add" | "edit";
   proxy?: ProxyConfig; // Proxy configuration passed when editing
+  proxies?: ProxyConfig[]; // Candidate hops for chains
   onSave: (proxyId: string | undefined, proxy: Omit<ProxyConfig, "id">) => void;
 }
 
@@ -76,6 +167,7 @@ export function ProxyDialog({
   onOpenChange,
   mode,
   proxy,
+  proxies = [],
   onSave,
 }: ProxyDialogProps) {
   const form = useForm<ProxyFormValues>({
@@ -88,10 +180,57 @@ export function ProxyDialog({
       username: "",
       password: "",
       url: "",
+      identity_file: "",
+      known_hosts: "strict",
+      command: "",
+      args: "",
+      readiness: "port",
+      ready_pattern: "",
+      ready_timeout_secs: 30,
+      endpoint_type: "socks5",
+      tor_mode: "existing",
+      control_port: undefined,
+      control_password: "",
+      tor_path: "",
+      bypass: "",
+      remote_dns: false,
+      fail_closed: false,
+      hops: [],
     },
   });
 
+  const [previewUrl, setPreviewUrl] = useState("");
+  const [previewResult, setPreviewResult] = useState<PacEvaluation | null>(
+    null
+  );
+  const [previewError, setPreviewError] = useState<string | null>(null);
+
   const selectedType = form.watch("type");
+  const selectedHops = form.watch("hops") || [];
+  const selectedTorMode = form.watch("tor_mode");
+  // The kill switch does not cover processes started for the session
+  const runsHelper =
+    selectedType === "ssh" ||
+    selectedType === "helper" ||
+    (selectedType === "tor" && selectedTorMode === "launch");
+  const isServerType =
+    selectedType !== "pac" &&
+    selectedType !== "chain" &&
+    selectedType !== "rules";
+
+  // Chains can route through any single proxy server except themselves
+  const hopCandidates = proxies.filter(
+    (candidate) =>
+      candidate.id !== proxy?.id &&
+      candidate.proxy_type !== "pac" &&
+      candidate.proxy_type !== "chain" &&
+      candidate.proxy_type !== "rules" &&
+      candidate.proxy_type !== "ssh" &&
+      candidate.proxy_type !== "helper" &&
+      !(candidate.proxy_type === "tor" && candidate.tor_mode === "launch")
+  );
+  const proxyName = (id: string) =>
+    proxies.find((candidate) => candidate.id === id)?.name || id;
 
   // Set form initial values when dialog opens and has proxy data
   useEffect(() => {
@@ -100,10 +239,29 @@ export function ProxyDialog({
         name: proxy.name,
         type: proxy.proxy_type,
         host: proxy.host,
-        port: proxy.port || 1,
+        port:
+          proxy.proxy_type === "helper" || proxy.proxy_type === "tor"
+            ? proxy.port || 0
+            : proxy.port || 1,
         username: proxy.username || "",
         password: proxy.password || "",
         url: proxy.url || "",
+        identity_file: proxy.identity_file || "",
+        known_hosts: proxy.known_hosts || "strict",
+        command: proxy.command || "",
+        args: (proxy.args || []).join("\n"),
+        readiness: proxy.readiness || "port",
+        ready_pattern: proxy.ready_pattern || "",
+        ready_timeout_secs: proxy.ready_timeout_secs || 30,
+        endpoint_type: proxy.endpoint_type || "socks5",
+        tor_mode: proxy.tor_mode || "existing",
+        control_port: proxy.control_port,
+        control_password: proxy.control_password || "",
+        tor_path: proxy.tor_path || "",
+        bypass: (proxy.bypass || []).join("; "),
+        remote_dns: proxy.remote_dns || false,
+        fail_closed: proxy.fail_closed || false,
+        hops: proxy.hops || [],
       });
     } else if (open && mode === "add") {
       form.reset({
@@ -114,19 +272,105 @@ export function ProxyDialog({
         username: "",
         password: "",
         url: "",
+        identity_file: "",
+        known_hosts: "strict",
+        command: "",
+        args: "",
+        readiness: "port",
+        ready_pattern: "",
+        ready_timeout_secs: 30,
+        endpoint_type: "socks5",
+        tor_mode: "existing",
+        control_port: undefined,
+        control_password: "",
+        tor_path: "",
+        bypass: "",
+        remote_dns: false,
+        fail_closed: false,
+        hops: [],
       });
     }
   }, [open, mode, proxy, form]);
 
   const onSubmit = (values: ProxyFormValues) => {
+    const hasServer =
+      values.type !== "pac" && values.type !== "chain" && values.type !== "rules";
     const proxyData: Omit<ProxyConfig, "id"> = {
       name: values.name,
       proxy_type: values.type,
-      host: values.type === "pac" ? "" : values.host || "", // PAC type doesn't need host
-      port: values.type === "pac" ? 0 : values.port || 8080, // PAC type doesn't need port
-      username: values.username || undefined,
-      password: values.password || undefined,
+      host: hasServer ? values.host || "" : "", // PAC, chain and rules types don't need host
+      port: hasServer
+        ? values.port ??
+          (values.type === "tor" && values.tor_mode === "launch" ? 0 : 8080)
+        : 0, // PAC, chain and rules types don't need port
+      username:
+        values.type !== "rules" &&
+        values.type !== "helper" &&
+        values.type !== "tor"
+          ? values.username || undefined
+          : undefined,
+      password:
+        values.type !== "rules" &&
+        values.type !== "ssh" &&
+        values.type !== "helper" &&
+        values.type !== "tor"
+          ? values.password || undefined
+          : undefined,
       ...(values.type === "pac" && values.url ? { url: values.url } : {}), // PAC type needs url
+      ...(values.type === "ssh"
+        ? {
+            identity_file: values.identity_file || undefined,
+            known_hosts: values.known_hosts || "strict",
+          }
+        : {}), // SSH type needs key and host key settings
+      ...(values.type === "helper"
+        ? {
+            command: (values.command || "").trim(),
+            args: (values.args || "")
+              .split("\n")
+              .map((arg) => arg.trim())
+              .filter((arg) => arg.length > 0),
+            readiness: values.readiness || "port",
+            ready_pattern:
+              values.readiness === "output" ? values.ready_pattern : undefined,
+            ready_timeout_secs: values.ready_timeout_secs || 30,
+            endpoint_type: values.endpoint_type || "socks5",
+          }
+        : {}), // Helper type needs the command and how to reach its proxy
+      ...(values.type === "tor"
+        ? {
+            tor_mode: values.tor_mode || "existing",
+            // A launched Tor gets a free control port with cookie auth
+            control_port:
+              values.tor_mode === "launch"
+                ? undefined
+                : values.control_port || undefined,
+            control_password:
+              values.tor_mode === "launch"
+                ? undefined
+                : values.control_password || undefined,
+            tor_path: values.tor_path || undefined,
+          }
+        : {}), // Tor type needs its mode and control port settings
+      bypass:
+        values.type === "pac" || values.type === "rules"
+          ? [] // PAC scripts decide which hosts go direct
+          : (values.bypass || "")
+              .split(/[;,\n]/)
+              .map((rule) => rule.trim())
+              .filter((rule) => rule.length > 0),
+      remote_dns:
+        (values.type === "socks5" ||
+          values.type === "ssh" ||
+          values.type === "tor" ||
+          (values.type === "helper" && values.endpoint_type !== "http")) &&
+        !!values.remote_dns, // Only SOCKS5 proxies, SSH tunnels and Tor resolve remotely
+      fail_closed:
+        values.type !== "ssh" &&
+        values.type !== "helper" &&
+        !(values.type === "tor" && values.tor_mode === "launch") &&
+        !!values.fail_closed, // SSH tunnels, helpers and launched Tor cannot fail closed
+      ...(values.type === "chain" ? { hops: values.hops || [] } : {}), // Chain type needs hops
     };
 
     onSave(proxy?.id, proxyData);
@@ -135,9 +379,52 @@ export function ProxyDialog({
 
   const handleClose = () => {
     form.reset();
+    setPreviewResult(null);
+    setPreviewError(null);
     onOpenChange(false);
   };
 
+  const handleBrowseIdentityFile = async () => {
+    const selectedPath = await TauriAPI.browseOpenFile([]);
+    if (selectedPath) {
+      form.setValue("identity_file", selectedPath);
+    }
+  };
+
+  const handleBrowseCommand = async () => {
+    const selectedPath = await TauriAPI.browseOpenFile([]);
+    if (selectedPath) {
+      form.setValue("command", selectedPath);
+    }
+  };
+
+  const handleBrowseTorPath = async () => {
+    const selectedPath = await TauriAPI.browseOpenFile([]);
+    if (selectedPath) {
+      form.setValue("tor_path", selectedPath);
+    }
+  };
+
+  const handleBrowsePacFile = async () => {
+    const selectedPath = await TauriAPI.browseOpenFile(["pac", "js"]);
+    if (selectedPath) {
+      form.setValue("url", selectedPath);
+    }
+  };
+
+  // Previews the saved configuration, so unsaved edits are not included
+  const handlePreview = async () => {
+    if (!proxy || !previewUrl.trim()) return;
+
+    try {
+      setPreviewResult(await TauriAPI.evaluatePac(proxy.id, previewUrl.trim()));
+      setPreviewError(null);
+    } catch (error) {
+      setPreviewResult(null);
+      setPreviewError(String(error));
+    }
+  };
+
   const isEditMode = mode === "edit";
 
   return (
@@ -182,10 +469,34 @@ export function ProxyDialog({
                       <Select
                         onValueChange={(value) => {
                           field.onChange(value);
-                          // Clear port and host when switching to PAC type
-                          if (value === "pac") {
+                          // Clear port and host when switching to PAC, chain or rules type
+                          if (
+                            value === "pac" ||
+                            value === "chain" ||
+                            value === "rules"
+                          ) {
                             form.setValue("port", undefined);
                             form.setValue("host", "");
+                          } else if (value === "ssh") {
+                            const port = form.getValues("port");
+                            if (!port || port === 8080) {
+                              form.setValue("port", 22);
+                            }
+                          } else if (value === "tor") {
+                            if (!form.getValues("host")) {
+                              form.setValue("host", "127.0.0.1");
+                            }
+                            const port = form.getValues("port");
+                            if (!port || port === 8080) {
+                              form.setValue("port", 9050);
+                            }
+                          } else if (value === "helper") {
+                            if (!form.getValues("host")) {
+                              form.setValue("host", "127.0.0.1");
+                            }
+                            if (form.getValues("port") === 8080) {
+                              form.setValue("port", 0);
+                            }
                           } else if (!form.getValues("port")) {
                             form.setValue("port", 8080);
                           }
@@ -199,8 +510,18 @@ export function ProxyDialog({
                         </FormControl>
                         <SelectContent>
                           <SelectItem value="http">HTTP</SelectItem>
+                          <SelectItem value="https">HTTPS</SelectItem>
+                          <SelectItem value="socks4">SOCKS4</SelectItem>
+                          <SelectItem value="socks4a">SOCKS4a</SelectItem>
                           <SelectItem value="socks5">SOCKS5</SelectItem>
                           <SelectItem value="pac">PAC</SelectItem>
+                          <SelectItem value="chain">Chain</SelectItem>
+                          <SelectItem value="rules">PAC Rules</SelectItem>
+                          <SelectItem value="ssh">SSH Tunnel</SelectItem>
+                          <SelectItem value="helper">
+                            Helper Process
+                          </SelectItem>
+                          <SelectItem value="tor">Tor</SelectItem>
                         </SelectContent>
                       </Select>
                       <FormMessage />
@@ -208,7 +529,7 @@ export function ProxyDialog({
                   )}
                 />
 
-                {selectedType !== "pac" && (
+                {isServerType && (
                   <FormField
                     control={form.control}
                     name="port"
@@ -220,11 +541,19 @@ export function ProxyDialog({
                             type="number"
                             placeholder="8080"
                             {...field}
-                            onChange={(e) =>
-                              field.onChange(parseInt(e.target.value) || 8080)
-                            }
+                            onChange={(e) => {
+                              const port = parseInt(e.target.value);
+                              field.onChange(Number.isNaN(port) ? 8080 : port);
+                            }}
                           />
                         </FormControl>
+                        {(selectedType === "helper" ||
+                          (selectedType === "tor" &&
+                            selectedTorMode === "launch")) && (
+                          <FormDescription className="text-muted-foreground">
+                            0 picks a free port
+                          </FormDescription>
+                        )}
                         <FormMessage />
                       </FormItem>
                     )}
@@ -232,6 +561,46 @@ export function ProxyDialog({
                 )}
               </div>
 
+              {selectedType === "tor" && (
+                <FormField
+                  control={form.control}
+                  name="tor_mode"
+                  render={({ field }) => (
+                    <FormItem>
+                      <FormLabel className="text-foreground">Tor</FormLabel>
+                      <Select
+                        onValueChange={(value) => {
+                          field.onChange(value);
+                          // A launched Tor picks free ports unless told otherwise
+                          const port = form.getValues("port");
+                          if (value === "launch" && port === 9050) {
+                            form.setValue("port", 0);
+                          } else if (value === "existing" && !port) {
+                            form.setValue("port", 9050);
+                          }
+                        }}
+                        value={field.value}
+                      >
+                        <FormControl>
+                          <SelectTrigger>
+                            <SelectValue />
+                          </SelectTrigger>
+                        </FormControl>
+                        <SelectContent>
+                          <SelectItem value="existing">
+                            Use a running Tor
+                          </SelectItem>
+                          <SelectItem value="launch">
+                            Launch Tor per profile
+                          </SelectItem>
+                        </SelectContent>
+                      </Select>
+                      <FormMessage />
+                    </FormItem>
+                  )}
+                />
+              )}
+
               {selectedType === "pac" ? (
                 <FormField
                   control={form.control}
@@ -239,14 +608,115 @@ export function ProxyDialog({
                   render={({ field }) => (
                     <FormItem>
                       <FormLabel className="text-foreground">PAC URL</FormLabel>
-                      <FormControl>
-                        <Input
-                          placeholder="http://example.com/proxy.pac"
-                          {...field}
-                        />
-                      </FormControl>
+                      <div className="flex gap-2">
+                        <FormControl>
+                          <Input
+                            placeholder="http://example.com/proxy.pac"
+                            {...field}
+                          />
+                        </FormControl>
+                        <Button
+                          type="button"
+                          variant="outline"
+                          onClick={handleBrowsePacFile}
+                        >
+                          Browse
+                        </Button>
+                      </div>
+                      <FormDescription className="text-muted-foreground">
+                        URL or local path of the PAC (Proxy Auto-Configuration)
+                        file. Local files are served to the browser by the app
+                      </FormDescription>
+                      <FormMessage />
+                    </FormItem>
+                  )}
+                />
+              ) : selectedType === "chain" ? (
+                <FormField
+                  control={form.control}
+                  name="hops"
+                  render={({ field }) => (
+                    <FormItem>
+                      <FormLabel className="text-foreground">Hops</FormLabel>
+                      <div className="space-y-2">
+                        {selectedHops.map((hopId, index) => (
+                          <div
+                            key={`${hopId}-${index}`}
+                            className="flex items-center justify-between rounded-md border px-3 py-1 text-sm"
+                          >
+                            <span>
+                              {index + 1}. {proxyName(hopId)}
+                            </span>
+                            <Button
+                              type="button"
+                              size="sm"
+                              variant="ghost"
+                              onClick={() =>
+                                field.onChange(
+                                  selectedHops.filter((_, i) => i !== index)
+                                )
+                              }
+                            >
+                              Remove
+                            </Button>
+                          </div>
+                        ))}
+                        <Select
+                          value=""
+                          onValueChange={(hopId) =>
+                            field.onChange([...selectedHops, hopId])
+                          }
+                        >
+                          <FormControl>
+                            <SelectTrigger>
+                              <SelectValue placeholder="Add hop" />
+                            </SelectTrigger>
+                          </FormControl>
+                          <SelectContent>
+                            {hopCandidates.map((candidate) => (
+                              <SelectItem key={candidate.id} value={candidate.id}>
+                                {candidate.name}
+                              </SelectItem>
+                            ))}
+                          </SelectContent>
+                        </Select>
+                      </div>
+                      <FormDescription className="text-muted-foreground">
+                        Traffic goes through each hop in order
+                      </FormDescription>
+                      <FormMessage />
+                    </FormItem>
+                  )}
+                />
+              ) : selectedType === "rules" ? (
+                <p className="text-sm text-muted-foreground">
+                  Routes each host by the PAC rules in Settings. The app
+                  serves the generated PAC script locally.
+                </p>
+              ) : selectedType === "tor" && selectedTorMode === "launch" ? (
+                <FormField
+                  control={form.control}
+                  name="tor_path"
+                  render={({ field }) => (
+                    <FormItem>
+                      <FormLabel className="text-foreground">
+                        Tor Binary
+                      </FormLabel>
+                      <div className="flex gap-2">
+                        <FormControl>
+                          <Input placeholder="tor (from PATH)" {...field} />
+                        </FormControl>
+                        <Button
+                          type="button"
+                          variant="outline"
+                          onClick={handleBrowseTorPath}
+                        >
+                          Browse
+                        </Button>
+                      </div>
                       <FormDescription className="text-muted-foreground">
-                        URL to the PAC (Proxy Auto-Configuration) file
+                        Started for each browser profile with its own data
+                        directory
                       </FormDescription>
                       <FormMessage />
                     </FormItem>
@@ -271,49 +741,448 @@ export function ProxyDialog({
                 />
               )}
 
-              <div className="grid grid-cols-2 gap-4">
+              {selectedType === "helper" && (
+                <>
+                  <FormField
+                    control={form.control}
+                    name="command"
+                    render={({ field }) => (
+                      <FormItem>
+                        <FormLabel className="text-foreground">
+                          Command
+                        </FormLabel>
+                        <div className="flex gap-2">
+                          <FormControl>
+                            <Input
+                              placeholder="e.g., tor, sslocal, cloudflared"
+                              {...field}
+                            />
+                          </FormControl>
+                          <Button
+                            type="button"
+                            variant="outline"
+                            onClick={handleBrowseCommand}
+                          >
+                            Browse
+                          </Button>
+                        </div>
+                        <FormMessage />
+                      </FormItem>
+                    )}
+                  />
+
+                  <FormField
+                    control={form.control}
+                    name="args"
+                    render={({ field }) => (
+                      <FormItem>
+                        <FormLabel className="text-foreground">
+                          Arguments
+                        </FormLabel>
+                        <FormControl>
+                          <textarea
+                            className="flex min-h-[80px] w-full rounded-md border border-input bg-background px-3 py-2 text-sm font-mono text-foreground ring-offset-background placeholder:text-muted-foreground focus-visible:outline-none focus-visible:ring-2 focus-visible:ring-ring focus-visible:ring-offset-2"
+                            placeholder={"--local-port\n{port}"}
+                            {...field}
+                          />
+                        </FormControl>
+                        <FormDescription className="text-muted-foreground">
+                          One argument per line. {"{port}"} is replaced by the
+                          port the helper should listen on
+                        </FormDescription>
+                        <FormMessage />
+                      </FormItem>
+                    )}
+                  />
+
+                  <div className="grid grid-cols-2 gap-4">
+                    <FormField
+                      control={form.control}
+                      name="endpoint_type"
+                      render={({ field }) => (
+                        <FormItem>
+                          <FormLabel className="text-foreground">
+                            Serves
+                          </FormLabel>
+                          <Select
+                            onValueChange={field.onChange}
+                            value={field.value}
+                          >
+                            <FormControl>
+                              <SelectTrigger>
+                                <SelectValue />
+                              </SelectTrigger>
+                            </FormControl>
+                            <SelectContent>
+                              <SelectItem value="socks5">SOCKS5</SelectItem>
+                              <SelectItem value="http">HTTP</SelectItem>
+                            </SelectContent>
+                          </Select>
+                          <FormMessage />
+                        </FormItem>
+                      )}
+                    />
+
+                    <FormField
+                      control={form.control}
+                      name="ready_timeout_secs"
+                      render={({ field }) => (
+                        <FormItem>
+                          <FormLabel className="text-foreground">
+                            Timeout (s)
+                          </FormLabel>
+                          <FormControl>
+                            <Input
+                              type="number"
+                              {...field}
+                              onChange={(e) =>
+                                field.onChange(parseInt(e.target.value) || 30)
+                              }
+                            />
+                          </FormControl>
+                          <FormMessage />
+                        </FormItem>
+                      )}
+                    />
+                  </div>
+
+                  <div className="grid grid-cols-2 gap-4">
+                    <FormField
+                      control={form.control}
+                      name="readiness"
+                      render={({ field }) => (
+                        <FormItem>
+                          <FormLabel className="text-foreground">
+                            Ready When
+                          </FormLabel>
+                          <Select
+                            onValueChange={field.onChange}
+                            value={field.value}
+                          >
+                            <FormControl>
+                              <SelectTrigger>
+                                <SelectValue />
+                              </SelectTrigger>
+                            </FormControl>
+                            <SelectContent>
+                              <SelectItem value="port">Port opens</SelectItem>
+                              <SelectItem value="output">
+                                Output matches
+                              </SelectItem>
+                            </SelectContent>
+                          </Select>
+                          <FormMessage />
+                        </FormItem>
+                      )}
+                    />
+
+                    {form.watch("readiness") === "output" && (
+                      <FormField
+                        control={form.control}
+                        name="ready_pattern"
+                        render={({ field }) => (
+                          <FormItem>
+                            <FormLabel className="text-foreground">
+                              Pattern
+                            </FormLabel>
+                            <FormControl>
+                              <Input
+                                placeholder="e.g., Bootstrapped 100%"
+                                {...field}
+                              />
+                            </FormControl>
+                            <FormDescription className="text-muted-foreground">
+                              Regular expression
+                            </FormDescription>
+                            <FormMessage />
+                          </FormItem>
+                        )}
+                      />
+                    )}
+                  </div>
+                </>
+              )}
+
+              {selectedType === "tor" && selectedTorMode !== "launch" && (
+                <div className="grid grid-cols-2 gap-4">
+                  <FormField
+                    control={form.control}
+                    name="control_port"
+                    render={({ field }) => (
+                      <FormItem>
+                        <FormLabel className="text-foreground">
+                          Control Port
+                        </FormLabel>
+                        <FormControl>
+                          <Input
+                            type="number"
+                            placeholder="9051"
+                            {...field}
+                            value={field.value ?? ""}
+                            onChange={(e) =>
+                              field.onChange(
+                                parseInt(e.target.value) || undefined
+                              )
+                            }
+                          />
+                        </FormControl>
+                        <FormDescription className="text-muted-foreground">
+                          For new identities
+                        </FormDescription>
+                        <FormMessage />
+                      </FormItem>
+                    )}
+                  />
+
+                  <FormField
+                    control={form.control}
+                    name="control_password"
+                    render={({ field }) => (
+                      <FormItem>
+                        <FormLabel className="text-foreground">
+                          Control Password
+                        </FormLabel>
+                        <FormControl>
+                          <Input
+                            type="password"
+                            placeholder="Optional"
+                            {...field}
+                          />
+                        </FormControl>
+                        <FormDescription className="text-muted-foreground">
+                          Not needed with cookie authentication
+                        </FormDescription>
+                        <FormMessage />
+                      </FormItem>
+                    )}
+                  />
+                </div>
+              )}
+
+              {selectedType !== "chain" &&
+                selectedType !== "rules" &&
+                selectedType !== "helper" &&
+                selectedType !== "tor" && (
+                  <div className="grid grid-cols-2 gap-4">
+                    <FormField
+                      control={form.control}
+                      name="username"
+                      render={({ field }) => (
+                        <FormItem>
+                          <FormLabel className="text-foreground">
+                            Username
+                          </FormLabel>
+                          <FormControl>
+                            <Input placeholder="Optional" {...field} />
+                          </FormControl>
+                          <FormDescription className="text-muted-foreground">
+                            Optional authentication
+                          </FormDescription>
+                          <FormMessage />
+                        </FormItem>
+                      )}
+                    />
+
+                    {selectedType === "ssh" ? (
+                      <FormField
+                        control={form.control}
+                        name="known_hosts"
+                        render={({ field }) => (
+                          <FormItem>
+                            <FormLabel className="text-foreground">
+                              Host Key
+                            </FormLabel>
+                            <Select
+                              onValueChange={field.onChange}
+                              value={field.value}
+                            >
+                              <FormControl>
+                                <SelectTrigger>
+                                  <SelectValue />
+                                </SelectTrigger>
+                              </FormControl>
+                              <SelectContent>
+                                <SelectItem value="strict">
+                                  Known hosts only
+                                </SelectItem>
+                                <SelectItem value="accept_new">
+                                  Accept new hosts
+                                </SelectItem>
+                                <SelectItem value="off">
+                                  Don&apos;t check
+                                </SelectItem>
+                              </SelectContent>
+                            </Select>
+                            <FormDescription className="text-muted-foreground">
+                              Checked against known_hosts
+                            </FormDescription>
+                            <FormMessage />
+                          </FormItem>
+                        )}
+                      />
+                    ) : (
+                      <FormField
+                        control={form.control}
+                        name="password"
+                        render={({ field }) => (
+                          <FormItem>
+                            <FormLabel className="text-foreground">
+                              Password
+                            </FormLabel>
+                            <FormControl>
+                              <Input
+                                type="password"
+                                placeholder="Optional"
+                                {...field}
+                              />
+                            </FormControl>
+                            <FormDescription className="text-muted-foreground">
+                              Optional authentication
+                            </FormDescription>
+                            <FormMessage />
+                          </FormItem>
+                        )}
+                      />
+                    )}
+                  </div>
+                )}
+
+              {selectedType === "ssh" && (
                 <FormField
                   control={form.control}
-                  name="username"
+                  name="identity_file"
                   render={({ field }) => (
                     <FormItem>
                       <FormLabel className="text-foreground">
-                        Username
+                        Identity File
                       </FormLabel>
-                      <FormControl>
-                        <Input placeholder="Optional" {...field} />
-                      </FormControl>
+                      <div className="flex gap-2">
+                        <FormControl>
+                          <Input placeholder="Path to private key" {...field} />
+                        </FormControl>
+                        <Button
+                          type="button"
+                          variant="outline"
+                          onClick={handleBrowseIdentityFile}
+                        >
+                          Browse
+                        </Button>
+                      </div>
                       <FormDescription className="text-muted-foreground">
-                        Optional authentication
+                        Private key to log in with. Leave empty to use the SSH
+                        agent and default keys; keys with a passphrase must be
+                        in the agent
                       </FormDescription>
                       <FormMessage />
                     </FormItem>
                   )}
                 />
+              )}
 
+              {selectedType !== "pac" && selectedType !== "rules" && (
                 <FormField
                   control={form.control}
-                  name="password"
+                  name="bypass"
                   render={({ field }) => (
                     <FormItem>
                       <FormLabel className="text-foreground">
-                        Password
+                        Bypass List
                       </FormLabel>
                       <FormControl>
                         <Input
-                          type="password"
-                          placeholder="Optional"
+                          placeholder="*.internal.corp; 10.0.0.0/8; <local>"
                           {...field}
                         />
                       </FormControl>
                       <FormDescription className="text-muted-foreground">
-                        Optional authentication
+                        Hosts to connect to directly, separated by semicolons.
+                        Add &lt;-loopback&gt; to proxy localhost too
                       </FormDescription>
                       <FormMessage />
                     </FormItem>
                   )}
                 />
-              </div>
+              )}
+
+              {(selectedType === "socks5" ||
+                selectedType === "ssh" ||
+                selectedType === "tor" ||
+                (selectedType === "helper" &&
+                  form.watch("endpoint_type") !== "http")) && (
+                <FormField
+                  control={form.control}
+                  name="remote_dns"
+                  render={({ field: { value, onChange } }) => (
+                    <FormItem className="flex flex-row items-center space-x-4 space-y-0">
+                      <FormControl>
+                        <Switch checked={value} onCheckedChange={onChange} />
+                      </FormControl>
+                      <FormLabel className="ml-2 text-sm text-foreground">
+                        Remote DNS only (block local lookups and WebRTC leaks)
+                      </FormLabel>
+                    </FormItem>
+                  )}
+                />
+              )}
+
+              {!runsHelper && (
+                <FormField
+                  control={form.control}
+                  name="fail_closed"
+                  render={({ field: { value, onChange } }) => (
+                    <FormItem>
+                      <div className="flex flex-row items-center space-x-4 space-y-0">
+                        <FormControl>
+                          <Switch checked={value} onCheckedChange={onChange} />
+                        </FormControl>
+                        <FormLabel className="ml-2 text-sm text-foreground">
+                          Fail closed (kill switch)
+                        </FormLabel>
+                      </div>
+                      <FormDescription className="text-muted-foreground">
+                        Block the browser&apos;s traffic while this proxy is down
+                        and never let it go direct. The proxy is checked at
+                        least every 15 seconds while this is on.
+                      </FormDescription>
+                    </FormItem>
+                  )}
+                />
+              )}
+
+              {isEditMode &&
+                (proxy?.proxy_type === "pac" ||
+                  proxy?.proxy_type === "rules") && (
+                  <div className="space-y-2">
+                    <label className="text-sm font-medium text-foreground block">
+                      Preview
+                    </label>
+                    <div className="flex gap-2">
+                      <Input
+                        placeholder="https://www.example.com/"
+                        value={previewUrl}
+                        onChange={(e) => setPreviewUrl(e.target.value)}
+                      />
+                      <Button
+                        type="button"
+                        variant="outline"
+                        onClick={handlePreview}
+                      >
+                        Evaluate
+                      </Button>
+                    </div>
+                    {previewResult && (
+                      <div className="text-sm font-mono p-2 bg-muted rounded">
+                        {previewResult.entries.join(" → ") || "(empty result)"}
+                      </div>
+                    )}
+                    {previewError && (
+                      <div className="text-sm text-red-500">{previewError}</div>
+                    )}
+                    <p className="text-sm text-muted-foreground">
+                      Runs the saved PAC script for a URL
+                    </p>
+                  </div>
+                )}
             </div>
             <DialogFooter>
               <Button type="button" variant="outline" onClick={handleClose}>
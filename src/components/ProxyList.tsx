@@ -1,8 +1,71 @@
-import { ProxyConfig } from "@/types";
+import {
+  GeoLocation,
+  ProxyConfig,
+  ProxyHealth,
+  TrafficTotals,
+} from "@/types";
 import { Button } from "@/components/ui/button";
 import { Card, CardContent, CardHeader, CardTitle } from "@/components/ui/card";
 import { Badge } from "@/components/ui/badge";
-import { Server, Plus, Edit, Trash2, Rocket } from "lucide-react";
+import {
+  Server,
+  Plus,
+  Edit,
+  Trash2,
+  Rocket,
+  Globe,
+  ArrowUpDown,
+  RefreshCw,
+} from "lucide-react";
+import { useProxyHealth } from "@/hooks/useProxyHealth";
+import { useProxyUsage } from "@/hooks/useProxyUsage";
+import { TauriAPI } from "@/lib/tauri-api";
+
+// e.g. "Frankfurt, Germany · AS24940 Hetzner Online GmbH"
+const formatLocation = (geo: GeoLocation) =>
+  [
+    [geo.city, geo.country || geo.country_code].filter(Boolean).join(", "),
+    geo.asn &&
+      [`AS${geo.asn}`, geo.as_organization].filter(Boolean).join(" "),
+  ]
+    .filter(Boolean)
+    .join(" · ");
+
+const formatBytes = (bytes: number) => {
+  const units = ["B", "KB", "MB", "GB", "TB"];
+  let value = bytes;
+  let unit = 0;
+  while (value >= 1024 && unit < units.length - 1) {
+    value /= 1024;
+    unit++;
+  }
+  return `${unit === 0 ? value : value.toFixed(1)} ${units[unit]}`;
+};
+
+// e.g. "Today: 1.2 GB ↑ 310.5 MB ↓ · 42 connections"
+const formatUsage = (totals: TrafficTotals) =>
+  `Today: ${formatBytes(totals.bytes_up)} ↑ ${formatBytes(
+    totals.bytes_down
+  )} ↓ · ${totals.connections} connections`;
+
+const STATUS_BADGES: Record<
+  ProxyHealth["status"],
+  { label: string; variant: "success" | "warning" | "destructive" | "outline" }
+> = {
+  up: { label: "Up", variant: "success" },
+  degraded: { label: "Degraded", variant: "warning" },
+  down: { label: "Down", variant: "destructive" },
+  not_running: { label: "Not running", variant: "outline" },
+};
+
+// New circuits only apply to new connections, so open tabs may keep the old exit
+const requestNewIdentity = async (proxy: ProxyConfig) => {
+  try {
+    await TauriAPI.torNewIdentity(proxy.id);
+  } catch (error) {
+    alert(`Failed to request a new Tor identity: ${error}`);
+  }
+};
 
 interface ProxyListProps {
   proxies: ProxyConfig[];
@@ -19,6 +82,11 @@ export function ProxyList({
   onDelete,
   onAddProxy,
 }: ProxyListProps) {
+  const { data: healthList = [] } = useProxyHealth();
+  const { data: usage } = useProxyUsage();
+  // Totals are kept per local day; en-CA formats dates as YYYY-MM-DD
+  const today = new Date().toLocaleDateString("en-CA");
+
   return (
     <Card>
       <CardHeader>
@@ -26,50 +94,123 @@ export function ProxyList({
       </CardHeader>
       <CardContent>
         <div className="space-y-2">
-          {proxies.map((proxy) => (
-            <div
-              key={proxy.id}
-              className="flex items-center justify-between p-3 border rounded-lg hover:bg-accent/50 transition-colors"
-            >
-              <div className="flex-1">
-                <div className="flex items-center gap-2 mb-1">
-                  <Server className="h-4 w-4 text-muted-foreground" />
-                  <span className="font-medium">{proxy.name}</span>
+          {proxies.map((proxy) => {
+            const health = healthList.find((h) => h.proxy_id === proxy.id);
+            const todayUsage = usage?.daily.find(
+              (day) => day.date === today && day.proxy_id === proxy.id
+            );
+            return (
+              <div
+                key={proxy.id}
+                className="flex items-center justify-between p-3 border rounded-lg hover:bg-accent/50 transition-colors"
+              >
+                <div className="flex-1">
+                  <div className="flex items-center gap-2 mb-1">
+                    <Server className="h-4 w-4 text-muted-foreground" />
+                    <span className="font-medium">{proxy.name}</span>
+                    {health && (
+                      <Badge
+                        variant={STATUS_BADGES[health.status].variant}
+                        className="text-xs"
+                        title={health.message}
+                      >
+                        {STATUS_BADGES[health.status].label}
+                      </Badge>
+                    )}
+                  </div>
+                  <div className="text-sm text-muted-foreground">
+                    {proxy.proxy_type === "pac"
+                      ? `PAC: ${proxy.url || "No URL configured"}`
+                      : proxy.proxy_type === "chain"
+                      ? `Chain: ${(proxy.hops || [])
+                          .map(
+                            (hopId) =>
+                              proxies.find((p) => p.id === hopId)?.name || hopId
+                          )
+                          .join(" → ")}`
+                      : proxy.proxy_type === "rules"
+                      ? "Routed by PAC rules"
+                      : proxy.proxy_type === "ssh"
+                      ? `SSH: ${proxy.username ? `${proxy.username}@` : ""}${
+                          proxy.host
+                        }:${proxy.port}`
+                      : proxy.proxy_type === "tor"
+                      ? proxy.tor_mode === "launch"
+                        ? "Tor (launched per profile)"
+                        : `Tor: ${proxy.host}:${proxy.port}`
+                      : proxy.proxy_type === "helper"
+                      ? `Helper: ${proxy.command} → ${(
+                          proxy.endpoint_type || "socks5"
+                        ).toUpperCase()}://${proxy.host}:${
+                          proxy.port || "auto"
+                        }`
+                      : `${proxy.proxy_type.toUpperCase()}://${proxy.host}:${
+                          proxy.port
+                        }`}
+                    {proxy.username && (
+                      <Badge variant="outline" className="ml-2 text-xs">
+                        Auth
+                      </Badge>
+                    )}
+                    {proxy.fail_closed && (
+                      <Badge
+                        variant={
+                          health?.status === "down" ? "destructive" : "outline"
+                        }
+                        className="ml-2 text-xs"
+                      >
+                        {health?.status === "down" ? "Blocked" : "Kill switch"}
+                      </Badge>
+                    )}
+                  </div>
+                  {health?.exit_ip && (
+                    <div className="flex items-center gap-1 mt-1 text-xs text-muted-foreground">
+                      <Globe className="h-3 w-3" />
+                      <span>
+                        Exit IP {health.exit_ip}
+                        {health.geo && ` · ${formatLocation(health.geo)}`}
+                      </span>
+                    </div>
+                  )}
+                  {todayUsage && (
+                    <div className="flex items-center gap-1 mt-1 text-xs text-muted-foreground">
+                      <ArrowUpDown className="h-3 w-3" />
+                      <span>{formatUsage(todayUsage)}</span>
+                    </div>
+                  )}
                 </div>
-                <div className="text-sm text-muted-foreground">
-                  {proxy.proxy_type === "pac"
-                    ? `PAC: ${proxy.url || "No URL configured"}`
-                    : `${proxy.proxy_type.toUpperCase()}://${proxy.host}:${
-                        proxy.port
-                      }`}
-                  {proxy.username && (
-                    <Badge variant="outline" className="ml-2 text-xs">
-                      Auth
-                    </Badge>
+                <div className="flex items-center gap-1 ml-4">
+                  {proxy.proxy_type === "tor" && (
+                    <Button
+                      size="sm"
+                      variant="outline"
+                      title="New identity"
+                      onClick={() => requestNewIdentity(proxy)}
+                    >
+                      <RefreshCw className="h-4 w-4" />
+                    </Button>
                   )}
+                  <Button
+                    size="sm"
+                    variant="outline"
+                    onClick={() => onEdit(proxy)}
+                  >
+                    <Edit className="h-4 w-4" />
+                  </Button>
+                  <Button
+                    size="sm"
+                    variant="outline"
+                    onClick={() => onDelete(proxy.id)}
+                  >
+                    <Trash2 className="h-4 w-4" />
+                  </Button>
+                  <Button size="sm" onClick={() => onLaunch(proxy)}>
+                    <Rocket className="h-4 w-4" />
+                  </Button>
                 </div>
               </div>
-              <div className="flex items-center gap-1 ml-4">
-                <Button
-                  size="sm"
-                  variant="outline"
-                  onClick={() => onEdit(proxy)}
-                >
-                  <Edit className="h-4 w-4" />
-                </Button>
-                <Button
-                  size="sm"
-                  variant="outline"
-                  onClick={() => onDelete(proxy.id)}
-                >
-                  <Trash2 className="h-4 w-4" />
-                </Button>
-                <Button size="sm" onClick={() => onLaunch(proxy)}>
-                  <Rocket className="h-4 w-4" />
-                </Button>
-              </div>
-            </div>
-          ))}
+            );
+          })}
           <Button
             variant="ghost"
             className="w-full justify-start text-muted-foreground hover:text-foreground"
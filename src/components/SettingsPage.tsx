@@ -19,11 +19,12 @@ import {
   FormLabel,
   FormMessage,
 } from "@/components/ui/form";
-import { ArrowLeft, Folder } from "lucide-react";
+import { ArrowLeft, Folder, Trash2 } from "lucide-react";
 import { useState, useEffect } from "react";
 import { useTheme } from "@/contexts/ThemeContext";
 import { TauriAPI } from "@/lib/tauri-api";
 import { useSettingsPath } from "@/hooks/useSettings";
+import { PacRulesCard } from "@/components/PacRulesCard";
 import { useForm } from "react-hook-form";
 import { zodResolver } from "@hookform/resolvers/zod";
 import * as z from "zod";
@@ -36,6 +37,7 @@ const settingsFormSchema = z.object({
     .or(z.literal("")),
   ignore_cert_errors: z.boolean(),
   launch_on_startup: z.boolean(),
+  connection_log_to_file: z.boolean(),
 });
 
 type SettingsFormValues = z.infer<typeof settingsFormSchema>;
@@ -56,6 +58,9 @@ export function SettingsPage({
   onBrowserPathUpdated,
 }: SettingsPageProps) {
   const [currentBrowsers, setCurrentBrowsers] = useState(browsers);
+  const [geoipDatabases, setGeoipDatabases] = useState(
+    settings.geoip_databases || []
+  );
   const { theme, setTheme } = useTheme();
   const { data: settingsPath } = useSettingsPath();
 
@@ -66,6 +71,7 @@ export function SettingsPage({
       default_launch_url: settings.default_launch_url || "",
       ignore_cert_errors: settings.ignore_cert_errors || false,
       launch_on_startup: settings.launch_on_startup || false,
+      connection_log_to_file: settings.connection_log_to_file || false,
     },
   });
 
@@ -77,14 +83,22 @@ export function SettingsPage({
       default_launch_url: settings.default_launch_url || "",
       ignore_cert_errors: settings.ignore_cert_errors || false,
       launch_on_startup: settings.launch_on_startup || false,
+      connection_log_to_file: settings.connection_log_to_file || false,
     });
   }, [settings, form]);
 
+  useEffect(() => {
+    setGeoipDatabases(settings.geoip_databases || []);
+  }, [settings.geoip_databases]);
+
   // Update browsers list when prop changes
   useEffect(() => {
     setCurrentBrowsers(browsers);
   }, [browsers]);
 
+  const browserCommand = (browser: Browser) =>
+    [browser.path, ...(browser.args ?? [])].join(" ");
+
   const handleBrowseForBrowser = async (browserId: string) => {
     try {
       const selectedPath = await TauriAPI.browseForBrowserExecutable();
@@ -93,7 +107,15 @@ export function SettingsPage({
         setCurrentBrowsers((prevBrowsers) =>
           prevBrowsers.map((browser) =>
             browser.id === browserId
-              ? { ...browser, path: selectedPath }
+              ? {
+                  ...browser,
+                  path: selectedPath,
+                  args: [],
+                  // Read again for the new executable on the next startup
+                  version: undefined,
+                  channel: undefined,
+                  family: undefined,
+                }
               : browser
           )
         );
@@ -106,6 +128,18 @@ export function SettingsPage({
     }
   };
 
+  const handleAddGeoipDatabase = async () => {
+    try {
+      const selectedPath = await TauriAPI.browseOpenFile(["mmdb"]);
+      if (selectedPath && !geoipDatabases.includes(selectedPath)) {
+        setGeoipDatabases((prevDatabases) => [...prevDatabases, selectedPath]);
+      }
+    } catch (error) {
+      console.error("Failed to browse for GeoIP database:", error);
+      alert(`Failed to select GeoIP database: ${error}`);
+    }
+  };
+
   const handleExportSettings = async () => {
     try {
       const selectedPath = await TauriAPI.browseSaveFile(
@@ -132,6 +166,8 @@ export function SettingsPage({
           default_launch_url: importedSettings.default_launch_url || "",
           ignore_cert_errors: importedSettings.ignore_cert_errors || false,
           launch_on_startup: importedSettings.launch_on_startup || false,
+          connection_log_to_file:
+            importedSettings.connection_log_to_file || false,
         });
         // Also need to reload browser list
         if (onBrowserPathUpdated) {
@@ -153,6 +189,7 @@ export function SettingsPage({
       sites: settings.sites,
       proxies: settings.proxies,
       browsers: settings.browsers,
+      geoip_databases: geoipDatabases,
     };
     onSave(updatedSettings);
   };
@@ -228,10 +265,18 @@ export function SettingsPage({
                           {browser.name}:
                         </span>
                         <Input
-                          value={browser.path}
+                          value={browserCommand(browser)}
                           readOnly
                           className="flex-1"
                         />
+                        {browser.version && (
+                          <span className="text-xs text-muted-foreground">
+                            {browser.version}
+                            {browser.channel &&
+                              browser.channel !== "stable" &&
+                              ` (${browser.channel})`}
+                          </span>
+                        )}
                         <Button
                           type="button"
                           variant="outline"
@@ -287,7 +332,7 @@ export function SettingsPage({
                 {currentBrowsers.length === 0 && (
                   <div className="p-3 bg-yellow-50 dark:bg-yellow-900/20 border border-yellow-200 dark:border-yellow-800 rounded-md">
                     <p className="text-sm text-yellow-800 dark:text-yellow-200">
-                      No browsers detected. Please install Chrome or Edge, or
+                      No browsers detected. Please install Chrome, Edge or Firefox, or
                       manually configure browser paths.
                     </p>
                   </div>
@@ -336,9 +381,81 @@ export function SettingsPage({
                     </FormItem>
                   )}
                 />
+
+                <FormField
+                  control={form.control}
+                  name="connection_log_to_file"
+                  render={({ field: { value, onChange } }) => (
+                    <FormItem>
+                      <div className="flex flex-row items-center space-y-0">
+                        <FormControl>
+                          <Switch checked={value} onCheckedChange={onChange} />
+                        </FormControl>
+                        <FormLabel className="ml-2 text-sm text-foreground">
+                          Save connection log to file
+                        </FormLabel>
+                      </div>
+                      <FormDescription className="text-muted-foreground">
+                        Appends requests through local forwarders to
+                        logs/connections.log in the storage location, rotated
+                        at 5 MB.
+                      </FormDescription>
+                    </FormItem>
+                  )}
+                />
               </CardContent>
             </Card>
 
+            {/* Exit IP Location */}
+            <Card>
+              <CardHeader>
+                <CardTitle>Exit IP Location</CardTitle>
+              </CardHeader>
+              <CardContent className="space-y-4">
+                <p className="text-sm text-muted-foreground">
+                  MaxMind databases (.mmdb) used to show the country, city and
+                  ASN of each proxy&apos;s exit IP, such as GeoLite2-City and
+                  GeoLite2-ASN. Lookups never leave this machine.
+                </p>
+
+                <div className="space-y-2">
+                  {geoipDatabases.map((path) => (
+                    <div key={path} className="flex items-center gap-2">
+                      <Input value={path} readOnly className="flex-1" />
+                      <Button
+                        type="button"
+                        size="sm"
+                        variant="ghost"
+                        onClick={() =>
+                          setGeoipDatabases((prevDatabases) =>
+                            prevDatabases.filter((p) => p !== path)
+                          )
+                        }
+                        title="Remove database"
+                      >
+                        <Trash2 className="h-4 w-4" />
+                      </Button>
+                    </div>
+                  ))}
+                </div>
+
+                <Button
+                  type="button"
+                  variant="outline"
+                  onClick={handleAddGeoipDatabase}
+                >
+                  <Folder className="h-4 w-4 mr-2 text-current" />
+                  Add Database
+                </Button>
+              </CardContent>
+            </Card>
+
+            {/* PAC Rules */}
+            <PacRulesCard
+              rules={settings.pac_rules || []}
+              proxies={settings.proxies}
+            />
+
             {/* Data Management */}
             <Card>
               <CardHeader>
@@ -31,7 +31,7 @@ import {
   SelectValue,
 } from "@/components/ui/select";
 import { Badge } from "@/components/ui/badge";
-import { ProxyConfig, Browser, SiteConfig } from "@/types";
+import { ProxyConfig, ProxyGroup, Browser, SiteConfig } from "@/types";
 
 const siteFormSchema = z.object({
   name: z.string().min(1, "Site name is required").trim(),
@@ -49,6 +49,7 @@ interface SiteDialogProps {
   site?: SiteConfig;
   onSave: (siteId: string | undefined, site: Omit<SiteConfig, "id">) => void;
   proxies: ProxyConfig[];
+  proxyGroups: ProxyGroup[];
   browsers: Browser[];
   defaultBrowser?: string;
 }
@@ -60,6 +61,7 @@ export function SiteDialog({
   site,
   onSave,
   proxies,
+  proxyGroups,
   browsers,
   defaultBrowser,
 }: SiteDialogProps) {
@@ -179,6 +181,16 @@ export function SiteDialog({
                             {browser.name}
                           </SelectItem>
                         ))}
+                        {proxyGroups.map((group) => (
+                          <SelectItem key={group.id} value={group.id}>
+                            <div>
+                              <span className="mr-2"> {group.name}</span>
+                              <Badge variant="outline" className="text-xs">
+                                group
+                              </Badge>
+                            </div>
+                          </SelectItem>
+                        ))}
                       </SelectContent>
                     </Select>
                     <FormMessage />
@@ -212,10 +224,21 @@ export function SiteDialog({
                             </div>
                           </SelectItem>
                         ))}
+                        {proxyGroups.map((group) => (
+                          <SelectItem key={group.id} value={group.id}>
+                            <div>
+                              <span className="mr-2"> {group.name}</span>
+                              <Badge variant="outline" className="text-xs">
+                                group
+                              </Badge>
+                            </div>
+                          </SelectItem>
+                        ))}
                       </SelectContent>
                     </Select>
                     <FormDescription className="text-muted-foreground">
-                      Optional proxy configuration for this site.
+                      Optional proxy or proxy group for this site. Groups
+                      pick a member each time the site is launched.
                     </FormDescription>
                     <FormMessage />
                   </FormItem>
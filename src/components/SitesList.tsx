@@ -1,4 +1,4 @@
-import { SiteConfig, Browser, ProxyConfig } from "@/types";
+import { SiteConfig, Browser, ProxyConfig, ProxyGroup } from "@/types";
 import { Button } from "@/components/ui/button";
 import { Card, CardContent, CardHeader, CardTitle } from "@/components/ui/card";
 import { Badge } from "@/components/ui/badge";
@@ -8,6 +8,7 @@ interface SitesListProps {
   sites: SiteConfig[];
   browsers: Browser[];
   proxies: ProxyConfig[];
+  proxyGroups: ProxyGroup[];
   onLaunch: (site: SiteConfig) => void;
   onEdit: (site: SiteConfig) => void;
   onDelete: (siteId: string) => void;
@@ -18,6 +19,7 @@ export function SitesList({
   sites,
   browsers,
   proxies,
+  proxyGroups,
   onLaunch,
   onEdit,
   onDelete,
@@ -51,6 +53,7 @@ export function SitesList({
                   {site.proxy_id && (
                     <Badge variant="outline" className="text-xs">
                       {proxies.find((p) => p.id === site.proxy_id)?.name ||
+                        proxyGroups.find((g) => g.id === site.proxy_id)?.name ||
                         "Unknown Proxy"}
                     </Badge>
                   )}
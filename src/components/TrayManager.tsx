@@ -2,6 +2,7 @@
 
 import { useEffect, useRef, useState } from "react";
 import { useAppData } from "@/hooks/useAppData";
+import { useProxyHealth } from "@/hooks/useProxyHealth";
 import {
   TrayIcon,
   TrayIconEvent,
@@ -10,7 +11,7 @@ import {
 import { defaultWindowIcon } from "@tauri-apps/api/app";
 import { Menu, Submenu, MenuItem } from "@tauri-apps/api/menu";
 import { TauriAPI } from "@/lib/tauri-api";
-import { ProxyConfig, SiteConfig } from "@/types";
+import { ProxyConfig, ProxyHealth, SiteConfig } from "@/types";
 
 interface TrayManagerProps {
   children: React.ReactNode;
@@ -36,10 +37,19 @@ const clickHandler = (event: any) => {
   }
 };
 
+// Menu items are plain text, so the status badge becomes a suffix
+const STATUS_LABELS: Record<ProxyHealth["status"], string> = {
+  up: "Up",
+  degraded: "Degraded",
+  down: "Down",
+  not_running: "Not running",
+};
+
 export async function createContextMenu(
   tray: TrayIcon,
   sites: SiteConfig[],
-  proxies: ProxyConfig[]
+  proxies: ProxyConfig[],
+  healthList: ProxyHealth[]
 ) {
   const siteMenuItems = await Promise.all(
     sites.map((site) =>
@@ -54,15 +64,18 @@ export async function createContextMenu(
   );
 
   const proxyMenuItems = await Promise.all(
-    proxies.map((proxy) =>
-      MenuItem.new({
+    proxies.map((proxy) => {
+      const health = healthList.find((h) => h.proxy_id === proxy.id);
+      return MenuItem.new({
         id: proxy.id,
-        text: proxy.name,
+        text: health
+          ? `${proxy.name} · ${STATUS_LABELS[health.status]}`
+          : proxy.name,
         action: async () => {
           await TauriAPI.launchProxy(proxy.id);
         },
-      })
-    )
+      });
+    })
   );
 
   const launchSitesSubMenu = await Submenu.new({
@@ -92,6 +105,7 @@ export async function createContextMenu(
 
 export function TrayManager({ children }: TrayManagerProps) {
   const { sites, proxies } = useAppData();
+  const { data: healthList } = useProxyHealth();
   const [tray, setTray] = useState<TrayIcon | null>(null);
   const isCreatingTray = useRef(false);
 
@@ -140,9 +154,9 @@ export function TrayManager({ children }: TrayManagerProps) {
 
   useEffect(() => {
     if (tray) {
-      createContextMenu(tray, sites, proxies);
+      createContextMenu(tray, sites, proxies, healthList ?? []);
     }
-  }, [tray, sites, proxies]);
+  }, [tray, sites, proxies, healthList]);
 
   return <>{children}</>;
 }
@@ -15,12 +15,14 @@ export const useAppData = () => {
   }, [settings?.sites])
 
   const proxies = settings?.proxies || []
+  const proxyGroups = settings?.proxy_groups || []
 
   return {
     browsers,
     settings,
     sites,
     proxies,
+    proxyGroups,
     isLoading: browsersLoading || settingsLoading,
     error: browsersError || settingsError,
   }
@@ -0,0 +1,29 @@
+import { useEffect } from 'react'
+import { useQuery, useQueryClient } from '@tanstack/react-query'
+import { listen } from '@tauri-apps/api/event'
+import { TauriAPI } from '@/lib/tauri-api'
+import { ProxyHealth, ProxyStatusChanged } from '@/types'
+
+export const useProxyHealth = () => {
+  const queryClient = useQueryClient()
+
+  // Apply status changes as the monitor reports them instead of waiting for the next poll
+  useEffect(() => {
+    const unlisten = listen<ProxyStatusChanged>('proxy-status-changed', (event) => {
+      const { health } = event.payload
+      queryClient.setQueryData<ProxyHealth[]>(['proxy-health'], (list = []) => [
+        ...list.filter((h) => h.proxy_id !== health.proxy_id),
+        health,
+      ])
+    })
+    return () => {
+      unlisten.then((stop) => stop())
+    }
+  }, [queryClient])
+
+  return useQuery({
+    queryKey: ['proxy-health'],
+    queryFn: TauriAPI.getProxyHealth,
+    refetchInterval: 30 * 1000, // The monitor checks in the background
+  })
+}
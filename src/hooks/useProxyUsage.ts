@@ -0,0 +1,10 @@
+import { useQuery } from '@tanstack/react-query'
+import { TauriAPI } from '@/lib/tauri-api'
+
+export const useProxyUsage = () => {
+  return useQuery({
+    queryKey: ['proxy-usage'],
+    queryFn: () => TauriAPI.getProxyUsage(),
+    refetchInterval: 10 * 1000, // Running sessions keep counting
+  })
+}
@@ -1,8 +1,22 @@
+import { useEffect } from 'react'
+import { listen } from '@tauri-apps/api/event'
 import { useQuery, useMutation, useQueryClient } from '@tanstack/react-query'
 import { TauriAPI } from '@/lib/tauri-api'
 import { AppSettings } from '@/types'
 
 export const useSettings = () => {
+  const queryClient = useQueryClient()
+
+  // Browsers are detected and probed in the background after startup
+  useEffect(() => {
+    const unlisten = listen('browsers-updated', () => {
+      queryClient.invalidateQueries({ queryKey: ['settings'] })
+    })
+    return () => {
+      unlisten.then((stop) => stop())
+    }
+  }, [queryClient])
+
   return useQuery({
     queryKey: ['settings'],
     queryFn: TauriAPI.loadSettings,
@@ -23,6 +37,18 @@ export const useSaveSettings = () => {
   })
 }
 
+export const useSavePacRules = () => {
+  const queryClient = useQueryClient()
+
+  return useMutation({
+    mutationFn: TauriAPI.savePacRules,
+    onSuccess: () => {
+      // Rules are part of the settings
+      queryClient.invalidateQueries({ queryKey: ['settings'] })
+    },
+  })
+}
+
 export const useSettingsPath = () => {
   return useQuery({
     queryKey: ['settings-path'],
@@ -1,21 +1,41 @@
 import { invoke } from '@tauri-apps/api/core'
-import { Browser, AppSettings } from '@/types'
+import { Browser, AppSettings, ConnectionEntry, ConnectionLogFilter, DnsLeakReport, LaunchReport, PacEvaluation, PacRule, ProxyConfig, ProxyGroup, ProxyHealth, ProxyUsage } from '@/types'
 
 export interface TauriBrowser {
   id: string
   name: string
   path: string
+  args?: string[]
+  version?: string
+  channel?: string
+  family?: string
 }
 
 export interface TauriProxyConfig {
   id: string
   name: string
-  proxy_type: string // "http", "socks5", "pac"
-  host: string
-  port: number
+  proxy_type: string // "http", "https", "socks4", "socks4a", "socks5", "pac", "chain", "rules", "ssh", "helper", "tor"
+  host?: string // not set for PAC proxies
+  port?: number
   username?: string
   password?: string
   url?: string // PAC URL
+  identity_file?: string // SSH private key
+  known_hosts?: string // "strict", "accept_new" or "off"
+  command?: string // Helper program
+  args?: string[] // Helper arguments
+  readiness?: string // "port" or "output"
+  ready_pattern?: string
+  ready_timeout_secs?: number
+  endpoint_type?: string // "http" or "socks5"
+  tor_mode?: string // "existing" or "launch"
+  control_port?: number
+  control_password?: string
+  tor_path?: string
+  bypass?: string[] // --proxy-bypass-list rules
+  remote_dns?: boolean // SOCKS5 only
+  fail_closed?: boolean // Kill switch
+  hops?: string[] // Chain hop proxy IDs
 }
 
 export interface TauriSiteConfig {
@@ -32,9 +52,18 @@ export interface TauriAppSettings {
   theme: string
   launch_on_startup: boolean
   ignore_cert_errors: boolean
+  preflight_blocks_launch?: boolean
+  health_check_url?: string
+  health_check_interval_secs?: number
+  health_history_size?: number
   browsers: TauriBrowser[]
   proxies: TauriProxyConfig[]
   sites: TauriSiteConfig[]
+  proxy_groups?: ProxyGroup[]
+  pac_rules?: PacRule[]
+  geoip_databases?: string[]
+  connection_log_size?: number
+  connection_log_to_file?: boolean
 }
 
 export class TauriAPI {
@@ -45,6 +74,10 @@ export class TauriAPI {
         id: browser.id,
         name: browser.name,
         path: browser.path,
+        args: browser.args,
+        version: browser.version,
+        channel: browser.channel as Browser["channel"],
+        family: browser.family as Browser["family"],
       }))
     } catch (error) {
       console.error('Failed to detect browsers:', error)
@@ -52,38 +85,147 @@ export class TauriAPI {
     }
   }
 
-  static async launchSite(siteId: string): Promise<void> {
+  private static checkLaunchReport(report: LaunchReport): LaunchReport {
+    const warnings = report.checks.filter(check => check.status === 'warn')
+    warnings.forEach(check => console.warn(`Preflight ${check.name}: ${check.message}`))
+
+    if (!report.launched) {
+      const failures = report.checks
+        .filter(check => check.status === 'fail')
+        .map(check => check.message)
+      throw new Error(failures.join('\n') || 'Browser was not launched')
+    }
+    return report
+  }
+
+  static async launchSite(siteId: string): Promise<LaunchReport> {
     try {
-      await invoke('launch_site', { siteId })
+      const report = await invoke<LaunchReport>('launch_site', { siteId })
+      return TauriAPI.checkLaunchReport(report)
     } catch (error) {
       console.error('Failed to launch site:', error)
       throw error
     }
   }
 
-  static async launchProxy(proxyId: string): Promise<void> {
+  static async launchProxy(proxyId: string): Promise<LaunchReport> {
     try {
-      await invoke('launch_proxy', { proxyId })
+      const report = await invoke<LaunchReport>('launch_proxy', { proxyId })
+      return TauriAPI.checkLaunchReport(report)
     } catch (error) {
       console.error('Failed to launch proxy:', error)
       throw error
     }
   }
 
+  static async testProxy(proxyId: string): Promise<ProxyHealth> {
+    try {
+      return await invoke<ProxyHealth>('test_proxy', { proxyId })
+    } catch (error) {
+      console.error('Failed to test proxy:', error)
+      throw error
+    }
+  }
+
+  static async getProxyHealth(): Promise<ProxyHealth[]> {
+    try {
+      return await invoke<ProxyHealth[]>('get_proxy_health')
+    } catch (error) {
+      console.error('Failed to get proxy health:', error)
+      return []
+    }
+  }
+
+  static async getProxyHealthHistory(proxyId: string): Promise<ProxyHealth[]> {
+    try {
+      return await invoke<ProxyHealth[]>('get_proxy_health_history', { proxyId })
+    } catch (error) {
+      console.error('Failed to get proxy health history:', error)
+      return []
+    }
+  }
+
+  static async getProxyUsage(proxyId?: string): Promise<ProxyUsage> {
+    try {
+      return await invoke<ProxyUsage>('get_proxy_usage', { proxyId })
+    } catch (error) {
+      console.error('Failed to get proxy usage:', error)
+      return { daily: [], sessions: [] }
+    }
+  }
+
+  static async getConnectionLog(filter?: ConnectionLogFilter): Promise<ConnectionEntry[]> {
+    try {
+      return await invoke<ConnectionEntry[]>('get_connection_log', { filter })
+    } catch (error) {
+      console.error('Failed to get connection log:', error)
+      return []
+    }
+  }
+
+  static async clearConnectionLog(): Promise<void> {
+    try {
+      await invoke('clear_connection_log')
+    } catch (error) {
+      console.error('Failed to clear connection log:', error)
+      throw error
+    }
+  }
+
+  static async torNewIdentity(proxyId: string): Promise<void> {
+    try {
+      await invoke('tor_new_identity', { proxyId })
+    } catch (error) {
+      console.error('Failed to request a new Tor identity:', error)
+      throw error
+    }
+  }
+
+  static async dnsLeakTest(proxyId: string): Promise<DnsLeakReport> {
+    try {
+      return await invoke<DnsLeakReport>('dns_leak_test', { proxyId })
+    } catch (error) {
+      console.error('Failed to run DNS leak test:', error)
+      throw error
+    }
+  }
+
+  static async getPacRules(): Promise<PacRule[]> {
+    try {
+      return await invoke<PacRule[]>('get_pac_rules')
+    } catch (error) {
+      console.error('Failed to get PAC rules:', error)
+      throw error
+    }
+  }
+
+  static async savePacRules(rules: PacRule[]): Promise<void> {
+    try {
+      await invoke('save_pac_rules', { rules })
+    } catch (error) {
+      console.error('Failed to save PAC rules:', error)
+      throw error
+    }
+  }
+
+  static async evaluatePac(proxyId: string, url: string): Promise<PacEvaluation> {
+    try {
+      return await invoke<PacEvaluation>('evaluate_pac', { proxyId, url })
+    } catch (error) {
+      console.error('Failed to evaluate PAC script:', error)
+      throw error
+    }
+  }
+
   static async loadSettings(): Promise<AppSettings> {
     try {
       const settings = await invoke<TauriAppSettings>('load_settings')
       return {
-        default_browser: settings.default_browser,
-        default_launch_url: settings.default_launch_url,
+        ...settings,
         theme: settings.theme as "light" | "dark" | "system",
-        launch_on_startup: settings.launch_on_startup,
-        ignore_cert_errors: settings.ignore_cert_errors,
-        browsers: settings.browsers,
-        sites: settings.sites,
         proxies: settings.proxies.map(proxy => ({
           ...proxy,
-          proxy_type: proxy.proxy_type as "http" | "socks5" | "pac",
+          proxy_type: proxy.proxy_type as ProxyConfig["proxy_type"],
         })),
       }
     } catch (error) {
@@ -96,15 +238,9 @@ export class TauriAPI {
     try {
       console.log('Saving settings via TauriAPI:', settings);
 
+      // Spread the settings so backend-only fields survive a round trip
       const tauriSettings: TauriAppSettings = {
-        default_browser: settings.default_browser,
-        default_launch_url: settings.default_launch_url,
-        theme: settings.theme,
-        launch_on_startup: settings.launch_on_startup,
-        ignore_cert_errors: settings.ignore_cert_errors,
-        browsers: settings.browsers,
-        proxies: settings.proxies,
-        sites: settings.sites,
+        ...settings,
       }
 
       console.log('Final Tauri settings to be sent:', tauriSettings);
@@ -186,16 +322,11 @@ export class TauriAPI {
     try {
       const settings = await invoke<TauriAppSettings>('import_settings', { filePath })
       return {
-        default_browser: settings.default_browser,
-        default_launch_url: settings.default_launch_url,
+        ...settings,
         theme: settings.theme as "light" | "dark" | "system",
-        launch_on_startup: settings.launch_on_startup,
-        ignore_cert_errors: settings.ignore_cert_errors,
-        browsers: settings.browsers,
-        sites: settings.sites,
         proxies: settings.proxies.map(proxy => ({
           ...proxy,
-          proxy_type: proxy.proxy_type as "http" | "socks5" | "pac",
+          proxy_type: proxy.proxy_type as ProxyConfig["proxy_type"],
         })),
       }
     } catch (error) {
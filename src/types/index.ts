@@ -1,12 +1,51 @@
 export interface ProxyConfig {
   id: string;
   name: string;
-  proxy_type: "http" | "socks5" | "pac";
-  host: string;
-  port: number;
+  proxy_type:
+    | "http"
+    | "https"
+    | "socks4"
+    | "socks4a"
+    | "socks5"
+    | "pac"
+    | "chain"
+    | "rules"
+    | "ssh"
+    | "helper"
+    | "tor";
+  host?: string; // not set for PAC proxies
+  port?: number;
   username?: string;
   password?: string;
   url?: string; // PAC URL
+  identity_file?: string; // SSH private key
+  known_hosts?: "strict" | "accept_new" | "off"; // SSH host key checking
+  command?: string; // Helper program
+  args?: string[]; // Helper arguments, {port} is replaced by the port
+  readiness?: "port" | "output"; // How to tell the helper is ready
+  ready_pattern?: string; // Regex matched against the helper's output
+  ready_timeout_secs?: number;
+  endpoint_type?: "http" | "socks5"; // Proxy the helper serves
+  tor_mode?: "existing" | "launch"; // Use a running Tor or start one per profile
+  control_port?: number; // Tor control port, for new identities
+  control_password?: string; // Tor HashedControlPassword
+  tor_path?: string; // tor binary to launch
+  bypass?: string[]; // --proxy-bypass-list rules
+  remote_dns?: boolean; // SOCKS5 only
+  fail_closed?: boolean; // Kill switch: block traffic while the proxy is down
+  hops?: string[]; // Chain hop proxy IDs, in order
+}
+
+export interface PacRule {
+  pattern: string; // domain glob like *.example.com or CIDR like 10.0.0.0/8
+  proxy_id?: string; // DIRECT when not set
+}
+
+export interface ProxyGroup {
+  id: string;
+  name: string;
+  strategy: "round_robin" | "random" | "least_latency" | "failover";
+  members: string[]; // Proxy IDs, in failover order
 }
 
 export interface SiteConfig {
@@ -14,13 +53,17 @@ export interface SiteConfig {
   name: string;
   url: string;
   browser_id: string;
-  proxy_id?: string;
+  proxy_id?: string; // a proxy or a proxy group
 }
 
 export interface Browser {
   id: string;
   name: string;
   path: string;
+  args?: string[]; // Launcher arguments, e.g. ["run", "com.google.Chrome"]
+  version?: string; // Read on detection and on every startup
+  channel?: "stable" | "beta" | "dev" | "canary" | "esr";
+  family?: "chromium" | "firefox" | "other";
 }
 
 export interface AppSettings {
@@ -29,7 +72,118 @@ export interface AppSettings {
   theme: "light" | "dark" | "system";
   launch_on_startup: boolean;
   ignore_cert_errors: boolean;
+  preflight_blocks_launch?: boolean;
+  health_check_url?: string;
+  health_check_interval_secs?: number;
+  health_history_size?: number;
   browsers: Browser[];
   sites: SiteConfig[];
   proxies: ProxyConfig[];
+  proxy_groups?: ProxyGroup[];
+  pac_rules?: PacRule[];
+  geoip_databases?: string[];
+  connection_log_size?: number;
+  connection_log_to_file?: boolean;
+}
+
+export interface GeoLocation {
+  country_code?: string;
+  country?: string;
+  city?: string;
+  asn?: number;
+  as_organization?: string;
+}
+
+export interface ProxyHealth {
+  proxy_id: string;
+  status: "up" | "degraded" | "down" | "not_running";
+  reachable: boolean;
+  connect_ms?: number;
+  ttfb_ms?: number;
+  exit_ip?: string;
+  geo?: GeoLocation; // From the configured GeoIP databases
+  failure?:
+    | "auth_failed"
+    | "connection_refused"
+    | "timeout"
+    | "unreachable"
+    | "bad_response"
+    | "config";
+  message?: string;
+  hops?: { proxy_id: string; latency_ms?: number }[]; // Chains only
+  checked_at: string;
+}
+
+export interface ProxyStatusChanged {
+  proxy_id: string;
+  previous?: ProxyHealth["status"];
+  health: ProxyHealth;
+}
+
+export interface PreflightCheck {
+  name: string;
+  status: "pass" | "warn" | "fail";
+  message: string;
+}
+
+export interface LaunchReport {
+  launched: boolean;
+  checks: PreflightCheck[];
+  proxy_id?: string; // the proxy used, a group member for groups
+}
+
+export interface TrafficTotals {
+  bytes_up: number; // Sent to the upstream proxy
+  bytes_down: number;
+  connections: number;
+}
+
+export interface DailyUsage extends TrafficTotals {
+  date: string; // YYYY-MM-DD, local time
+  proxy_id: string;
+}
+
+export interface SessionUsage extends TrafficTotals {
+  profile: string;
+  proxy_id: string;
+  started_at: string;
+}
+
+export interface ProxyUsage {
+  daily: DailyUsage[];
+  sessions: SessionUsage[]; // Running sessions only
+}
+
+export interface ConnectionEntry extends TrafficTotals {
+  id: number;
+  started_at: string;
+  profile: string;
+  proxy_id: string;
+  method: string; // CONNECT for tunnels
+  target: string; // host:port
+  upstream: string; // e.g. "a:8080 -> b:1080"
+  status?: number; // 200/502 for tunnels, the origin's status for plain HTTP
+  duration_ms: number;
+  error?: string;
+}
+
+export interface ConnectionLogFilter {
+  proxy_id?: string;
+  profile?: string;
+  host?: string;
+  failed_only?: boolean;
+  since?: string;
+  limit?: number;
+}
+
+export interface PacEvaluation {
+  url: string;
+  result: string; // FindProxyForURL return value
+  entries: string[]; // e.g. ["PROXY host:8080", "DIRECT"]
+}
+
+export interface DnsLeakReport {
+  passed: boolean;
+  probes: { hostname: string; resolved_remotely: boolean }[];
+  local_lookups: string[];
 }